@@ -0,0 +1,688 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use filetime::FileTime;
+
+use crate::file_manager::FileType;
+
+/// Archive formats that can be browsed as directories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl ArchiveKind {
+    /// Detects the kind of archive from the name of `path`, or returns
+    /// `None` when it doesn't look like a supported archive.
+    pub fn detect<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let name = path.as_ref().file_name()?.to_string_lossy().to_lowercase();
+        let kinds = [
+            (".zip", ArchiveKind::Zip),
+            (".jar", ArchiveKind::Zip),
+            (".tar", ArchiveKind::Tar),
+            (".tar.gz", ArchiveKind::TarGz),
+            (".tgz", ArchiveKind::TarGz),
+            (".tar.xz", ArchiveKind::TarXz),
+            (".txz", ArchiveKind::TarXz),
+            (".tar.zst", ArchiveKind::TarZst),
+            (".tzst", ArchiveKind::TarZst),
+        ];
+        kinds
+            .into_iter()
+            .find(|(extension, _)| name.ends_with(extension))
+            .map(|(_, kind)| kind)
+    }
+}
+
+/// A single item inside of an archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveEntry {
+    /// Path of the entry relative to the root of the archive
+    pub path: PathBuf,
+    pub file_type: FileType,
+    /// Uncompressed size in bytes. For directories this is the size of
+    /// everything inside of them
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Where the bytes of an archive come from. Archives nested inside of
+/// other archives are kept in memory.
+#[derive(Clone)]
+enum Source {
+    File(PathBuf),
+    Memory(Arc<Vec<u8>>),
+}
+
+struct SharedBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// An archive opened for browsing. The index of every entry is read once
+/// when the archive is opened.
+pub struct Archive {
+    path: PathBuf,
+    kind: ArchiveKind,
+    source: Source,
+    entries: BTreeMap<PathBuf, ArchiveEntry>,
+}
+
+impl fmt::Debug for Archive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Archive")
+            .field("path", &self.path)
+            .field("kind", &self.kind)
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+impl Archive {
+    /// Opens the archive file at `path`.
+    pub fn open<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        Self::with_source(path, Source::File(path.to_path_buf()))
+    }
+
+    /// Opens an archive from its contents, `path` is only used to detect
+    /// its kind and identify it. Used to browse archives nested inside of
+    /// other archives.
+    pub fn from_bytes<P>(path: P, bytes: Vec<u8>) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_source(path.as_ref(), Source::Memory(Arc::new(bytes)))
+    }
+
+    fn with_source(path: &Path, source: Source) -> anyhow::Result<Self> {
+        let kind = ArchiveKind::detect(path)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a supported archive", path.display()))?;
+        let mut archive = Archive {
+            path: path.to_path_buf(),
+            kind,
+            source,
+            entries: BTreeMap::new(),
+        };
+        archive.read_index()?;
+        Ok(archive)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn kind(&self) -> ArchiveKind {
+        self.kind
+    }
+
+    /// Returns the entry at `path`, relative to the root of the archive.
+    pub fn entry<P>(&self, path: P) -> Option<&ArchiveEntry>
+    where
+        P: AsRef<Path>,
+    {
+        self.entries.get(path.as_ref())
+    }
+
+    /// Lists the direct children of the directory at `dir`, relative to
+    /// the root of the archive. An empty path lists the root.
+    pub fn list<P>(&self, dir: P) -> Vec<&ArchiveEntry>
+    where
+        P: AsRef<Path>,
+    {
+        self.entries
+            .values()
+            .filter(|entry| entry.path.parent() == Some(dir.as_ref()))
+            .collect()
+    }
+
+    /// Reads the whole contents of the file at `path`.
+    pub fn read<P>(&self, path: P) -> anyhow::Result<Vec<u8>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        match self.entry(path) {
+            Some(entry) if entry.file_type == FileType::File => (),
+            _ => anyhow::bail!("{} is not a file inside of the archive", path.display()),
+        }
+
+        let mut contents = vec![];
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(self.reader()?)?;
+                for index in 0..zip.len() {
+                    let mut file = zip.by_index(index)?;
+                    if file.enclosed_name().and_then(|p| normalize(&p)).as_deref() == Some(path) {
+                        file.read_to_end(&mut contents)?;
+                        break;
+                    }
+                }
+            }
+            _ => {
+                let mut tar = self.tar()?;
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+                    if normalize(&entry.path()?).as_deref() == Some(path) {
+                        contents.clear();
+                        entry.read_to_end(&mut contents)?;
+                    }
+                }
+            }
+        }
+        Ok(contents)
+    }
+
+    /// Extracts the entries at `paths`, recursively for directories, into
+    /// the `dest` directory. Each entry is placed directly inside of `dest`,
+    /// keeping only its own name and not the directories containing it.
+    ///
+    /// Nothing is extracted when any of the entries already exists on
+    /// `dest`.
+    pub fn extract<P, Q>(&self, paths: &[P], dest: Q) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let dest = dest.as_ref();
+        let mut roots = vec![];
+        for path in paths {
+            let path = path.as_ref();
+            let entry = self
+                .entry(path)
+                .ok_or_else(|| anyhow::anyhow!("{} is not in the archive", path.display()))?;
+            let target = dest.join(path.file_name().unwrap_or_default());
+            if std::fs::symlink_metadata(&target).is_ok() {
+                anyhow::bail!("{} already exists", target.display());
+            }
+            roots.push((path.to_path_buf(), target, entry.file_type.clone()));
+        }
+        for (_, target, file_type) in roots.iter() {
+            if *file_type == FileType::Directory {
+                std::fs::create_dir_all(target)?;
+            }
+        }
+        let roots = roots.into_iter().map(|(root, ..)| root).collect::<Vec<_>>();
+        // the target of an entry, or `None` when it wasn't selected
+        let target_of = |path: &Path| {
+            let root = roots.iter().find(|root| path.starts_with(root))?;
+            let base = root.parent().unwrap_or(Path::new(""));
+            Some(dest.join(path.strip_prefix(base).ok()?))
+        };
+
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(self.reader()?)?;
+                for index in 0..zip.len() {
+                    let mut file = zip.by_index(index)?;
+                    let Some(path) = file.enclosed_name().and_then(|p| normalize(&p)) else {
+                        continue;
+                    };
+                    let Some(target) = target_of(&path) else {
+                        continue;
+                    };
+                    check_parents(dest, &target)?;
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    if file.is_dir() {
+                        std::fs::create_dir_all(&target)?;
+                        continue;
+                    }
+                    if file.is_symlink() {
+                        let mut link = String::new();
+                        file.read_to_string(&mut link)?;
+                        check_link(dest, &target, Path::new(&link))?;
+                        crate::ops::symlink(Path::new(&link), &target)?;
+                        continue;
+                    }
+                    // a new file is never opened through a link left there
+                    let mut output = File::options().write(true).create_new(true).open(&target)?;
+                    std::io::copy(&mut file, &mut output)?;
+                    set_permissions(&target, file.unix_mode())?;
+                    if let Some(modified) = file.last_modified().and_then(zip_time) {
+                        filetime::set_file_mtime(&target, FileTime::from_system_time(modified))?;
+                    }
+                }
+            }
+            _ => {
+                let mut tar = self.tar()?;
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+                    let Some(path) = normalize(&entry.path()?) else {
+                        continue;
+                    };
+                    let Some(target) = target_of(&path) else {
+                        continue;
+                    };
+                    check_parents(dest, &target)?;
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    let kind = entry.header().entry_type();
+                    if kind.is_symlink() || kind.is_hard_link() {
+                        let link = entry.link_name()?.unwrap_or_default().into_owned();
+                        if kind.is_symlink() {
+                            check_link(dest, &target, &link)?;
+                            crate::ops::symlink(&link, &target)?;
+                            continue;
+                        }
+                        // hard links name another entry of the archive,
+                        // which has to be extracted with this one
+                        let original = normalize(&link).and_then(|link| target_of(&link));
+                        let original = original.ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "{} links to {}, which is not extracted",
+                                target.display(),
+                                link.display()
+                            )
+                        })?;
+                        check_parents(dest, &original)?;
+                        std::fs::hard_link(original, &target)?;
+                        continue;
+                    }
+                    entry.unpack(&target)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn reader(&self) -> anyhow::Result<Box<dyn ReadSeek>> {
+        Ok(match &self.source {
+            Source::File(path) => Box::new(File::open(path)?),
+            Source::Memory(bytes) => Box::new(Cursor::new(SharedBytes(bytes.clone()))),
+        })
+    }
+
+    fn tar(&self) -> anyhow::Result<tar::Archive<Box<dyn Read>>> {
+        let reader = self.reader()?;
+        let reader: Box<dyn Read> = match self.kind {
+            ArchiveKind::Tar => Box::new(reader),
+            ArchiveKind::TarGz => Box::new(flate2::read::GzDecoder::new(reader)),
+            ArchiveKind::TarXz => Box::new(xz2::read::XzDecoder::new(reader)),
+            ArchiveKind::TarZst => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            ArchiveKind::Zip => unreachable!("zip archives are not tar streams"),
+        };
+        Ok(tar::Archive::new(reader))
+    }
+
+    fn read_index(&mut self) -> anyhow::Result<()> {
+        let mut entries = vec![];
+        match self.kind {
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(self.reader()?)?;
+                for index in 0..zip.len() {
+                    let file = zip.by_index(index)?;
+                    let Some(path) = file.enclosed_name().and_then(|p| normalize(&p)) else {
+                        continue;
+                    };
+                    let file_type = match (file.is_dir(), file.is_symlink()) {
+                        (true, _) => FileType::Directory,
+                        (_, true) => FileType::Symlink,
+                        _ => FileType::File,
+                    };
+                    entries.push(ArchiveEntry {
+                        path,
+                        file_type,
+                        size: file.size(),
+                        modified: file.last_modified().and_then(zip_time),
+                    });
+                }
+            }
+            _ => {
+                let mut tar = self.tar()?;
+                for entry in tar.entries()? {
+                    let entry = entry?;
+                    let Some(path) = normalize(&entry.path()?) else {
+                        continue;
+                    };
+                    let header = entry.header();
+                    let file_type = match header.entry_type() {
+                        t if t.is_dir() => FileType::Directory,
+                        t if t.is_symlink() => FileType::Symlink,
+                        _ => FileType::File,
+                    };
+                    entries.push(ArchiveEntry {
+                        path,
+                        file_type,
+                        size: header.size()?,
+                        modified: header
+                            .mtime()
+                            .ok()
+                            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                    });
+                }
+            }
+        }
+
+        for entry in entries {
+            // archives don't always have entries for every directory, so
+            // the missing ones are filled in
+            for ancestor in entry.path.ancestors().skip(1) {
+                if ancestor.as_os_str().is_empty() || self.entries.contains_key(ancestor) {
+                    continue;
+                }
+                let dir = ArchiveEntry {
+                    path: ancestor.to_path_buf(),
+                    file_type: FileType::Directory,
+                    size: 0,
+                    modified: None,
+                };
+                self.entries.insert(dir.path.clone(), dir);
+            }
+            // later entries replace earlier ones with the same path, just
+            // like they would when extracting the whole archive
+            self.entries.insert(entry.path.clone(), entry);
+        }
+
+        let sizes = self
+            .entries
+            .values()
+            .filter(|entry| entry.file_type == FileType::File)
+            .map(|entry| (entry.path.clone(), entry.size))
+            .collect::<Vec<_>>();
+        for entry in self.entries.values_mut() {
+            if entry.file_type == FileType::Directory {
+                entry.size = sizes
+                    .iter()
+                    .filter(|(path, _)| path.starts_with(&entry.path))
+                    .map(|(_, size)| size)
+                    .sum();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Turns a path stored in an archive into a relative path, or `None` when
+/// it would escape the archive or refers to its root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir | Component::RootDir => (),
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+/// Fails when an item between `dest` and `target` is a symbolic link, as
+/// extracting through it would write outside of `dest`.
+fn check_parents(dest: &Path, target: &Path) -> anyhow::Result<()> {
+    for parent in target
+        .ancestors()
+        .skip(1)
+        .take_while(|parent| *parent != dest)
+    {
+        if std::fs::symlink_metadata(parent).is_ok_and(|metadata| metadata.is_symlink()) {
+            anyhow::bail!(
+                "{} is a symbolic link, not extracting through it",
+                parent.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Fails when the symbolic link `target`, pointing to `link`, would point
+/// outside of `dest`. Absolute links are always refused.
+fn check_link(dest: &Path, target: &Path, link: &Path) -> anyhow::Result<()> {
+    let error = || {
+        anyhow::anyhow!(
+            "{} links to {}, outside of {}",
+            target.display(),
+            link.display(),
+            dest.display()
+        )
+    };
+    let location = target.strip_prefix(dest)?.parent().unwrap_or(Path::new(""));
+    let mut resolved = location.to_path_buf();
+    for component in link.components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => (),
+            Component::ParentDir if resolved.pop() => (),
+            _ => return Err(error()),
+        }
+    }
+    Ok(())
+}
+
+/// Converts the MS-DOS timestamp stored on zip files, which has no time
+/// zone, assuming it is UTC.
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    let (year, month, day) = (time.year() as i64, time.month() as i64, time.day() as i64);
+    // days since the epoch for a proleptic gregorian date
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs =
+        days * 86400 + time.hour() as i64 * 3600 + time.minute() as i64 * 60 + time.second() as i64;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+#[cfg(unix)]
+fn set_permissions(path: &Path, mode: Option<u32>) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    match mode {
+        Some(mode) => {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_permissions(_path: &Path, _mode: Option<u32>) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::{tempdir, TempDir};
+    use zip::write::SimpleFileOptions;
+
+    fn zip_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn setup_tempdir() -> TempDir {
+        let dir = tempdir().expect("failed to create tempdir");
+        let nested = zip_bytes(&[("inner.txt", b"inner")]);
+        let zip = zip_bytes(&[
+            ("docs/readme.md", b"readme"),
+            ("docs/guide/intro.md", b"intro"),
+            ("nested.zip", &nested),
+        ]);
+        std::fs::write(dir.path().join("archive.zip"), zip).unwrap();
+
+        let file = File::create(dir.path().join("archive.tar.gz")).unwrap();
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_mtime(1000);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "./src/main.rs", &b"hello"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_detect_kind() {
+        assert_eq!(ArchiveKind::detect("a.ZIP"), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::detect("a.tar.gz"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::detect("a.tzst"), Some(ArchiveKind::TarZst));
+        assert_eq!(ArchiveKind::detect("a.gz"), None);
+    }
+
+    #[test]
+    fn test_list_zip() {
+        let dir = setup_tempdir();
+
+        let archive = Archive::open(dir.path().join("archive.zip")).expect("failed to open");
+
+        let root = archive
+            .list("")
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            root,
+            vec![PathBuf::from("docs"), PathBuf::from("nested.zip")]
+        );
+        let docs = archive.entry("docs").unwrap();
+        assert_eq!(docs.file_type, FileType::Directory);
+        assert_eq!(docs.size, 11);
+        assert_eq!(archive.list("docs/guide").len(), 1);
+    }
+
+    #[test]
+    fn test_list_tar_gz() {
+        let dir = setup_tempdir();
+
+        let archive = Archive::open(dir.path().join("archive.tar.gz")).expect("failed to open");
+
+        let entry = archive.entry("src/main.rs").unwrap();
+        assert_eq!(entry.size, 5);
+        assert_eq!(entry.modified, Some(UNIX_EPOCH + Duration::from_secs(1000)));
+        assert_eq!(archive.read("src/main.rs").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_nested_archive() {
+        let dir = setup_tempdir();
+        let archive = Archive::open(dir.path().join("archive.zip")).expect("failed to open");
+
+        let bytes = archive.read("nested.zip").expect("failed to read");
+        let nested = Archive::from_bytes(dir.path().join("archive.zip/nested.zip"), bytes)
+            .expect("failed to open nested archive");
+
+        assert_eq!(nested.read("inner.txt").unwrap(), b"inner");
+    }
+
+    #[test]
+    fn test_extract() {
+        let dir = setup_tempdir();
+        let archive = Archive::open(dir.path().join("archive.zip")).expect("failed to open");
+        let dest = dir.path().join("out");
+        std::fs::create_dir(&dest).unwrap();
+
+        archive
+            .extract(&["docs/guide", "docs/readme.md"], &dest)
+            .expect("failed to extract");
+
+        assert_eq!(
+            std::fs::read_to_string(dest.join("guide/intro.md")).unwrap(),
+            "intro"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("readme.md")).unwrap(),
+            "readme"
+        );
+        assert!(archive.extract(&["docs/readme.md"], &dest).is_err());
+    }
+
+    #[test]
+    fn test_extract_never_writes_through_links() {
+        let dir = setup_tempdir();
+        let victim = dir.path().join("victim");
+        std::fs::create_dir(&victim).unwrap();
+        let mut builder = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "top/link", &victim)
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "top/link/pwned", &b"pwned"[..])
+            .unwrap();
+        let bytes = builder.into_inner().unwrap();
+        let archive = Archive::from_bytes(dir.path().join("evil.tar"), bytes).unwrap();
+        let dest = dir.path().join("out");
+        std::fs::create_dir(&dest).unwrap();
+
+        let error = archive.extract(&["top"], &dest).unwrap_err();
+
+        assert!(error.to_string().contains("outside of"));
+        assert!(!victim.join("pwned").exists());
+    }
+
+    #[test]
+    fn test_extract_refuses_links_outside_of_destination() {
+        let dir = setup_tempdir();
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default();
+        writer
+            .add_symlink("top/up", "../../outside", options)
+            .unwrap();
+        writer
+            .add_symlink("top/inner", "./sibling", options)
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let archive = Archive::from_bytes(dir.path().join("evil.zip"), bytes).unwrap();
+        let dest = dir.path().join("out");
+        std::fs::create_dir(&dest).unwrap();
+
+        let error = archive.extract(&["top/up"], &dest).unwrap_err();
+        archive
+            .extract(&["top/inner"], &dest)
+            .expect("failed to extract");
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{} links to ../../outside, outside of {}",
+                dest.join("up").display(),
+                dest.display()
+            )
+        );
+        assert!(std::fs::symlink_metadata(dest.join("inner")).is_ok());
+    }
+}
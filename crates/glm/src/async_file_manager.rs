@@ -0,0 +1,585 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::async_fs_ops::AsyncFsOps;
+use crate::bulk_rename::BulkEdit;
+use crate::file_manager::FileManager;
+use crate::fs_ops::FsOps;
+use crate::list_state::ListState;
+use crate::mode::ModeChange;
+use crate::plan::Plan;
+use crate::rename_pattern::RenamePattern;
+use crate::timestamps::{TimeSpec, Timestamps};
+use crate::vfs::{LocalFs, Vfs};
+
+/// The async counterpart of [`FileManager`], doing every operation on the
+/// blocking pool of tokio.
+///
+/// Clones share the same file manager, and operations run one at a time
+/// in the order they are polled. States are returned by value, as they
+/// may be replaced by another clone at any time.
+#[derive(Debug)]
+pub struct AsyncFileManager<B = LocalFs>
+where
+    B: Vfs + Send + 'static,
+{
+    inner: Arc<Mutex<FileManager<B>>>,
+}
+
+impl<B> Clone for AsyncFileManager<B>
+where
+    B: Vfs + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        AsyncFileManager {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<B> From<FileManager<B>> for AsyncFileManager<B>
+where
+    B: Vfs + Send + 'static,
+{
+    fn from(file_manager: FileManager<B>) -> Self {
+        AsyncFileManager {
+            inner: Arc::new(Mutex::new(file_manager)),
+        }
+    }
+}
+
+impl<B> AsyncFsOps<ListState> for AsyncFileManager<B>
+where
+    B: Vfs + Send + 'static,
+{
+    fn change_dir<P>(
+        &self,
+        path: P,
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.change_dir(path).cloned())
+    }
+
+    fn is_symlink<P>(&self, path: P) -> impl Future<Output = anyhow::Result<bool>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.is_symlink(path))
+    }
+
+    fn is_dir<P>(&self, path: P) -> impl Future<Output = anyhow::Result<bool>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.is_dir(path))
+    }
+
+    fn is_file<P>(&self, path: P) -> impl Future<Output = anyhow::Result<bool>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.is_file(path))
+    }
+}
+
+impl AsyncFileManager<LocalFs> {
+    /// Creates a file manager for the local file system, listing `path`.
+    pub fn new<T>(
+        path: T,
+    ) -> impl Future<Output = anyhow::Result<AsyncFileManager>> + Send + 'static
+    where
+        T: AsRef<Path>,
+    {
+        AsyncFileManager::with_backend(LocalFs, path)
+    }
+}
+
+impl<B> AsyncFileManager<B>
+where
+    B: Vfs + Send + 'static,
+{
+    /// Creates a file manager for any backend, listing `path`.
+    pub fn with_backend<T>(
+        backend: B,
+        path: T,
+    ) -> impl Future<Output = anyhow::Result<Self>> + Send + 'static
+    where
+        T: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        async move {
+            let fm = tokio::task::spawn_blocking(move || FileManager::with_backend(backend, path))
+                .await??;
+            Ok(AsyncFileManager::from(fm))
+        }
+    }
+
+    /// Runs `op` with exclusive access to the file manager on the blocking
+    /// pool, for anything without a dedicated method.
+    pub fn run<T, F>(&self, op: F) -> impl Future<Output = anyhow::Result<T>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut FileManager<B>) -> anyhow::Result<T> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        async move {
+            tokio::task::spawn_blocking(move || {
+                let mut fm = inner
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("file manager panicked on another task"))?;
+                op(&mut fm)
+            })
+            .await?
+        }
+    }
+
+    /// Returns a copy of the current state.
+    pub fn get_state(&self) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static {
+        self.run(|fm| Ok(fm.get_state().clone()))
+    }
+
+    pub fn toggle_hidden(
+        &self,
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static {
+        self.run(|fm| fm.toggle_hidden().cloned())
+    }
+
+    /// Re-fetches every item on the current directory, producing a new state.
+    pub fn refresh(&self) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static {
+        self.run(|fm| fm.refresh().cloned())
+    }
+
+    /// See [`FileManager::trash`].
+    pub fn trash<P>(
+        &self,
+        paths: &[P],
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.trash(&paths).cloned())
+    }
+
+    /// See [`FileManager::reveal_link_target`].
+    pub fn reveal_link_target<P>(
+        &self,
+        path: P,
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.reveal_link_target(path).cloned())
+    }
+
+    /// See [`FileManager::create_file`].
+    pub fn create_file<P>(
+        &self,
+        path: P,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.create_file(path, dry_run))
+    }
+
+    /// See [`FileManager::create_file_with`].
+    pub fn create_file_with<P>(
+        &self,
+        path: P,
+        contents: Vec<u8>,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.create_file_with(path, &contents, dry_run))
+    }
+
+    /// See [`FileManager::create_dir`].
+    pub fn create_dir<P>(
+        &self,
+        path: P,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.create_dir(path, dry_run))
+    }
+
+    /// See [`FileManager::rename`].
+    pub fn rename<P, Q>(
+        &self,
+        from: P,
+        to: Q,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref().to_path_buf(), to.as_ref().to_path_buf());
+        self.run(move |fm| fm.rename(from, to, dry_run))
+    }
+
+    /// See [`FileManager::move_to`].
+    pub fn move_to<P, Q>(
+        &self,
+        paths: &[P],
+        dest: Q,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        let dest = dest.as_ref().to_path_buf();
+        self.run(move |fm| fm.move_to(&paths, dest, dry_run))
+    }
+
+    /// See [`FileManager::copy_to`].
+    pub fn copy_to<P, Q>(
+        &self,
+        paths: &[P],
+        dest: Q,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        let dest = dest.as_ref().to_path_buf();
+        self.run(move |fm| fm.copy_to(&paths, dest, dry_run))
+    }
+
+    /// See [`FileManager::delete`].
+    pub fn delete<P>(
+        &self,
+        paths: &[P],
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.delete(&paths, dry_run))
+    }
+
+    /// See [`FileManager::mkdir`].
+    pub fn mkdir<P>(
+        &self,
+        path: P,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.mkdir(path, dry_run))
+    }
+
+    /// See [`FileManager::bulk_rename`].
+    pub fn bulk_rename(
+        &self,
+        edit: BulkEdit,
+        edited: String,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static {
+        self.run(move |fm| fm.bulk_rename(&edit, &edited, dry_run))
+    }
+
+    /// See [`FileManager::pattern_rename`].
+    pub fn pattern_rename<P>(
+        &self,
+        paths: &[P],
+        pattern: RenamePattern,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.pattern_rename(&paths, &pattern, dry_run))
+    }
+
+    /// See [`FileManager::symlink`].
+    pub fn symlink<T, L>(
+        &self,
+        target: T,
+        link: L,
+        relative: bool,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        T: AsRef<Path>,
+        L: AsRef<Path>,
+    {
+        let (target, link) = (target.as_ref().to_path_buf(), link.as_ref().to_path_buf());
+        self.run(move |fm| fm.symlink(target, link, relative, dry_run))
+    }
+
+    /// See [`FileManager::hardlink`].
+    pub fn hardlink<T, L>(
+        &self,
+        target: T,
+        link: L,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        T: AsRef<Path>,
+        L: AsRef<Path>,
+    {
+        let (target, link) = (target.as_ref().to_path_buf(), link.as_ref().to_path_buf());
+        self.run(move |fm| fm.hardlink(target, link, dry_run))
+    }
+
+    /// See [`FileManager::change_mode`].
+    pub fn change_mode<P>(
+        &self,
+        paths: &[P],
+        change: ModeChange,
+        recursive: bool,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.change_mode(&paths, &change, recursive, dry_run))
+    }
+
+    /// See [`FileManager::chown`].
+    pub fn chown<P>(
+        &self,
+        paths: &[P],
+        user: Option<String>,
+        group: Option<String>,
+        recursive: bool,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| {
+            fm.chown(
+                &paths,
+                user.as_deref(),
+                group.as_deref(),
+                recursive,
+                dry_run,
+            )
+        })
+    }
+
+    /// See [`FileManager::touch`].
+    pub fn touch<P>(
+        &self,
+        paths: &[P],
+        time: TimeSpec,
+        timestamps: Timestamps,
+        recursive: bool,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.touch(&paths, &time, timestamps, recursive, dry_run))
+    }
+
+    /// See [`FileManager::chmod`].
+    pub fn chmod<P>(
+        &self,
+        paths: &[P],
+        mode: u32,
+        dry_run: bool,
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.chmod(&paths, mode, dry_run))
+    }
+}
+
+#[cfg(feature = "archive")]
+impl<B> AsyncFileManager<B>
+where
+    B: Vfs + Send + 'static,
+{
+    /// See [`FileManager::open_archive`].
+    pub fn open_archive<P>(
+        &self,
+        path: P,
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        self.run(move |fm| fm.open_archive(path).cloned())
+    }
+
+    /// Returns the path of the outermost archive being browsed, if any.
+    pub fn archive_root(
+        &self,
+    ) -> impl Future<Output = anyhow::Result<Option<PathBuf>>> + Send + 'static {
+        self.run(|fm| Ok(fm.archive_root().map(Path::to_path_buf)))
+    }
+
+    /// See [`FileManager::extract`].
+    pub fn extract<P, Q>(
+        &self,
+        paths: &[P],
+        dest: Q,
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        let dest = dest.as_ref().to_path_buf();
+        self.run(move |fm| fm.extract(&paths, dest).cloned())
+    }
+}
+
+#[cfg(feature = "git")]
+impl<B> AsyncFileManager<B>
+where
+    B: Vfs + Send + 'static,
+{
+    pub fn git_stage<P>(
+        &self,
+        paths: &[P],
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.git_stage(&paths).cloned())
+    }
+
+    pub fn git_unstage<P>(
+        &self,
+        paths: &[P],
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.git_unstage(&paths).cloned())
+    }
+
+    pub fn git_discard<P>(
+        &self,
+        paths: &[P],
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.git_discard(&paths).cloned())
+    }
+
+    pub fn git_discard_plan<P>(
+        &self,
+        paths: &[P],
+    ) -> impl Future<Output = anyhow::Result<Plan>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.git_discard_plan(&paths))
+    }
+
+    pub fn git_ignore<P>(
+        &self,
+        paths: &[P],
+    ) -> impl Future<Output = anyhow::Result<ListState>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let paths = to_owned(paths);
+        self.run(move |fm| fm.git_ignore(&paths).cloned())
+    }
+}
+
+fn to_owned<P>(paths: &[P]) -> Vec<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    paths
+        .iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_manager::FileType;
+    use crate::vfs::MemFs;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_change_dir() {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(dir.path().join("nested/a.txt"), "a").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+
+        let sut = AsyncFileManager::new(dir.path())
+            .await
+            .expect("failed to create file manager");
+        let state = sut
+            .change_dir(dir.path().join("nested"))
+            .await
+            .expect("failed to change dir");
+
+        assert_eq!(state.items.len(), 1);
+        assert_eq!(state.items[0].file_name, "a.txt");
+        assert_eq!(
+            sut.get_state().await.unwrap().current_dir,
+            state.current_dir
+        );
+        assert!(sut.is_dir(dir.path()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_clones_share_state() {
+        let fs = MemFs::new()
+            .with_file("/project/src/main.rs", "fn main() {}")
+            .and_then(|fs| fs.with_symlink("/project/main", "src/main.rs"))
+            .expect("failed to set up memory file system");
+        let sut = AsyncFileManager::with_backend(fs, "/project")
+            .await
+            .expect("failed to create file manager");
+
+        let other = sut.clone();
+        other.change_dir("/project/src").await.unwrap();
+
+        let state = sut.get_state().await.unwrap();
+        assert_eq!(state.current_dir, PathBuf::from("/project/src"));
+        assert_eq!(
+            sut.get_file_type("/project/main").await.unwrap(),
+            FileType::Symlink
+        );
+        assert!(!sut.run(|fm| Ok(fm.backend().is_local())).await.unwrap());
+    }
+}
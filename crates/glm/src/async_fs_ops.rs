@@ -0,0 +1,55 @@
+use std::future::Future;
+use std::path::Path;
+
+use crate::file_manager::FileType;
+
+/// The async counterpart of [`crate::FsOps`].
+///
+/// Every operation returns a future that does its I/O on the blocking pool
+/// of tokio, so awaiting it never blocks the runtime. Futures own their
+/// arguments, which lets them be spawned as tasks of their own.
+pub trait AsyncFsOps<S> {
+    /// Lists the contents of `path`, producing a new state. Check specific
+    /// implementations for how they behave.
+    fn change_dir<P>(&self, path: P) -> impl Future<Output = anyhow::Result<S>> + Send + 'static
+    where
+        P: AsRef<Path>;
+
+    /// Testes whether a given path is a symbolic link.
+    fn is_symlink<P>(&self, path: P) -> impl Future<Output = anyhow::Result<bool>> + Send + 'static
+    where
+        P: AsRef<Path>;
+
+    /// Testes whether a given path represents a directory.
+    fn is_dir<P>(&self, path: P) -> impl Future<Output = anyhow::Result<bool>> + Send + 'static
+    where
+        P: AsRef<Path>;
+
+    /// Testes whether a given path represents a regular file
+    fn is_file<P>(&self, path: P) -> impl Future<Output = anyhow::Result<bool>> + Send + 'static
+    where
+        P: AsRef<Path>;
+
+    /// Returns the `FileType` of a given path, see
+    /// [`crate::FsOps::get_file_type`].
+    fn get_file_type<P>(
+        &self,
+        path: P,
+    ) -> impl Future<Output = anyhow::Result<FileType>> + Send + 'static
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let is_symlink = self.is_symlink(path.clone());
+        let is_dir = self.is_dir(path);
+        async move {
+            if is_symlink.await? {
+                Ok(FileType::Symlink)
+            } else if is_dir.await? {
+                Ok(FileType::Directory)
+            } else {
+                Ok(FileType::File)
+            }
+        }
+    }
+}
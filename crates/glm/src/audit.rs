@@ -0,0 +1,252 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+use crate::config::state_dir;
+use crate::plan::PlannedOp;
+use crate::timestamps::format_time;
+
+/// Name of the file being appended to, rotated files get a number suffix.
+const FILE_NAME: &str = "audit.jsonl";
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_KEEP: usize = 5;
+
+/// An append-only log with one JSON line per mutating operation, recording
+/// what was done and whether it worked.
+///
+/// Once the log grows past its maximum size it's renamed to
+/// `audit.jsonl.1`, shifting older logs up to the number of logs kept.
+/// Clones append to the same file.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    dir: PathBuf,
+    max_size: u64,
+    keep: usize,
+    /// Serializes writes and rotations within this process
+    lock: Arc<Mutex<()>>,
+}
+
+impl AuditLog {
+    /// Creates a log in `dir`, which is created on the first record.
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_size: DEFAULT_MAX_SIZE,
+            keep: DEFAULT_KEEP,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Creates a log in the state directory of glm, see [`crate::state_dir`].
+    pub fn in_state_dir() -> anyhow::Result<Self> {
+        let dir = state_dir()
+            .ok_or_else(|| anyhow::anyhow!("neither XDG_STATE_HOME nor HOME are set"))?;
+        Ok(Self::new(dir))
+    }
+
+    /// Rotates the log once it's larger than `max_size` bytes, keeping
+    /// `keep` rotated logs besides the current one.
+    pub fn with_rotation(mut self, max_size: u64, keep: usize) -> Self {
+        self.max_size = max_size;
+        self.keep = keep;
+        self
+    }
+
+    /// Path of the file currently appended to.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(FILE_NAME)
+    }
+
+    /// Appends the outcome of `op`.
+    pub fn record<T>(&self, op: &PlannedOp, result: &anyhow::Result<T>) -> anyhow::Result<()> {
+        let (name, source, destination) = match op {
+            PlannedOp::Rename { from, to } => ("rename", from, Some(to)),
+            PlannedOp::Copy { from, to } => ("copy", from, Some(to)),
+            PlannedOp::Delete { path } => ("trash", path, None),
+            PlannedOp::CreateDir { path } => ("mkdir", path, None),
+            PlannedOp::CreateFile { path, .. } => ("create", path, None),
+            PlannedOp::Chmod { path, .. } => ("chmod", path, None),
+            PlannedOp::Symlink { target, link } => ("symlink", target, Some(link)),
+            PlannedOp::HardLink { target, link } => ("hardlink", target, Some(link)),
+            PlannedOp::Chown { path, .. } => ("chown", path, None),
+            PlannedOp::SetTimes { path, .. } => ("touch", path, None),
+        };
+        let mut entry = entry(name, source, destination.map(PathBuf::as_path), result);
+        match op {
+            PlannedOp::Chmod { from, to, .. } => {
+                entry["mode"] = json!({ "from": format!("{from:o}"), "to": format!("{to:o}") });
+            }
+            PlannedOp::Chown { from, to, .. } => {
+                let owner = |(uid, gid): &(u32, u32)| format!("{uid}:{gid}");
+                entry["owner"] = json!({ "from": owner(from), "to": owner(to) });
+            }
+            PlannedOp::SetTimes {
+                accessed, modified, ..
+            } => {
+                let time = |time: &Option<SystemTime>| time.map(format_time);
+                entry["times"] = json!({ "accessed": time(accessed), "modified": time(modified) });
+            }
+            _ => (),
+        }
+        self.append(&entry)
+    }
+
+    /// Appends the outcome of an operation that isn't part of a plan, like
+    /// a git command or a transfer to another host.
+    pub fn record_other<T>(
+        &self,
+        op: &str,
+        source: &Path,
+        destination: Option<&Path>,
+        result: &anyhow::Result<T>,
+    ) -> anyhow::Result<()> {
+        self.append(&entry(op, source, destination, result))
+    }
+
+    fn append(&self, entry: &Value) -> anyhow::Result<()> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| anyhow::anyhow!("audit log panicked on another thread"))?;
+        let mut line = entry.to_string();
+        line.push('\n');
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path();
+        let size = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(line.as_bytes())
+            .map_err(|e| anyhow::anyhow!("failed to write audit log {}: {e}", path.display()))
+    }
+
+    /// Shifts every rotated log up by one, dropping the oldest.
+    fn rotate(&self) -> anyhow::Result<()> {
+        let rotated = |n: usize| self.dir.join(format!("{FILE_NAME}.{n}"));
+        if self.keep == 0 {
+            fs::remove_file(self.path())?;
+            return Ok(());
+        }
+        match fs::remove_file(rotated(self.keep)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => (),
+        }
+        for n in (1..self.keep).rev() {
+            if rotated(n).exists() {
+                fs::rename(rotated(n), rotated(n + 1))?;
+            }
+        }
+        fs::rename(self.path(), rotated(1))?;
+        Ok(())
+    }
+}
+
+/// Records the outcome of `op` in `audit`, when there is one, passing
+/// `result` through.
+pub fn audited<T>(
+    audit: Option<&AuditLog>,
+    op: &PlannedOp,
+    result: anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    if let Some(audit) = audit {
+        audit.record(op, &result)?;
+    }
+    result
+}
+
+fn entry<T>(
+    op: &str,
+    source: &Path,
+    destination: Option<&Path>,
+    result: &anyhow::Result<T>,
+) -> Value {
+    let mut entry = json!({
+        "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        "op": op,
+        "source": source.to_string_lossy(),
+    });
+    if let Some(destination) = destination {
+        entry["destination"] = json!(destination.to_string_lossy());
+    }
+    entry["result"] = json!(match result {
+        Ok(_) => "ok",
+        Err(_) => "error",
+    });
+    entry["error"] = json!(result.as_ref().err().map(|e| e.to_string()));
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn read_lines(path: &Path) -> Vec<Value> {
+        fs::read_to_string(path)
+            .expect("failed to read audit log")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("failed to parse audit line"))
+            .collect()
+    }
+
+    #[test]
+    fn test_record() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let sut = AuditLog::new(dir.path().join("state"));
+
+        let rename = PlannedOp::Rename {
+            from: "/tmp/a.txt".into(),
+            to: "/tmp/b.txt".into(),
+        };
+        sut.record(&rename, &Ok(())).unwrap();
+        let chmod = PlannedOp::Chmod {
+            path: "/tmp/b.txt".into(),
+            from: 0o644,
+            to: 0o600,
+        };
+        sut.record(&chmod, &Err::<(), _>(anyhow::anyhow!("permission denied")))
+            .unwrap();
+
+        let lines = read_lines(&sut.path());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["op"], "rename");
+        assert_eq!(lines[0]["source"], "/tmp/a.txt");
+        assert_eq!(lines[0]["destination"], "/tmp/b.txt");
+        assert_eq!(lines[0]["result"], "ok");
+        assert!(lines[0]["error"].is_null());
+        assert!(lines[0]["timestamp"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(lines[1]["result"], "error");
+        assert_eq!(lines[1]["error"], "permission denied");
+        assert_eq!(lines[1]["mode"]["to"], "600");
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let sut = AuditLog::new(dir.path()).with_rotation(200, 2);
+
+        for i in 0..10 {
+            let op = PlannedOp::Delete {
+                path: format!("/tmp/{i}.txt").into(),
+            };
+            sut.record(&op, &Ok(())).unwrap();
+        }
+
+        let current = read_lines(&sut.path());
+        assert_eq!(current.last().unwrap()["source"], "/tmp/9.txt");
+        assert!(dir.path().join("audit.jsonl.1").exists());
+        assert!(dir.path().join("audit.jsonl.2").exists());
+        assert!(!dir.path().join("audit.jsonl.3").exists());
+        assert!(fs::metadata(sut.path()).unwrap().len() <= 200);
+    }
+}
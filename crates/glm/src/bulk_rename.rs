@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use path_absolutize::Absolutize;
+
+use crate::plan::PlannedOp;
+
+const HEADER: &str = "\
+# Edit the names to rename, or add a directory to move (e.g. `old/name`).
+# Delete a line to trash its item, add a line without a number to create a
+# file, ending with / for a directory. Keep the numbers as they are.
+";
+
+/// Items written to a text buffer, one name per line behind a numeric id,
+/// to be renamed by editing the buffer, like `vidir` does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkEdit {
+    /// Directory names are relative to
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+}
+
+/// A change made by editing the buffer of a [`BulkEdit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkChange {
+    Rename { from: PathBuf, to: PathBuf },
+    Delete(PathBuf),
+    Create { path: PathBuf, is_dir: bool },
+}
+
+impl BulkEdit {
+    /// Fails when a name can't be written on a single line.
+    pub fn new<P: AsRef<Path>>(dir: P, paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+        if let Some(path) = paths
+            .iter()
+            .find(|path| path.as_os_str().to_string_lossy().contains('\n'))
+        {
+            anyhow::bail!(
+                "{} has a line break in its name, rename it on its own",
+                path.display()
+            );
+        }
+        Ok(BulkEdit {
+            dir: dir.as_ref().to_path_buf(),
+            paths,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The text to edit, a header followed by `<id>\t<name>` lines.
+    pub fn buffer(&self) -> String {
+        let width = self.paths.len().to_string().len();
+        let mut buffer = HEADER.to_string();
+        for (id, path) in self.paths.iter().enumerate() {
+            let name = path.strip_prefix(&self.dir).unwrap_or(path);
+            buffer.push_str(&format!("{:0width$}\t{}\n", id + 1, name.display()));
+        }
+        buffer
+    }
+
+    /// Compares the `edited` buffer with the original one. Items whose line
+    /// is gone are deleted, and lines without an id are created.
+    pub fn diff(&self, edited: &str) -> anyhow::Result<Vec<BulkChange>> {
+        let mut seen = HashSet::new();
+        let mut changes = vec![];
+        for (number, line) in edited.lines().enumerate() {
+            let error = |message: String| anyhow::anyhow!("line {}: {message}", number + 1);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, name) = match line.split_once('\t') {
+                Some((id, name)) if id.chars().all(|c| c.is_ascii_digit()) => (Some(id), name),
+                _ => (None, line),
+            };
+            let name = name.trim_end_matches('\r');
+            if name.trim().is_empty() {
+                return Err(error("the name is empty".into()));
+            }
+            let Some(id) = id else {
+                changes.push(BulkChange::Create {
+                    path: self.resolve(name)?,
+                    is_dir: name.ends_with('/'),
+                });
+                continue;
+            };
+            let index = id
+                .parse::<usize>()
+                .ok()
+                .filter(|id| (1..=self.paths.len()).contains(id))
+                .ok_or_else(|| error(format!("unknown id {id}")))?
+                - 1;
+            if !seen.insert(index) {
+                return Err(error(format!("id {id} is used more than once")));
+            }
+            let from = &self.paths[index];
+            let to = self.resolve(name)?;
+            if &to != from {
+                changes.push(BulkChange::Rename {
+                    from: from.clone(),
+                    to,
+                });
+            }
+        }
+        for (index, path) in self.paths.iter().enumerate() {
+            if !seen.contains(&index) {
+                changes.push(BulkChange::Delete(path.clone()));
+            }
+        }
+        Ok(changes)
+    }
+
+    fn resolve(&self, name: &str) -> anyhow::Result<PathBuf> {
+        Ok(Path::new(name).absolutize_from(&self.dir)?.to_path_buf())
+    }
+}
+
+/// Orders renames so no item is renamed onto another one that hasn't been
+/// renamed away yet. Swaps and longer cycles go through a temporary name,
+/// picked next to the item among the names `is_vacant` accepts.
+pub(crate) fn order_renames<F>(renames: Vec<(PathBuf, PathBuf)>, is_vacant: F) -> Vec<PlannedOp>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut pending = renames;
+    let mut ops = vec![];
+    while !pending.is_empty() {
+        let sources = pending
+            .iter()
+            .map(|(from, _)| from.clone())
+            .collect::<HashSet<_>>();
+        let (ready, blocked) = pending
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, to)| !sources.contains(to));
+        if ready.is_empty() {
+            // every item left is part of a cycle, moving one of them out
+            // of the way frees the name the next one is waiting for
+            let mut blocked = blocked;
+            let (from, to) = blocked.remove(0);
+            let temp = temporary_name(&from, &is_vacant, &sources);
+            ops.push(PlannedOp::Rename {
+                from,
+                to: temp.clone(),
+            });
+            blocked.push((temp, to));
+            pending = blocked;
+            continue;
+        }
+        ops.extend(
+            ready
+                .into_iter()
+                .map(|(from, to)| PlannedOp::Rename { from, to }),
+        );
+        pending = blocked;
+    }
+    ops
+}
+
+fn temporary_name<F>(path: &Path, is_vacant: &F, taken: &HashSet<PathBuf>) -> PathBuf
+where
+    F: Fn(&Path) -> bool,
+{
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    (0..)
+        .map(|n| match n {
+            0 => path.with_file_name(format!(".{name}.glm-rename")),
+            n => path.with_file_name(format!(".{name}.glm-rename-{n}")),
+        })
+        .find(|temp| !taken.contains(temp) && is_vacant(temp))
+        .expect("there is always a vacant name")
+}
+
+/// Finds targets used by more than one change.
+pub(crate) fn find_collision(changes: &[BulkChange]) -> Option<(&Path, &Path, &Path)> {
+    let mut targets: HashMap<&Path, &Path> = HashMap::new();
+    for change in changes {
+        let (source, target) = match change {
+            BulkChange::Rename { from, to } => (from.as_path(), to.as_path()),
+            BulkChange::Create { path, .. } => (path.as_path(), path.as_path()),
+            BulkChange::Delete(_) => continue,
+        };
+        if let Some(other) = targets.insert(target, source) {
+            return Some((target, other, source));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit() -> BulkEdit {
+        let paths = ["a", "b", "c"].map(|name| PathBuf::from("/dir").join(name));
+        BulkEdit::new("/dir", paths.to_vec()).expect("failed to create edit")
+    }
+
+    #[test]
+    fn test_diff() {
+        let sut = edit();
+        assert!(sut.buffer().ends_with("1\ta\n2\tb\n3\tc\n"));
+
+        let changes = sut
+            .diff("# header\n2\tsub/b2\n1\ta\nnew.txt\nnew dir/\n")
+            .expect("failed to diff");
+        assert_eq!(
+            changes,
+            [
+                BulkChange::Rename {
+                    from: "/dir/b".into(),
+                    to: "/dir/sub/b2".into()
+                },
+                BulkChange::Create {
+                    path: "/dir/new.txt".into(),
+                    is_dir: false
+                },
+                BulkChange::Create {
+                    path: "/dir/new dir".into(),
+                    is_dir: true
+                },
+                BulkChange::Delete("/dir/c".into()),
+            ]
+        );
+
+        let error = sut.diff("1\ta\n1\tb\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: id 1 is used more than once");
+        let error = sut.diff("\n9\tz\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: unknown id 9");
+        let error = sut.diff("1\t \n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: the name is empty");
+    }
+
+    #[test]
+    fn test_order_renames() {
+        let rename = |from: &str, to: &str| (PathBuf::from(from), PathBuf::from(to));
+        let op = |from: &str, to: &str| PlannedOp::Rename {
+            from: from.into(),
+            to: to.into(),
+        };
+
+        // a chain runs from its end, without temporary names
+        let ops = order_renames(vec![rename("/a", "/b"), rename("/b", "/c")], |_| true);
+        assert_eq!(ops, [op("/b", "/c"), op("/a", "/b")]);
+
+        // a swap, with the first temporary name taken
+        let ops = order_renames(vec![rename("/a", "/b"), rename("/b", "/a")], |path| {
+            path != Path::new("/.a.glm-rename")
+        });
+        assert_eq!(
+            ops,
+            [
+                op("/a", "/.a.glm-rename-1"),
+                op("/b", "/a"),
+                op("/.a.glm-rename-1", "/b")
+            ]
+        );
+
+        let changes = edit().diff("1\tz\n2\tz\n3\tc\n").expect("failed to diff");
+        assert_eq!(
+            find_collision(&changes),
+            Some((
+                Path::new("/dir/z"),
+                Path::new("/dir/a"),
+                Path::new("/dir/b")
+            ))
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use sha2::Digest;
+
+/// Size of the blocks files are read in, progress is reported after each.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// A hash function to compute checksums with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Blake3,
+    Md5,
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Md5 => "md5",
+        }
+    }
+
+    /// Conventional name of a manifest of this algorithm, the one written
+    /// by `sha256sum`, `b3sum` and `md5sum` users.
+    pub fn manifest_name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "SHA256SUMS",
+            HashAlgorithm::Blake3 => "B3SUMS",
+            HashAlgorithm::Md5 => "MD5SUMS",
+        }
+    }
+
+    /// Detects the algorithm of a manifest from its name, like
+    /// `SHA256SUMS`, `release.sha256` or `B3SUMS.txt`.
+    pub fn detect<P: AsRef<Path>>(manifest: P) -> Option<Self> {
+        let name = manifest
+            .as_ref()
+            .file_name()?
+            .to_string_lossy()
+            .to_lowercase();
+        let algorithm = |part: &str| match part {
+            "sha256" | "sha256sum" | "sha256sums" => Some(HashAlgorithm::Sha256),
+            "b3" | "b3sum" | "b3sums" | "blake3" => Some(HashAlgorithm::Blake3),
+            "md5" | "md5sum" | "md5sums" => Some(HashAlgorithm::Md5),
+            _ => None,
+        };
+        name.split('.').find_map(algorithm)
+    }
+
+    fn hasher(&self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Md5 => Hasher::Md5(md5::Md5::new()),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(HashAlgorithm::Sha256),
+            "blake3" | "b3" => Ok(HashAlgorithm::Blake3),
+            "md5" => Ok(HashAlgorithm::Md5),
+            _ => anyhow::bail!("unknown hash algorithm {s}, use sha256, blake3 or md5"),
+        }
+    }
+}
+
+enum Hasher {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Md5(md5::Md5),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => _ = hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The hash as lowercase hex, like every `*sum` tool prints it.
+    fn finalize(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Hasher::Md5(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+/// The hash of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub path: PathBuf,
+    /// Lowercase hex
+    pub hash: String,
+}
+
+/// Progress of files being hashed, reported after every block read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumProgress {
+    /// Bytes already hashed
+    pub done: u64,
+    pub total: u64,
+}
+
+/// A file listed on a manifest whose contents changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Result of checking every file of a manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Files whose hash matches the manifest
+    pub verified: Vec<PathBuf>,
+    pub mismatched: Vec<Mismatch>,
+    /// Files listed on the manifest that don't exist anymore
+    pub missing: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Hashes a single file.
+pub fn hash_file<P: AsRef<Path>>(path: P, algorithm: HashAlgorithm) -> anyhow::Result<String> {
+    hash_with_progress(path.as_ref(), algorithm, |_| Ok(()))
+}
+
+/// Hashes every file in `paths`, recursively for directories, spreading
+/// the files over one thread per core.
+///
+/// Checksums come back sorted by path. Symbolic links inside of
+/// directories are skipped, as their targets may be anywhere, while links
+/// given in `paths` are followed.
+pub fn checksum<P, F>(
+    paths: &[P],
+    algorithm: HashAlgorithm,
+    mut progress: F,
+) -> anyhow::Result<Vec<Checksum>>
+where
+    P: AsRef<Path>,
+    F: FnMut(ChecksumProgress),
+{
+    let files = collect_files(paths)?;
+    let total = files.iter().map(|(_, size)| size).sum();
+    let paths = files.into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+    let hashes = hash_in_parallel(&paths, algorithm, total, &mut progress)?;
+    Ok(paths
+        .into_iter()
+        .zip(hashes)
+        .map(|(path, hash)| Checksum { path, hash })
+        .collect())
+}
+
+/// Writes `checksums` to `manifest`, in the format of `sha256sum` so it can
+/// also be checked with `sha256sum -c`. Paths are written relative to the
+/// directory of the manifest, so every checksum must be inside of it. An
+/// older version of the manifest that was hashed along is left out.
+pub fn write_manifest<P: AsRef<Path>>(manifest: P, checksums: &[Checksum]) -> anyhow::Result<()> {
+    let manifest = manifest.as_ref();
+    let dir = manifest.parent().unwrap_or(Path::new(""));
+    let mut contents = String::new();
+    for checksum in checksums.iter().filter(|c| c.path != manifest) {
+        let path = checksum.path.strip_prefix(dir).map_err(|_| {
+            anyhow::anyhow!(
+                "{} is not inside of {}",
+                checksum.path.display(),
+                dir.display()
+            )
+        })?;
+        contents.push_str(&format!("{}  {}\n", checksum.hash, path.display()));
+    }
+    std::fs::write(manifest, contents)
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {e}", manifest.display()))
+}
+
+/// Checks every file listed on `manifest`, relative to its directory. The
+/// algorithm is detected from the name of the manifest unless one is given.
+pub fn verify_manifest<P, F>(
+    manifest: P,
+    algorithm: Option<HashAlgorithm>,
+    mut progress: F,
+) -> anyhow::Result<VerifyReport>
+where
+    P: AsRef<Path>,
+    F: FnMut(ChecksumProgress),
+{
+    let manifest = manifest.as_ref();
+    let Some(algorithm) = algorithm.or_else(|| HashAlgorithm::detect(manifest)) else {
+        anyhow::bail!(
+            "can't tell the algorithm of {}, name it SHA256SUMS, B3SUMS or MD5SUMS",
+            manifest.display()
+        );
+    };
+    let contents = std::fs::read_to_string(manifest)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", manifest.display()))?;
+    let dir = manifest.parent().unwrap_or(Path::new(""));
+
+    let mut report = VerifyReport::default();
+    let mut listed = vec![];
+    for (path, expected) in
+        parse_manifest(&contents).map_err(|e| anyhow::anyhow!("{}: {e}", manifest.display()))?
+    {
+        let path = dir.join(path);
+        match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => listed.push((path, expected, metadata.len())),
+            _ => report.missing.push(path),
+        }
+    }
+
+    let total = listed.iter().map(|(_, _, size)| size).sum();
+    let paths = listed
+        .iter()
+        .map(|(path, _, _)| path.clone())
+        .collect::<Vec<_>>();
+    let hashes = hash_in_parallel(&paths, algorithm, total, &mut progress)?;
+    for ((path, expected, _), actual) in listed.into_iter().zip(hashes) {
+        match actual == expected {
+            true => report.verified.push(path),
+            false => report.mismatched.push(Mismatch {
+                path,
+                expected,
+                actual,
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// Reads the hash and path of every line. Paths may be marked as binary
+/// with a `*`, like `sha256sum -b` writes them.
+fn parse_manifest(contents: &str) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let mut entries = vec![];
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((hash, path)) = line.split_once(' ') else {
+            anyhow::bail!("line {}: expected `<hash>  <path>`", number + 1);
+        };
+        let path = path.strip_prefix([' ', '*']).unwrap_or(path);
+        if path.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("line {}: expected `<hash>  <path>`", number + 1);
+        }
+        entries.push((PathBuf::from(path), hash.to_lowercase()));
+    }
+    Ok(entries)
+}
+
+/// Walks every path recursively, returning every regular file with its
+/// size, sorted by path.
+fn collect_files<P: AsRef<Path>>(paths: &[P]) -> anyhow::Result<Vec<(PathBuf, u64)>> {
+    let mut files = vec![];
+    let mut pending = paths
+        .iter()
+        .map(|path| (path.as_ref().to_path_buf(), true))
+        .collect::<Vec<_>>();
+    while let Some((path, is_root)) = pending.pop() {
+        let metadata = match is_root {
+            true => std::fs::metadata(&path),
+            false => std::fs::symlink_metadata(&path),
+        }
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", path.display()))?;
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(&path)? {
+                pending.push((entry?.path(), false));
+            }
+        } else if metadata.is_file() {
+            files.push((path, metadata.len()));
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Hashes `paths` on a pool of threads, returning the hashes in the same
+/// order. Progress is reported on the calling thread.
+fn hash_in_parallel<F>(
+    paths: &[PathBuf],
+    algorithm: HashAlgorithm,
+    total: u64,
+    progress: &mut F,
+) -> anyhow::Result<Vec<String>>
+where
+    F: FnMut(ChecksumProgress),
+{
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let (tx, next) = (tx.clone(), &next);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                let hash = hash_with_progress(path, algorithm, |read| {
+                    _ = tx.send(Hashed::Bytes(read));
+                    Ok(())
+                });
+                if tx.send(Hashed::File(index, hash)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut hashes = vec![String::new(); paths.len()];
+        let mut done = 0;
+        for hashed in rx {
+            match hashed {
+                Hashed::Bytes(read) => {
+                    done += read;
+                    progress(ChecksumProgress { done, total });
+                }
+                Hashed::File(index, hash) => hashes[index] = hash?,
+            }
+        }
+        Ok(hashes)
+    })
+}
+
+/// Sent by the hashing threads.
+enum Hashed {
+    Bytes(u64),
+    File(usize, anyhow::Result<String>),
+}
+
+/// Hashes a single file, calling `read` with the size of every block read.
+/// Hashing stops with the error `read` returns, if any.
+pub(crate) fn hash_with_progress<F>(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    mut read: F,
+) -> anyhow::Result<String>
+where
+    F: FnMut(u64) -> anyhow::Result<()>,
+{
+    let mut file =
+        File::open(path).map_err(|e| anyhow::anyhow!("failed to open {}: {e}", path.display()))?;
+    let mut hasher = algorithm.hasher();
+    let mut buffer = vec![0; BLOCK_SIZE];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        read(n as u64)?;
+    }
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_known_hashes() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let path = dir.path().join("abc");
+        fs::write(&path, "abc").unwrap();
+
+        assert_eq!(
+            hash_file(&path, HashAlgorithm::Sha256).expect("failed to hash"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash_file(&path, HashAlgorithm::Md5).expect("failed to hash"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hash_file(&path, HashAlgorithm::Blake3).expect("failed to hash"),
+            blake3::hash(b"abc").to_hex().to_string()
+        );
+        assert_eq!(
+            HashAlgorithm::detect("SHA256SUMS"),
+            Some(HashAlgorithm::Sha256)
+        );
+        assert_eq!(
+            HashAlgorithm::detect("app.tar.gz.md5"),
+            Some(HashAlgorithm::Md5)
+        );
+        assert_eq!(HashAlgorithm::detect("B3SUMS"), Some(HashAlgorithm::Blake3));
+        assert_eq!(HashAlgorithm::detect("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_write_and_verify_manifest() {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::create_dir(dir.path().join("bin")).unwrap();
+        fs::write(dir.path().join("bin/app"), vec![7; BLOCK_SIZE * 3 + 1]).unwrap();
+        fs::write(dir.path().join("README"), "read me").unwrap();
+        fs::write(dir.path().join("notes"), "to be removed").unwrap();
+
+        let mut reported = vec![];
+        let paths = [
+            dir.path().join("bin"),
+            dir.path().join("README"),
+            dir.path().join("notes"),
+        ];
+        let checksums =
+            checksum(&paths, HashAlgorithm::Sha256, |p| reported.push(p)).expect("failed to hash");
+        assert_eq!(
+            checksums.iter().map(|c| &c.path).collect::<Vec<_>>(),
+            [
+                &dir.path().join("README"),
+                &dir.path().join("bin/app"),
+                &dir.path().join("notes"),
+            ]
+        );
+        let last = reported.last().expect("progress was reported");
+        assert_eq!(last.done, last.total);
+        assert_eq!(last.total, (BLOCK_SIZE * 3 + 1 + 7 + 13) as u64);
+
+        let manifest = dir.path().join("SHA256SUMS");
+        write_manifest(&manifest, &checksums).expect("failed to write manifest");
+        let contents = fs::read_to_string(&manifest).unwrap();
+        assert!(contents.contains(&format!("{}  bin/app\n", checksums[1].hash)));
+
+        let report = verify_manifest(&manifest, None, |_| ()).expect("failed to verify");
+        assert!(report.is_ok());
+        assert_eq!(report.verified.len(), 3);
+
+        fs::write(dir.path().join("README"), "changed").unwrap();
+        fs::remove_file(dir.path().join("notes")).unwrap();
+        let report = verify_manifest(&manifest, None, |_| ()).expect("failed to verify");
+        assert!(!report.is_ok());
+        assert_eq!(report.verified, [dir.path().join("bin/app")]);
+        assert_eq!(report.missing, [dir.path().join("notes")]);
+        assert_eq!(report.mismatched.len(), 1);
+        assert_eq!(report.mismatched[0].path, dir.path().join("README"));
+        assert_eq!(report.mismatched[0].expected, checksums[0].hash);
+
+        let outside = write_manifest(dir.path().join("bin/SHA256SUMS"), &checksums);
+        assert!(outside
+            .unwrap_err()
+            .to_string()
+            .contains("is not inside of"));
+    }
+
+    #[test]
+    fn test_malformed_manifest() {
+        assert_eq!(
+            parse_manifest("# comment\n\nabc0 *app\n").expect("failed to parse"),
+            [(PathBuf::from("app"), "abc0".to_string())]
+        );
+        let error = parse_manifest("abc0  app\nnot a hash\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: expected `<hash>  <path>`");
+
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::write(dir.path().join("sums.txt"), "").unwrap();
+        let error = verify_manifest(dir.path().join("sums.txt"), None, |_| ()).unwrap_err();
+        assert!(error.to_string().starts_with("can't tell the algorithm"));
+    }
+}
@@ -0,0 +1,385 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zip::write::SimpleFileOptions;
+
+use crate::archive::ArchiveKind;
+
+/// Options to customize how an archive is created.
+#[derive(Debug, Clone, Default)]
+pub struct CompressOptions {
+    /// Compression level, or `None` for the default of the format. Ranges
+    /// from 0 to 9 for zip, gzip and xz, and from 1 to 22 for zstd. Plain
+    /// tar archives are never compressed
+    pub level: Option<i32>,
+}
+
+/// Progress of an archive being created, reported after every item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressProgress {
+    /// Bytes of file contents already stored
+    pub done: u64,
+    pub total: u64,
+    /// Path of the item that was just stored, relative to the archive
+    pub path: PathBuf,
+}
+
+/// An item to be stored on the archive.
+struct Entry {
+    source: PathBuf,
+    name: PathBuf,
+    metadata: std::fs::Metadata,
+}
+
+/// Creates the archive `dest` with every path in `paths`, recursively for
+/// directories. The kind of archive is detected from the name of `dest`.
+///
+/// Items are stored relative to their parent, so `/var/log/app` is stored
+/// as `app`. Symbolic links are stored as links, never followed. An
+/// existing `dest` is never overwritten, and a partially written archive
+/// is removed when anything fails.
+pub fn create_archive<P, Q, F>(
+    paths: &[P],
+    dest: Q,
+    options: &CompressOptions,
+    mut progress: F,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(CompressProgress),
+{
+    try_create_archive(paths, dest, options, |stored| {
+        progress(stored);
+        Ok(())
+    })
+}
+
+/// Same as [`create_archive`], stopping at the first error returned by
+/// `progress` and removing the partial archive.
+pub(crate) fn try_create_archive<P, Q, F>(
+    paths: &[P],
+    dest: Q,
+    options: &CompressOptions,
+    progress: F,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(CompressProgress) -> anyhow::Result<()>,
+{
+    let dest = dest.as_ref();
+    let kind = ArchiveKind::detect(dest)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a supported archive", dest.display()))?;
+    if let Some(level) = options.level {
+        let range = match kind {
+            ArchiveKind::Zip | ArchiveKind::TarGz | ArchiveKind::TarXz => 0..=9,
+            ArchiveKind::TarZst => 1..=22,
+            ArchiveKind::Tar => anyhow::bail!("tar archives are not compressed"),
+        };
+        if !range.contains(&level) {
+            anyhow::bail!(
+                "compression level must be between {} and {}",
+                range.start(),
+                range.end()
+            );
+        }
+    }
+
+    let mut entries = vec![];
+    for path in paths {
+        let path = path.as_ref();
+        if dest.starts_with(path) {
+            anyhow::bail!("cannot store {} inside of itself", path.display());
+        }
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} has no name", path.display()))?;
+        walk(path, Path::new(name), &mut entries)?;
+    }
+
+    let file = File::options().write(true).create_new(true).open(dest)?;
+    let result = match kind {
+        ArchiveKind::Zip => write_zip(file, &entries, options, progress),
+        _ => write_tar(file, kind, &entries, options, progress),
+    };
+    if result.is_err() {
+        _ = std::fs::remove_file(dest);
+    }
+    result
+}
+
+fn walk(source: &Path, name: &Path, entries: &mut Vec<Entry>) -> anyhow::Result<()> {
+    let metadata = std::fs::symlink_metadata(source)?;
+    let is_dir = metadata.is_dir();
+    entries.push(Entry {
+        source: source.to_path_buf(),
+        name: name.to_path_buf(),
+        metadata,
+    });
+    if is_dir {
+        let mut children = std::fs::read_dir(source)?.collect::<Result<Vec<_>, _>>()?;
+        children.sort_by_key(|entry| entry.file_name());
+        for child in children {
+            walk(&child.path(), &name.join(child.file_name()), entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reports progress after every entry, counting the contents of files.
+struct Progress<F> {
+    done: u64,
+    total: u64,
+    report: F,
+}
+
+impl<F> Progress<F>
+where
+    F: FnMut(CompressProgress) -> anyhow::Result<()>,
+{
+    fn new(entries: &[Entry], report: F) -> Self {
+        let total = entries
+            .iter()
+            .filter(|entry| entry.metadata.is_file())
+            .map(|entry| entry.metadata.len())
+            .sum();
+        Progress {
+            done: 0,
+            total,
+            report,
+        }
+    }
+
+    fn stored(&mut self, entry: &Entry) -> anyhow::Result<()> {
+        if entry.metadata.is_file() {
+            self.done += entry.metadata.len();
+        }
+        (self.report)(CompressProgress {
+            done: self.done,
+            total: self.total,
+            path: entry.name.clone(),
+        })
+    }
+}
+
+fn write_tar<F>(
+    file: File,
+    kind: ArchiveKind,
+    entries: &[Entry],
+    options: &CompressOptions,
+    progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(CompressProgress) -> anyhow::Result<()>,
+{
+    let file = BufWriter::new(file);
+    match kind {
+        ArchiveKind::Tar => {
+            fill_tar(file, entries, progress)?.flush()?;
+        }
+        ArchiveKind::TarGz => {
+            let level = flate2::Compression::new(options.level.unwrap_or(6) as u32);
+            let encoder = flate2::write::GzEncoder::new(file, level);
+            fill_tar(encoder, entries, progress)?.finish()?.flush()?;
+        }
+        ArchiveKind::TarXz => {
+            let encoder = xz2::write::XzEncoder::new(file, options.level.unwrap_or(6) as u32);
+            fill_tar(encoder, entries, progress)?.finish()?.flush()?;
+        }
+        ArchiveKind::TarZst => {
+            let encoder = zstd::stream::write::Encoder::new(file, options.level.unwrap_or(3))?;
+            fill_tar(encoder, entries, progress)?.finish()?.flush()?;
+        }
+        ArchiveKind::Zip => unreachable!("zip archives are not tar streams"),
+    }
+    Ok(())
+}
+
+fn fill_tar<W, F>(writer: W, entries: &[Entry], progress: F) -> anyhow::Result<W>
+where
+    W: Write,
+    F: FnMut(CompressProgress) -> anyhow::Result<()>,
+{
+    let mut progress = Progress::new(entries, progress);
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+    for entry in entries {
+        builder.append_path_with_name(&entry.source, &entry.name)?;
+        progress.stored(entry)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn write_zip<F>(
+    file: File,
+    entries: &[Entry],
+    options: &CompressOptions,
+    progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(CompressProgress) -> anyhow::Result<()>,
+{
+    let mut progress = Progress::new(entries, progress);
+    let mut zip = zip::ZipWriter::new(BufWriter::new(file));
+    for entry in entries {
+        // zip always uses forward slashes, whatever the platform
+        let name = entry
+            .name
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let mut file_options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(options.level.map(i64::from))
+            .large_file(entry.metadata.len() >= u32::MAX as u64);
+        if let Some(mode) = unix_mode(&entry.metadata) {
+            file_options = file_options.unix_permissions(mode);
+        }
+        if let Some(modified) = entry.metadata.modified().ok().and_then(zip_date) {
+            file_options = file_options.last_modified_time(modified);
+        }
+
+        let file_type = entry.metadata.file_type();
+        if file_type.is_symlink() {
+            let target = std::fs::read_link(&entry.source)?;
+            zip.add_symlink(name, target.to_string_lossy(), file_options)?;
+        } else if file_type.is_dir() {
+            zip.add_directory(name, file_options)?;
+        } else {
+            zip.start_file(name, file_options)?;
+            std::io::copy(&mut File::open(&entry.source)?, &mut zip)?;
+        }
+        progress.stored(entry)?;
+    }
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Converts a time to the MS-DOS timestamp stored on zip files, in UTC.
+/// Returns `None` for times zip can't represent, before 1980.
+fn zip_date(time: SystemTime) -> Option<zip::DateTime> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // proleptic gregorian date for a number of days since the epoch
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    zip::DateTime::from_date_and_time(
+        u16::try_from(year).ok()?,
+        month as u8,
+        day as u8,
+        (secs / 3600) as u8,
+        (secs % 3600 / 60) as u8,
+        (secs % 60) as u8,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::Archive;
+    use crate::file_manager::FileType;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_tempdir() -> TempDir {
+        let dir = tempdir().expect("failed to create tempdir");
+        let logs = dir.path().join("logs");
+        fs::create_dir_all(logs.join("old")).unwrap();
+        fs::write(logs.join("app.log"), "started").unwrap();
+        fs::write(logs.join("old/app.log.1"), "crashed").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("app.log", logs.join("latest")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_create_archives() {
+        let dir = setup_tempdir();
+
+        for name in ["logs.zip", "logs.tar", "logs.tar.gz", "logs.tar.zst"] {
+            let dest = dir.path().join(name);
+            let mut reported = vec![];
+            create_archive(
+                &[dir.path().join("logs")],
+                &dest,
+                &CompressOptions::default(),
+                |progress| reported.push(progress),
+            )
+            .expect("failed to create archive");
+
+            let archive = Archive::open(&dest).expect("failed to open archive");
+            assert_eq!(archive.read("logs/old/app.log.1").unwrap(), b"crashed");
+            #[cfg(unix)]
+            assert_eq!(
+                archive.entry("logs/latest").unwrap().file_type,
+                FileType::Symlink
+            );
+            let last = reported.last().unwrap();
+            assert_eq!((last.done, last.total), (14, 14));
+        }
+    }
+
+    #[test]
+    fn test_create_archive_never_overwrites() {
+        let dir = setup_tempdir();
+        let dest = dir.path().join("logs.zip");
+        fs::write(&dest, "precious").unwrap();
+
+        let result = create_archive(
+            &[dir.path().join("logs")],
+            &dest,
+            &CompressOptions::default(),
+            |_| {},
+        );
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "precious");
+    }
+
+    #[test]
+    fn test_create_archive_with_invalid_level() {
+        let dir = setup_tempdir();
+        let dest = dir.path().join("logs.tar.gz");
+        let options = CompressOptions { level: Some(12) };
+
+        let result = create_archive(&[dir.path().join("logs")], &dest, &options, |_| {});
+
+        assert!(result.is_err());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_zip_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let date = zip_date(time).unwrap();
+
+        assert_eq!((date.year(), date.month(), date.day()), (2023, 11, 14));
+        assert_eq!((date.hour(), date.minute(), date.second()), (22, 13, 20));
+    }
+}
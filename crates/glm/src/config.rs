@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+/// Resolves an XDG base directory, falling back to `fallback` inside of the
+/// home directory when the variable isn't set or isn't absolute.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    let base = std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(fallback)))?;
+    Some(base.join("glm"))
+}
+
+/// Where glm keeps state between runs, `$XDG_STATE_HOME/glm` or
+/// `~/.local/state/glm` when it isn't set.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Where glm reads its configuration and templates from,
+/// `$XDG_CONFIG_HOME/glm` or `~/.config/glm` when it isn't set.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Settings read from `config.toml` in the [`config_dir`].
+///
+/// Only flat `key = "value"` lines are understood, comments start with `#`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Name used for the `{{author}}` variable of templates
+    pub author: Option<String>,
+}
+
+impl Config {
+    /// Reads the configuration, which is empty when there is no file.
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = config_dir().map(|dir| dir.join("config.toml")) else {
+            return Ok(Config::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text)
+                .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut config = Config::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                anyhow::bail!("line {}: expected `key = \"value\"`", number + 1);
+            };
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .ok_or_else(|| anyhow::anyhow!("line {}: values must be quoted", number + 1))?;
+            match key.trim() {
+                "author" => config.author = Some(value.to_string()),
+                key => anyhow::bail!("line {}: unknown setting {key}", number + 1),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse("# who writes\n\nauthor = \"Ada Lovelace\"\n")
+            .expect("failed to parse config");
+        assert_eq!(config.author.as_deref(), Some("Ada Lovelace"));
+
+        let error = Config::parse("author = Ada").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: values must be quoted");
+        assert!(Config::parse("editor = \"vi\"").is_err());
+    }
+}
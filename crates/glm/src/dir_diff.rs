@@ -0,0 +1,217 @@
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+use crate::checksums::{hash_file, HashAlgorithm};
+
+/// Options to customize how two directory trees are compared.
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /// When set, files with the same size are compared by a hash of their
+    /// contents instead of their modification time.
+    pub compare_contents: bool,
+}
+
+/// Result of comparing two directory trees. Every path is relative to the
+/// root of the compared trees, and every list is sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirDiff {
+    /// Items that only exist on the left tree. Directories are listed
+    /// without their contents
+    pub only_left: Vec<PathBuf>,
+    /// Items that only exist on the right tree. Directories are listed
+    /// without their contents
+    pub only_right: Vec<PathBuf>,
+    /// Items that exist on both trees, but are different
+    pub differing: Vec<PathBuf>,
+    /// Items that are the same on both trees
+    pub identical: Vec<PathBuf>,
+}
+
+impl DirDiff {
+    /// Whether both trees are the same.
+    pub fn is_identical(&self) -> bool {
+        self.only_left.is_empty() && self.only_right.is_empty() && self.differing.is_empty()
+    }
+}
+
+/// Compares the directory trees at `left` and `right`.
+///
+/// Items are matched by name, directories present on both sides are
+/// compared recursively, and files are considered different when their
+/// type, size or modification time differ. When
+/// [`CompareOptions::compare_contents`] is set, files with the same size
+/// are compared by content hash instead of modification time. Symbolic
+/// links are never followed, and are compared by their target.
+pub fn compare_dirs<P, Q>(left: P, right: Q, options: &CompareOptions) -> anyhow::Result<DirDiff>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut diff = DirDiff::default();
+    compare_into(
+        left.as_ref(),
+        right.as_ref(),
+        Path::new(""),
+        options,
+        &mut diff,
+    )?;
+    diff.only_left.sort();
+    diff.only_right.sort();
+    diff.differing.sort();
+    diff.identical.sort();
+    Ok(diff)
+}
+
+fn compare_into(
+    left_root: &Path,
+    right_root: &Path,
+    relative: &Path,
+    options: &CompareOptions,
+    diff: &mut DirDiff,
+) -> anyhow::Result<()> {
+    let left_names = read_names(&left_root.join(relative))?;
+    let right_names = read_names(&right_root.join(relative))?;
+
+    for name in left_names.union(&right_names) {
+        let path = relative.join(name);
+        match (left_names.contains(name), right_names.contains(name)) {
+            (true, false) => diff.only_left.push(path),
+            (false, true) => diff.only_right.push(path),
+            _ => {
+                let left = left_root.join(&path);
+                let right = right_root.join(&path);
+                let left_metadata = std::fs::symlink_metadata(&left)?;
+                let right_metadata = std::fs::symlink_metadata(&right)?;
+
+                if left_metadata.is_dir() && right_metadata.is_dir() {
+                    compare_into(left_root, right_root, &path, options, diff)?;
+                    continue;
+                }
+                match is_same_entry(&left, &left_metadata, &right, &right_metadata, options)? {
+                    true => diff.identical.push(path),
+                    false => diff.differing.push(path),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_names(dir: &Path) -> anyhow::Result<BTreeSet<OsString>> {
+    std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.file_name()))
+        .collect()
+}
+
+fn is_same_entry(
+    left: &Path,
+    left_metadata: &Metadata,
+    right: &Path,
+    right_metadata: &Metadata,
+    options: &CompareOptions,
+) -> anyhow::Result<bool> {
+    let (left_type, right_type) = (left_metadata.file_type(), right_metadata.file_type());
+    if left_type.is_symlink() || right_type.is_symlink() {
+        return Ok(left_type.is_symlink()
+            && right_type.is_symlink()
+            && std::fs::read_link(left)? == std::fs::read_link(right)?);
+    }
+    if left_type.is_dir() != right_type.is_dir() || left_metadata.len() != right_metadata.len() {
+        return Ok(false);
+    }
+    match options.compare_contents {
+        true => {
+            Ok(hash_file(left, HashAlgorithm::Blake3)? == hash_file(right, HashAlgorithm::Blake3)?)
+        }
+        false => Ok(left_metadata.modified()? == right_metadata.modified()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    fn write_with_mtime(path: PathBuf, contents: &str, mtime: i64) {
+        fs::write(&path, contents).unwrap();
+        filetime::set_file_mtime(path, FileTime::from_unix_time(mtime, 0)).unwrap();
+    }
+
+    fn setup_tempdir() -> TempDir {
+        let dir = tempdir().expect("failed to create tempdir");
+        for side in ["left", "right"] {
+            let root = dir.path().join(side);
+            fs::create_dir_all(root.join("shared")).unwrap();
+            write_with_mtime(root.join("same.txt"), "same", 1000);
+            write_with_mtime(root.join("shared/same.txt"), "same", 1000);
+        }
+        let (left, right) = (dir.path().join("left"), dir.path().join("right"));
+        fs::create_dir(left.join("left_dir")).unwrap();
+        fs::write(left.join("left_dir/inner.txt"), "inner").unwrap();
+        fs::write(right.join("right.txt"), "right").unwrap();
+        write_with_mtime(left.join("shared/size.txt"), "short", 1000);
+        write_with_mtime(right.join("shared/size.txt"), "longer", 1000);
+        write_with_mtime(left.join("touched.txt"), "touched", 1000);
+        write_with_mtime(right.join("touched.txt"), "touched", 2000);
+        dir
+    }
+
+    #[test]
+    fn test_compare_dirs() {
+        let dir = setup_tempdir();
+
+        let diff = compare_dirs(
+            dir.path().join("left"),
+            dir.path().join("right"),
+            &CompareOptions::default(),
+        )
+        .expect("failed to compare");
+
+        assert_eq!(diff.only_left, vec![PathBuf::from("left_dir")]);
+        assert_eq!(diff.only_right, vec![PathBuf::from("right.txt")]);
+        assert_eq!(
+            diff.differing,
+            vec![
+                PathBuf::from("shared/size.txt"),
+                PathBuf::from("touched.txt")
+            ]
+        );
+        assert_eq!(
+            diff.identical,
+            vec![PathBuf::from("same.txt"), PathBuf::from("shared/same.txt")]
+        );
+        assert!(!diff.is_identical());
+    }
+
+    #[test]
+    fn test_compare_dirs_by_contents() {
+        let dir = setup_tempdir();
+        let options = CompareOptions {
+            compare_contents: true,
+        };
+
+        let diff = compare_dirs(dir.path().join("left"), dir.path().join("right"), &options)
+            .expect("failed to compare");
+
+        assert_eq!(diff.differing, vec![PathBuf::from("shared/size.txt")]);
+        assert!(diff.identical.contains(&PathBuf::from("touched.txt")));
+    }
+
+    #[test]
+    fn test_compare_identical_dirs() {
+        let dir = setup_tempdir();
+
+        let diff = compare_dirs(
+            dir.path().join("left"),
+            dir.path().join("left"),
+            &CompareOptions::default(),
+        )
+        .expect("failed to compare");
+
+        assert!(diff.is_identical());
+    }
+}
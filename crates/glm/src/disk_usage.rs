@@ -0,0 +1,450 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::SystemTime;
+
+/// Amount of entries visited between two progress reports.
+const PROGRESS_INTERVAL: u64 = 512;
+
+/// Recursive size of a directory, or the size of a single file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// Sum of the lengths of every file, as reported by `ls -l`
+    pub apparent: u64,
+    /// Space actually used on disk, as reported by `du`
+    pub allocated: u64,
+    /// Amount of non directory entries visited
+    pub files: u64,
+    /// Amount of directories visited, including the root
+    pub dirs: u64,
+}
+
+impl DiskUsage {
+    fn add(&mut self, metadata: &std::fs::Metadata) {
+        self.apparent += metadata.len();
+        self.allocated += allocated_size(metadata);
+        match metadata.is_dir() {
+            true => self.dirs += 1,
+            false => self.files += 1,
+        }
+    }
+
+    fn merge(&mut self, other: &DiskUsage) {
+        self.apparent += other.apparent;
+        self.allocated += other.allocated;
+        self.files += other.files;
+        self.dirs += other.dirs;
+    }
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // `st_blocks` is always expressed in 512 byte units
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Device and inode of an entry, or `None` on platforms without inodes.
+#[cfg(unix)]
+fn inode(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+fn is_hard_linked(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    !metadata.is_dir() && metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn is_hard_linked(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Computes the disk usage of `path` recursively.
+///
+/// Symbolic links are never followed, files with many hard links are only
+/// counted once, and directories on a different device than `path` (mount
+/// points) are not descended into.
+pub fn disk_usage<P>(path: P) -> anyhow::Result<DiskUsage>
+where
+    P: AsRef<Path>,
+{
+    let cancel = AtomicBool::new(false);
+    walk(
+        path.as_ref(),
+        &mut |dir, _| list(dir, &cancel),
+        &cancel,
+        &mut |_| {},
+    )
+}
+
+/// What a directory directly contains, which stays valid as long as the
+/// directory itself is not modified.
+#[derive(Debug, Clone, Default)]
+struct Listing {
+    /// Usage of the files directly inside, without the hard linked ones
+    files: DiskUsage,
+    /// Hard linked files directly inside, only counted once per walk
+    links: Vec<(Option<(u64, u64)>, DiskUsage)>,
+    /// Names of the directories directly inside
+    subdirs: Vec<OsString>,
+}
+
+impl Listing {
+    fn len(&self) -> u64 {
+        self.files.files + self.links.len() as u64 + self.subdirs.len() as u64
+    }
+}
+
+/// Lists `dir`, returning `None` when it cannot be read.
+fn list(dir: &Path, cancel: &AtomicBool) -> anyhow::Result<Option<Listing>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(None);
+    };
+    let mut listing = Listing::default();
+    for entry in entries.flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            anyhow::bail!("disk usage calculation of {} was cancelled", dir.display());
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            listing.subdirs.push(entry.file_name());
+        } else if is_hard_linked(&metadata) {
+            let mut usage = DiskUsage::default();
+            usage.add(&metadata);
+            listing.links.push((inode(&metadata), usage));
+        } else {
+            listing.files.add(&metadata);
+        }
+    }
+    Ok(Some(listing))
+}
+
+/// Sums up the usage of `root`, getting what each directory contains from
+/// `listing`, which returns `None` for directories to skip.
+fn walk(
+    root: &Path,
+    listing: &mut dyn FnMut(&Path, &std::fs::Metadata) -> anyhow::Result<Option<Listing>>,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(&DiskUsage),
+) -> anyhow::Result<DiskUsage> {
+    let root_metadata = std::fs::symlink_metadata(root)?;
+    let root_device = inode(&root_metadata).map(|(dev, _)| dev);
+    let mut usage = DiskUsage::default();
+    if !root_metadata.is_dir() {
+        usage.add(&root_metadata);
+        return Ok(usage);
+    }
+
+    let mut seen_links = HashSet::new();
+    let mut visited = 0;
+    let mut reported = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        if cancel.load(Ordering::Relaxed) {
+            anyhow::bail!("disk usage calculation of {} was cancelled", root.display());
+        }
+        let Ok(metadata) = std::fs::symlink_metadata(&dir) else {
+            continue;
+        };
+        if !metadata.is_dir() || inode(&metadata).map(|(dev, _)| dev) != root_device {
+            continue;
+        }
+        usage.add(&metadata);
+        // unreadable directories are skipped, as `du` does
+        let Some(listing) = listing(&dir, &metadata)? else {
+            continue;
+        };
+
+        usage.merge(&listing.files);
+        for (key, link) in listing.links.iter() {
+            if seen_links.insert(*key) {
+                usage.merge(link);
+            }
+        }
+        pending.extend(listing.subdirs.iter().map(|name| dir.join(name)));
+        visited += listing.len();
+        if visited - reported >= PROGRESS_INTERVAL {
+            reported = visited;
+            progress(&usage);
+        }
+    }
+
+    Ok(usage)
+}
+
+/// Listings of directories by inode, along with the modification time of
+/// the directory when it was listed.
+type Cache = HashMap<(u64, u64), (Option<SystemTime>, Listing)>;
+
+/// Returns the cached listing of the directory with `metadata`, if it was
+/// not modified since it was listed.
+fn cached_listing(cache: &Mutex<Cache>, metadata: &std::fs::Metadata) -> Option<Listing> {
+    let key = inode(metadata)?;
+    match cache.lock().unwrap().get(&key) {
+        Some((modified, listing)) if *modified == metadata.modified().ok() => Some(listing.clone()),
+        _ => None,
+    }
+}
+
+/// Events produced by a `DiskUsageScanner` while it computes sizes.
+#[derive(Debug, Clone)]
+pub enum DiskUsageEvent {
+    /// A partial result for a path that is still being computed
+    Progress { path: PathBuf, usage: DiskUsage },
+    /// The final result for a path
+    Done { path: PathBuf, usage: DiskUsage },
+    /// Computing the usage of a path failed
+    Failed { path: PathBuf, error: String },
+}
+
+/// Computes disk usage on a background thread, reporting progress and
+/// results as `DiskUsageEvent`s.
+///
+/// What each directory contains is cached by inode and modification time,
+/// so asking for the same directory twice only checks the modification time
+/// of every directory under it, and lists again only the ones that changed.
+/// Files that are rewritten in place don't modify their directory, so their
+/// new size is only noticed once their directory changes.
+#[derive(Debug)]
+pub struct DiskUsageScanner {
+    cache: Arc<Mutex<Cache>>,
+    cancel: Arc<AtomicBool>,
+    tx: mpsc::Sender<DiskUsageEvent>,
+    rx: mpsc::Receiver<DiskUsageEvent>,
+}
+
+impl Default for DiskUsageScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiskUsageScanner {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            cancel: Arc::new(AtomicBool::new(false)),
+            tx,
+            rx,
+        }
+    }
+
+    /// Starts computing the usage of every path in `paths`, one after the
+    /// other on a background thread. Any scan that is still running is
+    /// cancelled, so only the latest request produces events.
+    pub fn scan(&mut self, paths: Vec<PathBuf>) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+
+        let cancel = self.cancel.clone();
+        let cache = self.cache.clone();
+        let tx = self.tx.clone();
+        std::thread::spawn(move || {
+            for path in paths {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                let result = walk(
+                    &path,
+                    &mut |dir, metadata| {
+                        if let Some(listing) = cached_listing(&cache, metadata) {
+                            return Ok(Some(listing));
+                        }
+                        let listing = list(dir, &cancel)?;
+                        if let (Some(key), Some(listing)) = (inode(metadata), &listing) {
+                            let modified = metadata.modified().ok();
+                            cache
+                                .lock()
+                                .unwrap()
+                                .insert(key, (modified, listing.clone()));
+                        }
+                        Ok(listing)
+                    },
+                    &cancel,
+                    &mut |usage| {
+                        _ = tx.send(DiskUsageEvent::Progress {
+                            path: path.clone(),
+                            usage: *usage,
+                        });
+                    },
+                );
+
+                let event = match result {
+                    Ok(usage) => DiskUsageEvent::Done { path, usage },
+                    Err(_) if cancel.load(Ordering::Relaxed) => return,
+                    Err(e) => DiskUsageEvent::Failed {
+                        path,
+                        error: e.to_string(),
+                    },
+                };
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Cancels the scan that is currently running, if any.
+    pub fn cancel(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the usage of `path` from the cache, if every directory under
+    /// it is cached and was not modified since.
+    pub fn cached<P>(&self, path: P) -> Option<DiskUsage>
+    where
+        P: AsRef<Path>,
+    {
+        walk(
+            path.as_ref(),
+            &mut |dir, metadata| match cached_listing(&self.cache, metadata) {
+                Some(listing) => Ok(Some(listing)),
+                None => anyhow::bail!("{} is not cached", dir.display()),
+            },
+            &AtomicBool::new(false),
+            &mut |_| {},
+        )
+        .ok()
+    }
+
+    /// Returns every event produced since the last call, without blocking.
+    pub fn events(&self) -> Vec<DiskUsageEvent> {
+        self.rx.try_iter().collect()
+    }
+
+    /// Blocks until the next event is produced. Mostly useful for
+    /// frontends that run the scanner on their own thread.
+    pub fn next_event(&self) -> anyhow::Result<DiskUsageEvent> {
+        Ok(self.rx.recv()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_tempdir() -> TempDir {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("one.txt"), vec![0; 100]).unwrap();
+        fs::write(dir.path().join("a/two.txt"), vec![0; 200]).unwrap();
+        fs::write(dir.path().join("a/b/three.txt"), vec![0; 300]).unwrap();
+        dir
+    }
+
+    fn dirs_size(dir: &Path) -> u64 {
+        [dir.to_path_buf(), dir.join("a"), dir.join("a/b")]
+            .iter()
+            .map(|path| fs::metadata(path).unwrap().len())
+            .sum()
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let dir = setup_tempdir();
+
+        let usage = disk_usage(dir.path()).expect("failed to compute usage");
+
+        assert_eq!(usage.files, 3);
+        assert_eq!(usage.dirs, 3);
+        assert_eq!(usage.apparent, 600 + dirs_size(dir.path()));
+        assert!(usage.allocated > 0);
+    }
+
+    #[test]
+    fn test_disk_usage_of_file() {
+        let dir = setup_tempdir();
+
+        let usage = disk_usage(dir.path().join("one.txt")).expect("failed to compute usage");
+
+        assert_eq!(usage.files, 1);
+        assert_eq!(usage.dirs, 0);
+        assert_eq!(usage.apparent, 100);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_are_counted_once() {
+        let dir = setup_tempdir();
+        fs::hard_link(dir.path().join("one.txt"), dir.path().join("a/link.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("a"), dir.path().join("symlink")).unwrap();
+
+        let usage = disk_usage(dir.path()).expect("failed to compute usage");
+
+        let symlink_size = fs::symlink_metadata(dir.path().join("symlink"))
+            .unwrap()
+            .len();
+        assert_eq!(usage.files, 4);
+        assert_eq!(usage.apparent, 600 + symlink_size + dirs_size(dir.path()));
+    }
+
+    fn scan_one(sut: &mut DiskUsageScanner, path: PathBuf) -> DiskUsage {
+        sut.scan(vec![path]);
+        loop {
+            match sut.next_event().expect("scanner should produce events") {
+                DiskUsageEvent::Done { usage, .. } => return usage,
+                DiskUsageEvent::Failed { error, .. } => panic!("{error}"),
+                DiskUsageEvent::Progress { .. } => continue,
+            }
+        }
+    }
+
+    #[test]
+    fn test_scanner_reports_results_and_caches_them() {
+        let dir = setup_tempdir();
+        let mut sut = DiskUsageScanner::new();
+
+        let usage = scan_one(&mut sut, dir.path().join("a"));
+
+        assert_eq!(usage.files, 2);
+        assert_eq!(sut.cached(dir.path().join("a")), Some(usage));
+        assert_eq!(sut.cached(dir.path()), None);
+    }
+
+    #[test]
+    fn test_scanner_recomputes_nested_changes() {
+        let dir = setup_tempdir();
+        let mut sut = DiskUsageScanner::new();
+        let before = scan_one(&mut sut, dir.path().to_path_buf());
+
+        // replaces the file the way editors save it, and moves the mtime of
+        // its directory forward, since timestamps can be coarser than the test
+        let nested = dir.path().join("a/b");
+        fs::write(nested.join("three.tmp"), vec![0; 1000]).unwrap();
+        fs::rename(nested.join("three.tmp"), nested.join("three.txt")).unwrap();
+        let modified = fs::metadata(&nested).unwrap().modified().unwrap();
+        fs::File::open(&nested)
+            .and_then(|file| file.set_modified(modified + std::time::Duration::from_secs(10)))
+            .unwrap();
+
+        assert_eq!(sut.cached(dir.path()), None);
+        let after = scan_one(&mut sut, dir.path().to_path_buf());
+        assert_eq!(after.files, before.files);
+        assert_eq!(after.apparent, 1300 + dirs_size(dir.path()));
+        assert_eq!(sut.cached(dir.path()), Some(after));
+        assert_eq!(
+            sut.cached(dir.path().join("a")).map(|usage| usage.files),
+            Some(2)
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::audit::{audited, AuditLog};
+use crate::checksums::{hash_file, HashAlgorithm};
+use crate::plan::PlannedOp;
+
+/// Size of the blocks read from the start and the end of a file when
+/// computing its partial hash.
+const PARTIAL_BLOCK_SIZE: u64 = 4096;
+
+/// A set of files that have exactly the same contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Size in bytes of every file on the group
+    pub size: u64,
+    /// Paths of every file on the group, sorted, at least two of them
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Bytes that could be reclaimed by keeping a single copy.
+    pub fn wasted_size(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Finds files with identical contents inside of `roots`, recursively.
+///
+/// Candidates go through a pipeline where each step is more expensive than
+/// the previous one, and only files that could still be duplicates move to
+/// the next step:
+///
+/// 1. files are grouped by size;
+/// 2. then by a hash of their first and last blocks;
+/// 3. and finally by a hash of their whole contents.
+///
+/// Symbolic links and empty files are ignored, and when many paths are hard
+/// links to the same inode only one of them is considered, as they don't
+/// take any extra space. Groups are sorted by wasted space, biggest first.
+pub fn find_duplicates<P>(roots: &[P]) -> anyhow::Result<Vec<DuplicateGroup>>
+where
+    P: AsRef<Path>,
+{
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in collect_files(roots) {
+        by_size.entry(size).or_default().push(path);
+    }
+
+    let mut groups = vec![];
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        for candidates in group_by(paths, partial_hash) {
+            // files smaller than both blocks were already fully hashed
+            let confirmed = match size <= PARTIAL_BLOCK_SIZE * 2 {
+                true => vec![candidates],
+                false => group_by(candidates, full_hash),
+            };
+            for mut paths in confirmed {
+                paths.sort();
+                groups.push(DuplicateGroup { size, paths });
+            }
+        }
+    }
+
+    groups.sort_by(|a, b| {
+        b.wasted_size()
+            .cmp(&a.wasted_size())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    Ok(groups)
+}
+
+/// Replaces every file in `duplicates` with a hard link to `original`, so
+/// they share the same storage. Every duplicate is replaced atomically, by
+/// creating the link under a temporary name and renaming it over the file,
+/// and recorded in `audit` when there is one.
+///
+/// Every file is hashed again right before being replaced, so a stale
+/// group never loses data. Fails if any of the files has different contents
+/// than `original`, or if they are not on the same filesystem.
+pub fn link_duplicates<P, Q>(
+    original: P,
+    duplicates: &[Q],
+    audit: Option<&AuditLog>,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let original = original.as_ref();
+    let original_size = std::fs::metadata(original)?.len();
+    let original_hash = full_hash(original)?;
+
+    for duplicate in duplicates {
+        let duplicate = duplicate.as_ref();
+        if std::fs::metadata(duplicate)?.len() != original_size
+            || full_hash(duplicate)? != original_hash
+        {
+            anyhow::bail!(
+                "{} is not a duplicate of {}",
+                duplicate.display(),
+                original.display()
+            );
+        }
+        let op = PlannedOp::HardLink {
+            target: original.to_path_buf(),
+            link: duplicate.to_path_buf(),
+        };
+        audited(audit, &op, replace_with_link(original, duplicate))?;
+    }
+    Ok(())
+}
+
+fn replace_with_link(original: &Path, duplicate: &Path) -> anyhow::Result<()> {
+    let file_name = duplicate.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = duplicate.with_file_name(format!(".{file_name}.glm-link"));
+    std::fs::hard_link(original, &temp_path).map_err(|e| {
+        anyhow::anyhow!(
+            "failed to link {} to {}: {e}",
+            duplicate.display(),
+            original.display()
+        )
+    })?;
+    if let Err(e) = std::fs::rename(&temp_path, duplicate) {
+        _ = std::fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Walks every root recursively, returning every regular, non empty file
+/// with its size. Only the first path found for each inode is returned.
+fn collect_files<P>(roots: &[P]) -> Vec<(PathBuf, u64)>
+where
+    P: AsRef<Path>,
+{
+    let mut files = vec![];
+    let mut seen_inodes = HashSet::new();
+    let mut pending = roots
+        .iter()
+        .map(|root| root.as_ref().to_path_buf())
+        .collect::<Vec<_>>();
+
+    while let Some(path) = pending.pop() {
+        // files that vanish or can't be read are left out as well
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            // a directory that can't be read leaves its files out instead
+            // of failing the whole search
+            let Ok(entries) = std::fs::read_dir(&path) else {
+                continue;
+            };
+            pending.extend(entries.flatten().map(|entry| entry.path()));
+            continue;
+        }
+        if !metadata.is_file() || metadata.len() == 0 {
+            continue;
+        }
+        if let Some(inode) = inode(&metadata) {
+            if !seen_inodes.insert(inode) {
+                continue;
+            }
+        }
+        files.push((path, metadata.len()));
+    }
+
+    files
+}
+
+#[cfg(unix)]
+fn inode(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Splits `paths` into groups of paths that have the same key, dropping
+/// groups with a single path. Paths that cannot be read are dropped too.
+fn group_by<F, K>(paths: Vec<PathBuf>, key: F) -> Vec<Vec<PathBuf>>
+where
+    F: Fn(&Path) -> anyhow::Result<K>,
+    K: Eq + Hash,
+{
+    let mut groups: HashMap<K, Vec<PathBuf>> = HashMap::new();
+    for path in paths {
+        if let Ok(hash) = key(&path) {
+            groups.entry(hash).or_default().push(path);
+        }
+    }
+    groups
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .collect()
+}
+
+/// Hashes the first and the last blocks of a file.
+fn partial_hash(path: &Path) -> anyhow::Result<blake3::Hash> {
+    let mut file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = Vec::with_capacity(PARTIAL_BLOCK_SIZE as usize);
+
+    file.by_ref()
+        .take(PARTIAL_BLOCK_SIZE)
+        .read_to_end(&mut buffer)?;
+    hasher.update(&buffer);
+
+    let size = file.metadata()?.len();
+    if size > PARTIAL_BLOCK_SIZE {
+        let offset = u64::max(PARTIAL_BLOCK_SIZE, size - PARTIAL_BLOCK_SIZE);
+        file.seek(SeekFrom::Start(offset))?;
+        buffer.clear();
+        file.take(PARTIAL_BLOCK_SIZE).read_to_end(&mut buffer)?;
+        hasher.update(&buffer);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Hashes the whole contents of a file.
+fn full_hash(path: &Path) -> anyhow::Result<String> {
+    hash_file(path, HashAlgorithm::Blake3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_tempdir() -> TempDir {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(dir.path().join("a.txt"), "same contents").unwrap();
+        fs::write(dir.path().join("nested/b.txt"), "same contents").unwrap();
+        fs::write(dir.path().join("c.txt"), "diff contents").unwrap();
+        fs::write(dir.path().join("empty1"), "").unwrap();
+        fs::write(dir.path().join("empty2"), "").unwrap();
+
+        // big files that only differ in the middle, so the partial hash
+        // matches but the full hash does not
+        let mut big = vec![1u8; 3 * PARTIAL_BLOCK_SIZE as usize];
+        fs::write(dir.path().join("big1.bin"), &big).unwrap();
+        fs::write(dir.path().join("big2.bin"), &big).unwrap();
+        big[PARTIAL_BLOCK_SIZE as usize + 10] = 0;
+        fs::write(dir.path().join("big3.bin"), &big).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = setup_tempdir();
+
+        let groups = find_duplicates(&[dir.path()]).expect("failed to find duplicates");
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups[0].paths,
+            vec![dir.path().join("big1.bin"), dir.path().join("big2.bin")]
+        );
+        assert_eq!(
+            groups[1].paths,
+            vec![dir.path().join("a.txt"), dir.path().join("nested/b.txt")]
+        );
+        assert_eq!(groups[1].wasted_size(), 13);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_are_not_duplicates() {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::write(dir.path().join("a.txt"), "same contents").unwrap();
+        fs::hard_link(dir.path().join("a.txt"), dir.path().join("b.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("a.txt"), dir.path().join("c.txt")).unwrap();
+
+        let groups = find_duplicates(&[dir.path()]).expect("failed to find duplicates");
+
+        assert!(groups.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_duplicates() {
+        let dir = setup_tempdir();
+        let original = dir.path().join("a.txt");
+        let duplicate = dir.path().join("nested/b.txt");
+        let state = tempdir().expect("failed to create tempdir");
+        let audit = AuditLog::new(state.path());
+
+        link_duplicates(&original, &[&duplicate], Some(&audit)).expect("failed to link duplicates");
+
+        assert_eq!(
+            inode(&fs::metadata(&original).unwrap()),
+            inode(&fs::metadata(&duplicate).unwrap())
+        );
+        let groups = find_duplicates(&[dir.path()]).expect("failed to find duplicates");
+        assert_eq!(groups.len(), 1);
+        let log = fs::read_to_string(audit.path()).expect("failed to read audit log");
+        assert!(log.contains(r#""op":"hardlink""#));
+    }
+
+    #[test]
+    fn test_link_duplicates_with_different_size() {
+        let dir = setup_tempdir();
+
+        let result = link_duplicates(
+            dir.path().join("a.txt"),
+            &[dir.path().join("big1.bin")],
+            None,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_link_duplicates_with_changed_contents() {
+        let dir = setup_tempdir();
+        let changed = dir.path().join("c.txt");
+
+        // same size as a.txt, but not the same contents anymore
+        let result = link_duplicates(dir.path().join("a.txt"), &[&changed], None);
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "diff contents");
+    }
+}
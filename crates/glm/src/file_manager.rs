@@ -1,8 +1,21 @@
 use path_absolutize::*;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+#[cfg(feature = "archive")]
+use crate::archive::Archive;
+use crate::audit::{audited, AuditLog};
+use crate::bulk_rename::{self, BulkChange, BulkEdit};
 use crate::fs_ops::FsOps;
+#[cfg(feature = "git")]
+use crate::git::{GitRepo, GitStatus};
 use crate::list_state::ListState;
+use crate::mode::ModeChange;
+use crate::owners::Owners;
+use crate::plan::{Plan, PlannedOp};
+use crate::rename_pattern::{self, RenamePattern, RenamePreview};
+use crate::timestamps::{TimeSpec, Timestamps};
+use crate::vfs::{LocalFs, Metadata, Vfs};
 
 /// FileType of a given item, in which can be `Directory | File | Symlink`
 #[derive(Debug, Clone, PartialEq)]
@@ -12,6 +25,17 @@ pub enum FileType {
     Symlink,
 }
 
+/// Where a symbolic link points to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkTarget {
+    /// The target as stored on the link, which may be relative to the
+    /// directory of the link
+    pub path: PathBuf,
+    /// Type of the item the link resolves to, or `None` when the link is
+    /// broken
+    pub file_type: Option<FileType>,
+}
+
 /// `Item` is the representation of any contents of the filesystem.
 #[derive(Debug, Clone)]
 pub struct Item {
@@ -25,39 +49,109 @@ pub struct Item {
     pub file_ext: Option<String>,
     /// Whether this is considered a hidden file or not
     pub is_hidden: bool,
+    /// Size in bytes of the item itself, symbolic links are not followed.
+    /// For directories this is not the size of their contents, see
+    /// [`crate::disk_usage`] for that
+    pub size: u64,
+    /// Last modification time of the item, when available
+    pub modified: Option<SystemTime>,
+    /// Permission bits of the item itself, like `0o644`, when available
+    pub mode: Option<u32>,
+    /// Numeric ids of the owning user and group, when available, see
+    /// [`Owners`] for their names
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Where the item points to, for symbolic links
+    pub link: Option<LinkTarget>,
+    /// Git status of the item, or `None` when the item is unchanged or not
+    /// inside of a git work tree
+    #[cfg(feature = "git")]
+    pub git_status: Option<GitStatus>,
 }
 
+/// Lists and manipulates the contents of directories on a [`Vfs`]
+/// backend, the local file system by default.
 #[derive(Debug)]
-pub struct FileManager {
+pub struct FileManager<B = LocalFs>
+where
+    B: Vfs,
+{
+    backend: B,
     state: ListState,
     show_hidden: bool,
+    /// Where mutating operations are recorded, when enabled
+    audit: Option<AuditLog>,
+    /// Users and groups that names of owners resolve to, read from the
+    /// system when an owner is given by name unless set
+    owners: Option<Owners>,
+    /// Archives being browsed, the last one is nested inside of the ones
+    /// before it
+    #[cfg(feature = "archive")]
+    archives: Vec<Archive>,
 }
 
-impl FsOps<ListState> for FileManager {
+impl<B> FsOps<ListState> for FileManager<B>
+where
+    B: Vfs,
+{
     fn change_dir<P>(&mut self, path: P) -> anyhow::Result<&ListState>
     where
         P: AsRef<Path>,
     {
         let mut items = vec![];
         let path = path.as_ref().absolutize().unwrap();
-        for entry in std::fs::read_dir(path.clone())? {
-            let entry = entry?;
-            let is_hidden = self.is_hidden(entry.path())?;
+        #[cfg(feature = "archive")]
+        {
+            // leave every archive that doesn't contain the new directory
+            while let Some(archive) = self.archives.last() {
+                match path.starts_with(archive.path()) {
+                    true => return self.list_archive(&path),
+                    false => _ = self.archives.pop(),
+                }
+            }
+        }
+        #[cfg(feature = "git")]
+        let git_statuses = match self.backend.is_local() {
+            true => GitRepo::discover(&path)
+                .and_then(|repo| repo.statuses_in(&path).ok())
+                .unwrap_or_default(),
+            false => Default::default(),
+        };
+
+        for file_path in self.backend.list(&path)? {
+            let is_hidden = self.is_hidden(&file_path)?;
 
             if let (true, false) = (is_hidden, self.show_hidden) {
                 continue;
             }
 
-            let file_name = entry.file_name().to_string_lossy().to_string();
-            let file_path = entry.path();
-            let file_type = self.get_file_type(entry.path())?;
-            let file_ext = self.get_file_extension(entry.path());
+            let file_name = file_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let metadata = self.backend.lstat(&file_path)?;
+            let link = match metadata.file_type {
+                FileType::Symlink => self.link_target(&file_path),
+                _ => None,
+            };
             let item = Item {
+                #[cfg(feature = "git")]
+                git_status: file_path
+                    .file_name()
+                    .and_then(|name| git_statuses.get(name))
+                    .copied(),
                 file_name,
+                file_ext: self.get_file_extension(&file_path),
                 file_path,
-                file_type,
-                file_ext,
+                file_type: metadata.file_type,
                 is_hidden,
+                size: metadata.len,
+                modified: metadata.modified,
+                mode: metadata.mode,
+                uid: metadata.uid,
+                gid: metadata.gid,
+                link,
             };
             items.push(item);
         }
@@ -65,20 +159,61 @@ impl FsOps<ListState> for FileManager {
         let new_state = ListState {
             current_dir: path.as_ref().to_path_buf(),
             items,
+            focus: None,
         };
         self.state = new_state;
         Ok(&self.state)
     }
+
+    fn is_symlink<P>(&self, path: P) -> anyhow::Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(self.backend.lstat(path.as_ref())?.file_type == FileType::Symlink)
+    }
+
+    fn is_dir<P>(&self, path: P) -> anyhow::Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(self.backend.stat(path.as_ref())?.file_type == FileType::Directory)
+    }
+
+    fn is_file<P>(&self, path: P) -> anyhow::Result<bool>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(self.backend.stat(path.as_ref())?.file_type == FileType::File)
+    }
 }
 
-impl FileManager {
+impl FileManager<LocalFs> {
+    /// Creates a file manager for the local file system, listing `path`.
     pub fn new<T>(path: T) -> anyhow::Result<FileManager>
+    where
+        T: AsRef<Path>,
+    {
+        FileManager::with_backend(LocalFs, path)
+    }
+}
+
+impl<B> FileManager<B>
+where
+    B: Vfs,
+{
+    /// Creates a file manager for any backend, listing `path`.
+    pub fn with_backend<T>(backend: B, path: T) -> anyhow::Result<Self>
     where
         T: AsRef<Path>,
     {
         let mut fm = FileManager {
+            backend,
             state: ListState::default(),
             show_hidden: false,
+            audit: None,
+            owners: None,
+            #[cfg(feature = "archive")]
+            archives: vec![],
         };
 
         fm.change_dir(path)?;
@@ -93,15 +228,1016 @@ impl FileManager {
         &self.state
     }
 
+    /// Records every mutating operation executed from now on in `audit`,
+    /// or stops recording them with `None`.
+    pub fn set_audit_log(&mut self, audit: Option<AuditLog>) {
+        self.audit = audit;
+    }
+
+    /// Resolves names of owners with `owners` instead of the users and
+    /// groups of the system.
+    pub fn set_owners(&mut self, owners: Owners) {
+        self.owners = Some(owners);
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
     pub fn toggle_hidden(&mut self) -> anyhow::Result<&ListState> {
         self.show_hidden = !self.show_hidden;
+        self.refresh()
+    }
+
+    /// Re-fetches every item on the current directory, producing a new state.
+    pub fn refresh(&mut self) -> anyhow::Result<&ListState> {
         self.change_dir(self.state.current_dir.clone())
     }
+
+    /// Moves every path in `paths` to the trash of the platform, so they
+    /// can still be recovered, producing a new state.
+    pub fn trash<P>(&mut self, paths: &[P]) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+    {
+        self.delete(paths, false)?;
+        Ok(&self.state)
+    }
+
+    /// Renames `from` to `to`, which must not exist yet.
+    ///
+    /// Like every mutating operation, a `dry_run` checks everything it can
+    /// and returns the plan without touching anything. Otherwise the plan
+    /// is executed and the state refreshed.
+    pub fn rename<P, Q>(&mut self, from: P, to: Q, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        self.ensure_not_in_archive()?;
+        self.backend.lstat(from)?;
+        self.ensure_vacant(to)?;
+        let op = PlannedOp::Rename {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        };
+        self.execute(Plan::new(vec![op], dry_run))
+    }
+
+    /// Moves every path into the directory `dest`, keeping their names.
+    pub fn move_to<P, Q>(&mut self, paths: &[P], dest: Q, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let ops = self
+            .targets(paths, dest.as_ref())?
+            .into_iter()
+            .map(|(from, to)| PlannedOp::Rename { from, to })
+            .collect();
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Copies every path into the directory `dest`, keeping their names.
+    pub fn copy_to<P, Q>(&mut self, paths: &[P], dest: Q, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let ops = self
+            .targets(paths, dest.as_ref())?
+            .into_iter()
+            .map(|(from, to)| PlannedOp::Copy { from, to })
+            .collect();
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Moves every path to the trash, see [`FileManager::trash`].
+    pub fn delete<P>(&mut self, paths: &[P], dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        self.ensure_not_in_archive()?;
+        let mut ops = vec![];
+        for path in paths {
+            self.backend.lstat(path.as_ref())?;
+            ops.push(PlannedOp::Delete {
+                path: path.as_ref().to_path_buf(),
+            });
+        }
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Creates the directory `path`, its parent must already exist.
+    pub fn mkdir<P>(&mut self, path: P, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        self.ensure_not_in_archive()?;
+        self.ensure_vacant(path)?;
+        if let Some(parent) = path.parent() {
+            if self.backend.stat(parent)?.file_type != FileType::Directory {
+                anyhow::bail!("{} is not a directory", parent.display());
+            }
+        }
+        let op = PlannedOp::CreateDir {
+            path: path.to_path_buf(),
+        };
+        self.execute(Plan::new(vec![op], dry_run))
+    }
+
+    /// Creates an empty file at `path`, relative to the current directory
+    /// unless it's absolute, along with any missing parent directory. An
+    /// existing file is never overwritten.
+    ///
+    /// Once created, the new state lists the directory of the file, focused
+    /// on it.
+    pub fn create_file<P>(&mut self, path: P, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        self.create_file_with(path, b"", dry_run)
+    }
+
+    /// Creates a file with `contents`, like [`FileManager::create_file`].
+    /// Scripts starting with a shebang are made executable, which is only
+    /// planned once the file exists, since its mode isn't known before.
+    pub fn create_file_with<P>(
+        &mut self,
+        path: P,
+        contents: &[u8],
+        dry_run: bool,
+    ) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        let path = self.resolve_new(path.as_ref())?;
+        let mut ops = self.missing_parents(&path)?;
+        ops.push(PlannedOp::CreateFile {
+            path: path.clone(),
+            contents: contents.to_vec(),
+        });
+        let mut plan = self.execute(Plan::new(ops, dry_run))?;
+        if dry_run {
+            return Ok(plan);
+        }
+        if contents.starts_with(b"#!") {
+            if let Some(from) = self.backend.stat(&path)?.mode {
+                // executable by whoever can read it
+                let to = from | (from & 0o444) >> 2;
+                let op = PlannedOp::Chmod {
+                    path: path.clone(),
+                    from,
+                    to,
+                };
+                plan.ops
+                    .extend(self.execute(Plan::new(vec![op], false))?.ops);
+            }
+        }
+        self.reveal(&path)?;
+        Ok(plan)
+    }
+
+    /// Creates the directory `path` along with any missing parent, see
+    /// [`FileManager::create_file`].
+    pub fn create_dir<P>(&mut self, path: P, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        let path = self.resolve_new(path.as_ref())?;
+        let mut ops = self.missing_parents(&path)?;
+        ops.push(PlannedOp::CreateDir { path: path.clone() });
+        let plan = self.execute(Plan::new(ops, dry_run))?;
+        if !dry_run {
+            self.reveal(&path)?;
+        }
+        Ok(plan)
+    }
+
+    /// Makes `path` absolute against the current directory, failing when
+    /// it already exists.
+    fn resolve_new(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        self.ensure_not_in_archive()?;
+        if path.as_os_str().is_empty() {
+            anyhow::bail!("a name is required");
+        }
+        let path = path.absolutize_from(&self.state.current_dir)?.to_path_buf();
+        self.ensure_vacant(&path)?;
+        Ok(path)
+    }
+
+    /// Plans the creation of every ancestor of `path` that doesn't exist,
+    /// outermost first.
+    fn missing_parents(&self, path: &Path) -> anyhow::Result<Vec<PlannedOp>> {
+        let mut ops = vec![];
+        for ancestor in path.ancestors().skip(1) {
+            match self.backend.stat(ancestor) {
+                Ok(metadata) if metadata.file_type == FileType::Directory => break,
+                Ok(_) => anyhow::bail!("{} is not a directory", ancestor.display()),
+                Err(_) => ops.push(PlannedOp::CreateDir {
+                    path: ancestor.to_path_buf(),
+                }),
+            }
+        }
+        ops.reverse();
+        Ok(ops)
+    }
+
+    /// Lists the directory of `path`, focused on it.
+    pub fn reveal(&mut self, path: &Path) -> anyhow::Result<&ListState> {
+        if let Some(parent) = path.parent() {
+            if parent != self.state.current_dir {
+                self.change_dir(parent)?;
+            }
+        }
+        self.state.focus = Some(path.to_path_buf());
+        Ok(&self.state)
+    }
+
+    /// Creates a symbolic link at `link` pointing to `target`, both relative
+    /// to the current directory, along with the missing parents of `link`.
+    ///
+    /// When `relative`, the link stores the path from its own directory to
+    /// the target, computed after resolving the symbolic links of both, so
+    /// it keeps working when both move together, like in a dotfiles
+    /// repository. Otherwise it stores the absolute path of the target.
+    pub fn symlink<T, L>(
+        &mut self,
+        target: T,
+        link: L,
+        relative: bool,
+        dry_run: bool,
+    ) -> anyhow::Result<Plan>
+    where
+        T: AsRef<Path>,
+        L: AsRef<Path>,
+    {
+        let link = self.resolve_new(link.as_ref())?;
+        let target = target
+            .as_ref()
+            .absolutize_from(&self.state.current_dir)?
+            .to_path_buf();
+        if target == link {
+            anyhow::bail!("cannot link {} to itself", link.display());
+        }
+        let stored = match (relative, link.parent()) {
+            (true, Some(dir)) => relative_path(&self.physical(dir), &self.physical(&target)),
+            _ => target,
+        };
+        let mut ops = self.missing_parents(&link)?;
+        ops.push(PlannedOp::Symlink {
+            target: stored,
+            link,
+        });
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Creates a hard link at `link` to the file at `target`, both relative
+    /// to the current directory, along with the missing parents of `link`.
+    /// Both must be on the same file system.
+    pub fn hardlink<T, L>(&mut self, target: T, link: L, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        T: AsRef<Path>,
+        L: AsRef<Path>,
+    {
+        let link = self.resolve_new(link.as_ref())?;
+        let target = target
+            .as_ref()
+            .absolutize_from(&self.state.current_dir)?
+            .to_path_buf();
+        if self.backend.lstat(&target)?.file_type == FileType::Directory {
+            anyhow::bail!("cannot hard link the directory {}", target.display());
+        }
+        let mut ops = self.missing_parents(&link)?;
+        ops.push(PlannedOp::HardLink { target, link });
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Lists the directory that contains the real target of the symbolic
+    /// link at `path`, after following every link on the way, focused on
+    /// the target.
+    pub fn reveal_link_target<P>(&mut self, path: P) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let target = self.backend.read_link(path)?;
+        let real = self.backend.canonicalize(path).map_err(|_| {
+            anyhow::anyhow!(
+                "{} is a broken link to {}",
+                path.display(),
+                target.display()
+            )
+        })?;
+        self.reveal(&real)
+    }
+
+    fn link_target(&self, path: &Path) -> Option<LinkTarget> {
+        let target = self.backend.read_link(path).ok()?;
+        Some(LinkTarget {
+            path: target,
+            file_type: self.backend.stat(path).ok().map(|meta| meta.file_type),
+        })
+    }
+
+    /// Resolves the symbolic links of `path`, which may not exist yet, by
+    /// resolving its closest existing ancestor.
+    fn physical(&self, path: &Path) -> PathBuf {
+        for ancestor in path.ancestors() {
+            if let Ok(resolved) = self.backend.canonicalize(ancestor) {
+                let rest = path
+                    .strip_prefix(ancestor)
+                    .expect("path starts with ancestor");
+                return match rest.as_os_str().is_empty() {
+                    true => resolved,
+                    false => resolved.join(rest),
+                };
+            }
+        }
+        path.to_path_buf()
+    }
+
+    /// Sets the permission bits of every path to `mode`, following
+    /// symbolic links. Paths that already have `mode` are left out of the
+    /// plan.
+    pub fn chmod<P>(&mut self, paths: &[P], mode: u32, dry_run: bool) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        if mode > 0o7777 {
+            anyhow::bail!("{mode:o} is not a valid mode");
+        }
+        self.change_mode(paths, &ModeChange::Absolute(mode), false, dry_run)
+    }
+
+    /// Applies `change` to the permission bits of every path, following
+    /// symbolic links. When `recursive`, everything inside of directories
+    /// changes too, without following the links found on the way.
+    pub fn change_mode<P>(
+        &mut self,
+        paths: &[P],
+        change: &ModeChange,
+        recursive: bool,
+        dry_run: bool,
+    ) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        self.ensure_not_in_archive()?;
+        let mut ops = vec![];
+        for path in paths {
+            self.walk(path.as_ref(), recursive, &mut |path, metadata| {
+                let is_dir = metadata.file_type == FileType::Directory;
+                let from = metadata.mode.ok_or_else(|| {
+                    anyhow::anyhow!("permissions of {} are unknown", path.display())
+                })?;
+                let to = change.apply(from, is_dir);
+                if from != to {
+                    ops.push(PlannedOp::Chmod {
+                        path: path.to_path_buf(),
+                        from,
+                        to,
+                    });
+                }
+                Ok(())
+            })?;
+        }
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Sets the owning user and group of every path, following symbolic
+    /// links. Both can be given by name or by numeric id, `None` keeps the
+    /// current one, so only passing a group is chgrp. When `recursive`,
+    /// everything inside of directories changes too.
+    ///
+    /// Names are resolved with the users and groups of this machine, even
+    /// for remote backends, see [`FileManager::set_owners`].
+    pub fn chown<P>(
+        &mut self,
+        paths: &[P],
+        user: Option<&str>,
+        group: Option<&str>,
+        recursive: bool,
+        dry_run: bool,
+    ) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        self.ensure_not_in_archive()?;
+        let uid = user
+            .map(|user| self.resolve_owner(user, Owners::uid))
+            .transpose()?;
+        let gid = group
+            .map(|group| self.resolve_owner(group, Owners::gid))
+            .transpose()?;
+        let mut ops = vec![];
+        for path in paths {
+            self.walk(path.as_ref(), recursive, &mut |path, metadata| {
+                let (Some(current_uid), Some(current_gid)) = (metadata.uid, metadata.gid) else {
+                    anyhow::bail!("owner of {} is unknown", path.display());
+                };
+                let from = (current_uid, current_gid);
+                let to = (uid.unwrap_or(current_uid), gid.unwrap_or(current_gid));
+                if from != to {
+                    ops.push(PlannedOp::Chown {
+                        path: path.to_path_buf(),
+                        from,
+                        to,
+                    });
+                }
+                Ok(())
+            })?;
+        }
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Sets the access or modification times of every path, or both, to
+    /// `time`, following symbolic links. The time is taken once, so every
+    /// item gets the same one, and a reference is read relative to the
+    /// current directory. Items that already have the time are left out
+    /// of the plan. When `recursive`, everything inside of directories
+    /// changes too.
+    pub fn touch<P>(
+        &mut self,
+        paths: &[P],
+        time: &TimeSpec,
+        timestamps: Timestamps,
+        recursive: bool,
+        dry_run: bool,
+    ) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        self.ensure_not_in_archive()?;
+        let (accessed, modified) = match time {
+            TimeSpec::Reference(reference) => {
+                let reference = reference.absolutize_from(&self.state.current_dir)?;
+                let metadata = self.backend.stat(&reference)?;
+                let unknown = |name: &str| {
+                    anyhow::anyhow!("{name} time of {} is unknown", reference.display())
+                };
+                (
+                    metadata.accessed.ok_or_else(|| unknown("access"))?,
+                    metadata.modified.ok_or_else(|| unknown("modification"))?,
+                )
+            }
+            time => {
+                let time = time.at(SystemTime::now())?.expect("not a reference");
+                (time, time)
+            }
+        };
+        let accessed = timestamps.access().then_some(accessed);
+        let modified = timestamps.modification().then_some(modified);
+        let mut ops = vec![];
+        for path in paths {
+            self.walk(path.as_ref(), recursive, &mut |path, metadata| {
+                let is_set = |time: Option<SystemTime>, current| time.is_none() || time == current;
+                if !is_set(accessed, metadata.accessed) || !is_set(modified, metadata.modified) {
+                    ops.push(PlannedOp::SetTimes {
+                        path: path.to_path_buf(),
+                        accessed,
+                        modified,
+                    });
+                }
+                Ok(())
+            })?;
+        }
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Resolves a user or group, only reading the system files when it's
+    /// given by name.
+    fn resolve_owner<F>(&mut self, name: &str, resolve: F) -> anyhow::Result<u32>
+    where
+        F: Fn(&Owners, &str) -> anyhow::Result<u32>,
+    {
+        if let Ok(id) = name.parse() {
+            return Ok(id);
+        }
+        if self.owners.is_none() {
+            self.owners = Some(Owners::load()?);
+        }
+        resolve(self.owners.as_ref().expect("owners were just loaded"), name)
+    }
+
+    /// Calls `visit` with `path`, following symbolic links, and when
+    /// `recursive` with everything inside of it, without following the
+    /// links found on the way.
+    fn walk<F>(&self, path: &Path, recursive: bool, visit: &mut F) -> anyhow::Result<()>
+    where
+        F: FnMut(&Path, &Metadata) -> anyhow::Result<()>,
+    {
+        let metadata = self.backend.stat(path)?;
+        self.walk_inside(path, &metadata, recursive, visit)
+    }
+
+    fn walk_inside<F>(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+        recursive: bool,
+        visit: &mut F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnMut(&Path, &Metadata) -> anyhow::Result<()>,
+    {
+        visit(path, metadata)?;
+        if recursive && metadata.file_type == FileType::Directory {
+            let mut children = self.backend.list(path)?;
+            children.sort();
+            for child in children {
+                let metadata = self.backend.lstat(&child)?;
+                if metadata.file_type != FileType::Symlink {
+                    self.walk_inside(&child, &metadata, recursive, visit)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the changes made to the buffer of `edit`: renames, items
+    /// to trash and items to create, see [`BulkEdit::diff`].
+    ///
+    /// Items are trashed first, then renamed, so their names can be
+    /// reused. Renames onto a name that is renamed away wait for it, and
+    /// swaps or longer cycles go through a temporary name. Missing parent
+    /// directories are created, and nothing is ever overwritten.
+    pub fn bulk_rename(
+        &mut self,
+        edit: &BulkEdit,
+        edited: &str,
+        dry_run: bool,
+    ) -> anyhow::Result<Plan> {
+        self.ensure_not_in_archive()?;
+        let changes = edit.diff(edited)?;
+        if let Some((target, first, second)) = bulk_rename::find_collision(&changes) {
+            anyhow::bail!(
+                "{} is the new name of both {} and {}",
+                target.display(),
+                first.display(),
+                second.display()
+            );
+        }
+        // names that are free once the plan runs
+        let freed = changes
+            .iter()
+            .filter_map(|change| match change {
+                BulkChange::Rename { from, .. } | BulkChange::Delete(from) => Some(from.clone()),
+                BulkChange::Create { .. } => None,
+            })
+            .collect::<Vec<_>>();
+
+        let (mut deletes, mut parents, mut renames, mut creates) = (vec![], vec![], vec![], vec![]);
+        for change in changes {
+            match change {
+                BulkChange::Delete(path) => {
+                    self.backend.lstat(&path)?;
+                    deletes.push(PlannedOp::Delete { path });
+                }
+                BulkChange::Rename { from, to } => {
+                    self.backend.lstat(&from)?;
+                    if to.starts_with(&from) {
+                        anyhow::bail!("cannot put {} inside of itself", from.display());
+                    }
+                    if !freed.contains(&to) {
+                        self.ensure_vacant(&to)?;
+                    }
+                    parents.extend(self.missing_parents(&to)?);
+                    renames.push((from, to));
+                }
+                BulkChange::Create { path, is_dir } => {
+                    if !freed.contains(&path) {
+                        self.ensure_vacant(&path)?;
+                    }
+                    creates.extend(self.missing_parents(&path)?);
+                    creates.push(match is_dir {
+                        true => PlannedOp::CreateDir { path },
+                        false => PlannedOp::CreateFile {
+                            path,
+                            contents: vec![],
+                        },
+                    });
+                }
+            }
+        }
+
+        let mut ops = deletes;
+        ops.extend(parents);
+        ops.extend(bulk_rename::order_renames(renames, |path| {
+            self.backend.lstat(path).is_err()
+        }));
+        ops.extend(creates);
+        // parents shared by many items are only created once
+        let mut created = vec![];
+        ops.retain(|op| match op {
+            PlannedOp::CreateDir { path } if created.contains(path) => false,
+            PlannedOp::CreateDir { path } => {
+                created.push(path.clone());
+                true
+            }
+            _ => true,
+        });
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Computes the new name of every path with `pattern`, in the order
+    /// given, flagging the ones that can't be renamed. Nothing is touched.
+    pub fn preview_rename<P>(
+        &self,
+        paths: &[P],
+        pattern: &RenamePattern,
+    ) -> anyhow::Result<Vec<RenamePreview>>
+    where
+        P: AsRef<Path>,
+    {
+        rename_pattern::preview_rename(paths, pattern, |path| self.backend.lstat(path).is_ok())
+    }
+
+    /// Renames every path with `pattern`, see [`FileManager::preview_rename`].
+    /// Fails without renaming anything when any item can't take its new
+    /// name. Swaps between items go through a temporary name.
+    pub fn pattern_rename<P>(
+        &mut self,
+        paths: &[P],
+        pattern: &RenamePattern,
+        dry_run: bool,
+    ) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        self.ensure_not_in_archive()?;
+        let mut renames = vec![];
+        for preview in self.preview_rename(paths, pattern)? {
+            if let Some(conflict) = preview.conflict {
+                let name = preview.to.file_name().unwrap_or_default().to_string_lossy();
+                anyhow::bail!(
+                    "cannot rename {} to {name:?}: {conflict}",
+                    preview.from.display()
+                );
+            }
+            if preview.is_changed() {
+                self.backend.lstat(&preview.from)?;
+                renames.push((preview.from, preview.to));
+            }
+        }
+        let ops = bulk_rename::order_renames(renames, |path| self.backend.lstat(path).is_err());
+        self.execute(Plan::new(ops, dry_run))
+    }
+
+    /// Pairs every path with where it ends up inside of `dest`, failing
+    /// when any of them already exists or when a directory would end up
+    /// inside of itself.
+    fn targets<P>(&self, paths: &[P], dest: &Path) -> anyhow::Result<Vec<(PathBuf, PathBuf)>>
+    where
+        P: AsRef<Path>,
+    {
+        self.ensure_not_in_archive()?;
+        if self.backend.stat(dest)?.file_type != FileType::Directory {
+            anyhow::bail!("{} is not a directory", dest.display());
+        }
+        let mut targets = vec![];
+        for path in paths {
+            let path = path.as_ref();
+            self.backend.lstat(path)?;
+            if dest.starts_with(path) {
+                anyhow::bail!("cannot put {} inside of itself", path.display());
+            }
+            let name = path
+                .file_name()
+                .ok_or_else(|| anyhow::anyhow!("{} has no name", path.display()))?;
+            let target = dest.join(name);
+            self.ensure_vacant(&target)?;
+            targets.push((path.to_path_buf(), target));
+        }
+        Ok(targets)
+    }
+
+    fn ensure_vacant(&self, path: &Path) -> anyhow::Result<()> {
+        match self.backend.lstat(path) {
+            Ok(_) => anyhow::bail!("{} already exists", path.display()),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Executes every operation of `plan` unless it's a dry run,
+    /// refreshing the state afterwards even when an operation failed.
+    fn execute(&mut self, plan: Plan) -> anyhow::Result<Plan> {
+        if plan.dry_run {
+            return Ok(plan);
+        }
+        let result = plan.ops.iter().try_for_each(|op| {
+            let result = match op {
+                PlannedOp::Rename { from, to } => self.backend.rename(from, to),
+                PlannedOp::Copy { from, to } => match self.backend.is_local() {
+                    true => crate::ops::copy(from, to),
+                    false => self.copy_with_backend(from, to),
+                },
+                PlannedOp::Delete { path } => self.backend.trash(path),
+                PlannedOp::CreateDir { path } => self.backend.mkdir(path),
+                PlannedOp::CreateFile { path, contents } => self.backend.create_new(path, contents),
+                PlannedOp::Chmod { path, to, .. } => self.backend.set_mode(path, *to),
+                PlannedOp::Chown { path, to, .. } => self.backend.set_owner(path, to.0, to.1),
+                PlannedOp::SetTimes {
+                    path,
+                    accessed,
+                    modified,
+                } => self.backend.set_times(path, *accessed, *modified),
+                PlannedOp::Symlink { target, link } => self.backend.symlink(target, link),
+                PlannedOp::HardLink { target, link } => self.backend.hard_link(target, link),
+            };
+            audited(self.audit.as_ref(), op, result)
+        });
+        // the error of the operation matters more than the one of refreshing
+        let refreshed = self.refresh().map(|_| ());
+        result?;
+        refreshed?;
+        Ok(plan)
+    }
+
+    /// Records the outcome of an operation that isn't part of a plan once
+    /// for each of `sources`.
+    #[cfg(any(feature = "git", feature = "archive"))]
+    fn record_all<P, T>(
+        &self,
+        op: &str,
+        sources: &[P],
+        destination: Option<&Path>,
+        result: &anyhow::Result<T>,
+    ) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        if let Some(audit) = &self.audit {
+            for source in sources {
+                audit.record_other(op, source.as_ref(), destination, result)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies recursively through the backend, for backends that aren't
+    /// the local file system.
+    fn copy_with_backend(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        match self.backend.lstat(from)?.file_type {
+            FileType::File => self.backend.write(to, &self.backend.read(from)?),
+            FileType::Directory => {
+                self.backend.mkdir(to)?;
+                for child in self.backend.list(from)? {
+                    let name = child.file_name().unwrap_or_default();
+                    self.copy_with_backend(&child, &to.join(name))?;
+                }
+                Ok(())
+            }
+            FileType::Symlink => anyhow::bail!(
+                "cannot copy the symbolic link {} on this backend",
+                from.display()
+            ),
+        }
+    }
+
+    #[cfg(feature = "archive")]
+    fn ensure_not_in_archive(&self) -> anyhow::Result<()> {
+        match self.is_in_archive() {
+            true => anyhow::bail!("items inside of an archive can't be modified"),
+            false => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "archive"))]
+    fn ensure_not_in_archive(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// The path that leads from the directory `from` to `to`, both absolute
+/// and without `.` or `..` components.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    relative.extend(&to[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+#[cfg(feature = "archive")]
+impl<B> FileManager<B>
+where
+    B: Vfs,
+{
+    /// Opens the archive at `path` to be browsed as a directory, producing
+    /// a state with the root of the archive. Items inside of an archive
+    /// have paths as if the archive was a directory, and archives nested
+    /// inside of the current archive can be opened as well.
+    pub fn open_archive<P>(&mut self, path: P) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().absolutize()?.to_path_buf();
+        let nested = match self.archives.last() {
+            Some(parent) if path.starts_with(parent.path()) => {
+                let bytes = parent.read(path.strip_prefix(parent.path())?)?;
+                Some(Archive::from_bytes(&path, bytes)?)
+            }
+            _ => None,
+        };
+        let archive = match nested {
+            Some(archive) => archive,
+            None => {
+                let archive = match self.backend.is_local() {
+                    true => Archive::open(&path)?,
+                    false => Archive::from_bytes(&path, self.backend.read(&path)?)?,
+                };
+                self.archives.clear();
+                archive
+            }
+        };
+        self.archives.push(archive);
+        self.change_dir(path)
+    }
+
+    /// Whether the current directory is inside of an archive.
+    pub fn is_in_archive(&self) -> bool {
+        !self.archives.is_empty()
+    }
+
+    /// Returns the path of the outermost archive being browsed, the one
+    /// that exists on the file system.
+    pub fn archive_root(&self) -> Option<&Path> {
+        self.archives.first().map(|archive| archive.path())
+    }
+
+    /// Extracts `paths` from the archive being browsed into `dest`. See
+    /// [`Archive::extract`].
+    pub fn extract<P, Q>(&mut self, paths: &[P], dest: Q) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let archive = self
+            .archives
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("not inside of an archive"))?;
+        if !self.backend.is_local() {
+            anyhow::bail!("archives can only be extracted on the local file system");
+        }
+        let paths = paths
+            .iter()
+            .map(|path| path.as_ref().strip_prefix(archive.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let dest = dest.as_ref();
+        let result = archive.extract(&paths, dest);
+        let sources = paths
+            .iter()
+            .map(|path| archive.path().join(path))
+            .collect::<Vec<_>>();
+        self.record_all("extract", &sources, Some(dest), &result)?;
+        result?;
+        Ok(&self.state)
+    }
+
+    fn list_archive(&mut self, path: &Path) -> anyhow::Result<&ListState> {
+        let archive = self.archives.last().expect("an archive is being browsed");
+        let relative = path.strip_prefix(archive.path())?;
+        if let Some(entry) = archive.entry(relative) {
+            if entry.file_type != FileType::Directory {
+                anyhow::bail!("{} is not a directory", path.display());
+            }
+        }
+
+        let mut items = vec![];
+        for entry in archive.list(relative) {
+            let file_path = archive.path().join(&entry.path);
+            let is_hidden = self.is_hidden(&file_path)?;
+            if let (true, false) = (is_hidden, self.show_hidden) {
+                continue;
+            }
+            items.push(Item {
+                file_name: entry
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                file_ext: self.get_file_extension(&file_path),
+                file_path,
+                file_type: entry.file_type.clone(),
+                is_hidden,
+                size: entry.size,
+                modified: entry.modified,
+                mode: None,
+                uid: None,
+                gid: None,
+                link: None,
+                #[cfg(feature = "git")]
+                git_status: None,
+            });
+        }
+
+        self.state = ListState {
+            current_dir: path.to_path_buf(),
+            items,
+            focus: None,
+        };
+        Ok(&self.state)
+    }
+}
+
+#[cfg(feature = "git")]
+impl<B> FileManager<B>
+where
+    B: Vfs,
+{
+    /// Stages every change inside of `paths` on the repository containing
+    /// the current directory.
+    pub fn git_stage<P>(&mut self, paths: &[P]) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+    {
+        let result = self.current_repo()?.stage(paths);
+        self.record_all("git stage", paths, None, &result)?;
+        result?;
+        self.refresh()
+    }
+
+    /// Removes the staged changes of `paths` from the index.
+    pub fn git_unstage<P>(&mut self, paths: &[P]) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+    {
+        let result = self.current_repo()?.unstage(paths);
+        self.record_all("git unstage", paths, None, &result)?;
+        result?;
+        self.refresh()
+    }
+
+    /// Discards the working tree changes inside of `paths`, moving anything
+    /// that would be lost to the trash. See [`GitRepo::discard`].
+    pub fn git_discard<P>(&mut self, paths: &[P]) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+    {
+        let result = self.current_repo()?.discard_with(paths, |paths| {
+            paths.iter().try_for_each(|path| self.backend.trash(path))
+        });
+        self.record_all("git discard", paths, None, &result)?;
+        result?;
+        self.refresh()
+    }
+
+    /// Plans [`FileManager::git_discard`], listing the items that would be
+    /// moved to the trash, so it can be confirmed first.
+    pub fn git_discard_plan<P>(&self, paths: &[P]) -> anyhow::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        let ops = self
+            .current_repo()?
+            .discarded(paths)?
+            .into_iter()
+            .map(|path| PlannedOp::Delete { path })
+            .collect();
+        Ok(Plan::new(ops, true))
+    }
+
+    /// Adds `paths` to the `.gitignore` at the root of the work tree.
+    pub fn git_ignore<P>(&mut self, paths: &[P]) -> anyhow::Result<&ListState>
+    where
+        P: AsRef<Path>,
+    {
+        let result = self.current_repo()?.ignore(paths);
+        self.record_all("git ignore", paths, None, &result)?;
+        result?;
+        self.refresh()
+    }
+
+    fn current_repo(&self) -> anyhow::Result<GitRepo> {
+        self.ensure_not_in_archive()?;
+        if !self.backend.is_local() {
+            anyhow::bail!("git is only available on the local file system");
+        }
+        GitRepo::discover(&self.state.current_dir).ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} is not inside a git work tree",
+                self.state.current_dir.display()
+            )
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rename_pattern::{Case, RenameConflict};
+    use crate::vfs::MemFs;
     use std::fs::File;
     use std::io::Write;
     use tempfile::{tempdir, TempDir};
@@ -159,6 +1295,637 @@ mod tests {
         assert!(!expect_false);
     }
 
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_items_have_git_status() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let repo = git2::Repository::init(dir.path()).expect("failed to init repository");
+        std::fs::write(dir.path().join("tracked.txt"), "tracked").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("tracked.txt")).unwrap();
+        index.write().unwrap();
+        std::fs::write(dir.path().join("untracked.txt"), "untracked").unwrap();
+
+        let sut = FileManager::new(dir.path()).expect("failed to create file manager");
+        let state = sut.get_state();
+        let status_of = |name: &str| {
+            state
+                .items
+                .iter()
+                .find(|item| item.file_name == name)
+                .and_then(|item| item.git_status)
+        };
+
+        assert_eq!(status_of("tracked.txt"), Some(GitStatus::Staged));
+        assert_eq!(status_of("untracked.txt"), Some(GitStatus::Untracked));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_stage_outside_work_tree() {
+        let (dir, mut sut) = make_sut();
+
+        let result = sut.git_stage(&[dir.path().join("0tempfile.txt")]);
+
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_items_outside_work_tree_have_no_git_status() {
+        let (_, sut) = make_sut();
+
+        let state = sut.get_state();
+
+        assert!(state.items.iter().all(|item| item.git_status.is_none()));
+    }
+
+    #[test]
+    fn test_trash() {
+        let fs = MemFs::new()
+            .with_file("/home/a.txt", "a")
+            .and_then(|fs| fs.with_file("/home/b.txt", "b"))
+            .and_then(|fs| fs.with_trash("/trash"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/home").expect("failed to create");
+
+        let state = sut.trash(&["/home/a.txt"]).expect("failed to trash item");
+
+        assert_eq!(state.items.len(), 1);
+        let trashed = sut.backend().read(Path::new("/trash/a.txt")).unwrap();
+        assert_eq!(trashed, b"a");
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_browse_archive() {
+        let (dir, mut sut) = make_sut();
+        let archive = dir.path().join("archive.tar");
+        let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "nested/file.txt", &b"hi"[..])
+            .unwrap();
+        builder.finish().unwrap();
+
+        let state = sut.open_archive(&archive).expect("failed to open archive");
+        assert_eq!(state.current_dir, archive);
+        assert_eq!(state.items[0].file_type, FileType::Directory);
+
+        let state = sut
+            .change_dir(archive.join("nested"))
+            .expect("failed to change dir");
+        assert_eq!(state.items[0].file_path, archive.join("nested/file.txt"));
+        assert_eq!(state.items[0].size, 2);
+        assert!(sut.trash(&[archive.join("nested/file.txt")]).is_err());
+
+        let state = sut.change_dir(dir.path()).expect("failed to leave archive");
+        assert_eq!(state.items.len(), 11);
+        assert!(!sut.is_in_archive());
+    }
+
+    #[test]
+    fn test_memory_backend() {
+        let fs = MemFs::new()
+            .with_file("/project/src/main.rs", "fn main() {}")
+            .and_then(|fs| fs.with_file("/project/.env", "SECRET=1"))
+            .and_then(|fs| fs.with_symlink("/project/main", "src/main.rs"))
+            .expect("failed to set up memory file system");
+
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+        let state = sut.get_state();
+        let names = state
+            .items
+            .iter()
+            .map(|item| (item.file_name.as_str(), item.file_type.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            vec![("main", FileType::Symlink), ("src", FileType::Directory)]
+        );
+        assert!(sut.is_file("/project/main").unwrap());
+        assert_eq!(sut.toggle_hidden().unwrap().items.len(), 3);
+        assert!(sut.trash(&["/project/.env"]).is_err());
+    }
+
+    #[test]
+    fn test_dry_run_leaves_everything_untouched() {
+        let fs = MemFs::new()
+            .with_file("/project/notes.txt", "notes")
+            .and_then(|fs| fs.with_dir("/project/docs"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+        let notes = Path::new("/project/notes.txt");
+
+        let plans = [
+            sut.rename(notes, "/project/todo.txt", true).unwrap(),
+            sut.move_to(&[notes], "/project/docs", true).unwrap(),
+            sut.copy_to(&[notes], "/project/docs", true).unwrap(),
+            sut.delete(&[notes], true).unwrap(),
+            sut.mkdir("/project/src", true).unwrap(),
+            sut.chmod(&[notes], 0o600, true).unwrap(),
+        ];
+
+        assert!(plans.iter().all(|plan| plan.dry_run && plan.len() == 1));
+        assert_eq!(
+            plans[5].ops[0],
+            PlannedOp::Chmod {
+                path: notes.to_path_buf(),
+                from: 0o644,
+                to: 0o600
+            }
+        );
+        let names = sut
+            .refresh()
+            .unwrap()
+            .items
+            .iter()
+            .map(|i| i.file_name.clone());
+        assert_eq!(names.collect::<Vec<_>>(), vec!["docs", "notes.txt"]);
+        assert_eq!(sut.backend().lstat(notes).unwrap().mode, Some(0o644));
+    }
+
+    #[test]
+    fn test_mutating_operations() {
+        let fs = MemFs::new()
+            .with_file("/project/notes.txt", "notes")
+            .and_then(|fs| fs.with_dir("/project/docs"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+
+        sut.mkdir("/project/src", false).unwrap();
+        sut.copy_to(&["/project/notes.txt"], "/project/src", false)
+            .unwrap();
+        sut.move_to(&["/project/notes.txt"], "/project/docs", false)
+            .unwrap();
+        sut.chmod(&["/project/docs/notes.txt"], 0o600, false)
+            .unwrap();
+        let plan = sut.rename("/project/src", "/project/lib", false).unwrap();
+
+        assert!(!plan.dry_run);
+        let backend = sut.backend();
+        assert_eq!(
+            backend.read(Path::new("/project/lib/notes.txt")).unwrap(),
+            b"notes"
+        );
+        let moved = backend.lstat(Path::new("/project/docs/notes.txt")).unwrap();
+        assert_eq!(moved.mode, Some(0o600));
+        let names = sut.get_state().items.iter().map(|i| i.file_name.clone());
+        assert_eq!(names.collect::<Vec<_>>(), vec!["docs", "lib"]);
+    }
+
+    #[test]
+    fn test_mutating_operations_never_overwrite() {
+        let fs = MemFs::new()
+            .with_file("/project/a.txt", "a")
+            .and_then(|fs| fs.with_file("/project/docs/a.txt", "precious"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+
+        assert!(sut
+            .rename("/project/a.txt", "/project/docs/a.txt", true)
+            .is_err());
+        assert!(sut
+            .copy_to(&["/project/a.txt"], "/project/docs", false)
+            .is_err());
+        assert!(sut
+            .move_to(&["/project/docs"], "/project/docs", true)
+            .is_err());
+        assert!(sut.mkdir("/project/docs", false).is_err());
+        let kept = sut
+            .backend()
+            .read(Path::new("/project/docs/a.txt"))
+            .unwrap();
+        assert_eq!(kept, b"precious");
+    }
+
+    #[test]
+    fn test_audit_log_records_executed_operations() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let fs = MemFs::new()
+            .with_file("/project/notes.txt", "notes")
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+        let audit = AuditLog::new(dir.path());
+        sut.set_audit_log(Some(audit.clone()));
+
+        sut.mkdir("/project/docs", true).unwrap();
+        sut.rename("/project/notes.txt", "/project/todo.txt", false)
+            .unwrap();
+        sut.delete(&["/project/todo.txt"], false).unwrap_err();
+
+        let log = std::fs::read_to_string(audit.path()).expect("failed to read audit log");
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""op":"rename""#));
+        assert!(lines[1].contains(r#""op":"trash""#));
+        assert!(lines[1].contains(r#""result":"error""#));
+    }
+
+    #[test]
+    fn test_create_file_with_missing_parents() {
+        let fs = MemFs::new()
+            .with_file("/project/README.md", "readme")
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+
+        let plan = sut.create_file("src/new/mod.rs", true).unwrap();
+        assert_eq!(plan.len(), 3);
+        assert!(sut.backend().lstat(Path::new("/project/src")).is_err());
+        sut.create_file("src/new/mod.rs", false).unwrap();
+
+        let state = sut.get_state();
+        assert_eq!(state.current_dir, PathBuf::from("/project/src/new"));
+        assert_eq!(state.focus, Some(PathBuf::from("/project/src/new/mod.rs")));
+        assert_eq!(state.items[0].file_name, "mod.rs");
+        let contents = sut.backend().read(Path::new("/project/src/new/mod.rs"));
+        assert_eq!(contents.unwrap(), b"");
+    }
+
+    #[test]
+    fn test_create_script_is_executable() {
+        let fs = MemFs::new()
+            .with_dir("/project")
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+
+        let plan = sut.create_file_with("bin/run", b"#!/bin/sh\n", false);
+        sut.create_file_with("/project/notes.txt", b"#notes", false)
+            .unwrap();
+
+        assert!(matches!(
+            plan.unwrap().ops.last(),
+            Some(PlannedOp::Chmod { to: 0o755, .. })
+        ));
+
+        let mode = |path: &str| sut.backend().stat(Path::new(path)).unwrap().mode;
+        assert_eq!(mode("/project/bin/run"), Some(0o755));
+        assert_eq!(mode("/project/notes.txt"), Some(0o644));
+    }
+
+    #[test]
+    fn test_create_never_overwrites() {
+        let fs = MemFs::new()
+            .with_file("/project/README.md", "readme")
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+
+        assert!(sut.create_file("README.md", true).is_err());
+        assert!(sut.create_dir("README.md", false).is_err());
+        assert!(sut.create_file("README.md/nested", false).is_err());
+        assert!(sut.create_file("", false).is_err());
+
+        sut.create_dir("docs/api", false).unwrap();
+        assert_eq!(
+            sut.get_state().focus,
+            Some(PathBuf::from("/project/docs/api"))
+        );
+        assert_eq!(
+            sut.backend().read(Path::new("/project/README.md")).unwrap(),
+            b"readme"
+        );
+    }
+
+    #[test]
+    fn test_change_mode_recursively() {
+        let fs = MemFs::new()
+            .with_file("/project/src/main.rs", "fn main() {}")
+            .and_then(|fs| fs.with_file("/project/build.sh", "make"))
+            .and_then(|fs| fs.with_symlink("/project/src/link", "../build.sh"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+        let change = "go-rx,u+X".parse::<ModeChange>().unwrap();
+
+        let plan = sut
+            .change_mode(&["/project/src"], &change, true, false)
+            .unwrap();
+
+        let ops = plan.ops.iter().map(|op| op.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                "chmod 755 -> 700 /project/src",
+                "chmod 644 -> 600 /project/src/main.rs"
+            ]
+        );
+        let mode = |path: &str| sut.backend().lstat(Path::new(path)).unwrap().mode;
+        assert_eq!(mode("/project/build.sh"), Some(0o644));
+        assert_eq!(mode("/project/src"), Some(0o700));
+    }
+
+    #[test]
+    fn test_chown_by_name_and_id() {
+        let fs = MemFs::new()
+            .with_file("/project/src/main.rs", "fn main() {}")
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/project").expect("failed to create");
+        let owners = Owners::parse(
+            "ada:x:1000:1000::/:/bin/sh\ngrace:x:1001:1001::/:/bin/sh\n",
+            "ada:x:1000:\nstaff:x:50:\n",
+        )
+        .expect("failed to parse owners");
+        sut.set_owners(owners);
+
+        let plan = sut
+            .chown(&["/project/src"], Some("grace"), None, true, true)
+            .unwrap();
+        assert_eq!(
+            plan.ops[1].to_string(),
+            "chown 1000:1000 -> 1001:1000 /project/src/main.rs"
+        );
+
+        sut.chown(&["/project/src/main.rs"], None, Some("staff"), false, false)
+            .unwrap();
+        sut.chown(&["/project"], Some("4242"), Some("4242"), false, false)
+            .unwrap();
+        let owner = |path: &str| {
+            let metadata = sut.backend().lstat(Path::new(path)).unwrap();
+            (metadata.uid, metadata.gid)
+        };
+        assert_eq!(owner("/project/src/main.rs"), (Some(1000), Some(50)));
+        assert_eq!(owner("/project/src"), (Some(1000), Some(1000)));
+        assert_eq!(owner("/project"), (Some(4242), Some(4242)));
+
+        let error = sut
+            .chown(&["/project"], Some("linus"), None, false, false)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "no user named linus");
+    }
+
+    #[test]
+    fn test_touch() {
+        let fs = MemFs::new()
+            .with_file("/build/out/app", "binary")
+            .and_then(|fs| fs.with_file("/build/stamp", ""))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/build").expect("failed to create");
+        let epoch = "@1706702400".parse::<TimeSpec>().unwrap();
+        let times = |sut: &FileManager<MemFs>, path: &str| {
+            let metadata = sut.backend().stat(Path::new(path)).unwrap();
+            (metadata.accessed, metadata.modified)
+        };
+
+        let plan = sut
+            .touch(
+                &["/build/out"],
+                &epoch,
+                Timestamps::Modification,
+                true,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            plan.ops[1].to_string(),
+            "touch mtime 2024-01-31T12:00:00Z /build/out/app"
+        );
+        let at = Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1706702400));
+        assert_eq!(times(&sut, "/build/out/app").1, at);
+        assert_ne!(times(&sut, "/build/out/app").0, at);
+
+        // already set, nothing left to do
+        let plan = sut
+            .touch(
+                &["/build/out"],
+                &epoch,
+                Timestamps::Modification,
+                true,
+                true,
+            )
+            .unwrap();
+        assert!(plan.is_empty());
+
+        let reference = TimeSpec::Reference("out/app".into());
+        sut.touch(
+            &["/build/stamp"],
+            &reference,
+            Timestamps::Both,
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(times(&sut, "/build/stamp"), times(&sut, "/build/out/app"));
+
+        let error = sut
+            .touch(
+                &["/build/missing"],
+                &TimeSpec::Now,
+                Timestamps::Both,
+                false,
+                true,
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "/build/missing: no such file or directory"
+        );
+    }
+
+    #[test]
+    fn test_relative_symlinks() {
+        let fs = MemFs::new()
+            .with_file("/home/ada/dotfiles/vimrc", "set number")
+            .and_then(|fs| fs.with_file("/home/ada/dotfiles/nvim/init.lua", "-- nvim"))
+            .and_then(|fs| fs.with_dir("/home/ada/.config"))
+            .and_then(|fs| fs.with_symlink("/home/ada/cfg", ".config"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/home/ada").expect("failed to create");
+
+        sut.symlink("dotfiles/vimrc", ".vimrc", true, false)
+            .unwrap();
+        // the link goes into .config, through the cfg link
+        let plan = sut
+            .symlink("/home/ada/dotfiles/nvim", "cfg/nvim", true, false)
+            .unwrap();
+        sut.symlink("dotfiles/vimrc", "backup/vimrc", false, false)
+            .unwrap();
+
+        assert_eq!(
+            plan.ops[0].to_string(),
+            "symlink /home/ada/cfg/nvim -> ../dotfiles/nvim"
+        );
+        let read = |path: &str| sut.backend().read(Path::new(path)).unwrap();
+        assert_eq!(read("/home/ada/.vimrc"), b"set number");
+        assert_eq!(read("/home/ada/.config/nvim/init.lua"), b"-- nvim");
+        assert_eq!(read("/home/ada/backup/vimrc"), b"set number");
+        let error = sut.symlink("dotfiles", ".vimrc", true, false).unwrap_err();
+        assert_eq!(error.to_string(), "/home/ada/.vimrc already exists");
+    }
+
+    #[test]
+    fn test_bulk_rename() {
+        let fs = MemFs::new()
+            .with_file("/work/a", "a")
+            .and_then(|fs| fs.with_file("/work/b", "b"))
+            .and_then(|fs| fs.with_file("/work/c", "c"))
+            .and_then(|fs| fs.with_file("/work/old", "old"))
+            .and_then(|fs| fs.with_file("/work/.a.glm-rename", "taken"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/work").expect("failed to create");
+        let paths = ["a", "b", "c", "old"].map(|name| PathBuf::from("/work").join(name));
+        let edit = BulkEdit::new("/work", paths.to_vec()).unwrap();
+
+        // a and b swap, c takes the name of the trashed item
+        let plan = sut
+            .bulk_rename(&edit, "1\tb\n2\ta\n3\told\nnew/\n", true)
+            .expect("failed to plan");
+
+        assert_eq!(
+            plan.ops.iter().map(|op| op.to_string()).collect::<Vec<_>>(),
+            [
+                "trash /work/old",
+                "rename /work/c -> /work/old",
+                "rename /work/a -> /work/.a.glm-rename-1",
+                "rename /work/b -> /work/a",
+                "rename /work/.a.glm-rename-1 -> /work/b",
+                "mkdir /work/new",
+            ]
+        );
+        // the memory file system has no trash, so only the swap runs
+        sut.bulk_rename(&edit, "1\tb\n2\ta\n3\tc\n4\told\n", false)
+            .expect("failed to rename");
+        let read = |path: &str| sut.backend().read(Path::new(path)).unwrap();
+        assert_eq!(read("/work/a"), b"b");
+        assert_eq!(read("/work/b"), b"a");
+        assert_eq!(read("/work/.a.glm-rename"), b"taken");
+
+        let paths = ["a", "b"].map(|name| PathBuf::from("/work").join(name));
+        let edit = BulkEdit::new("/work", paths.to_vec()).unwrap();
+        let error = sut.bulk_rename(&edit, "1\tx\n2\tx\n", true).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "/work/x is the new name of both /work/a and /work/b"
+        );
+        let error = sut.bulk_rename(&edit, "1\told\n2\tb\n", true).unwrap_err();
+        assert_eq!(error.to_string(), "/work/old already exists");
+    }
+
+    #[test]
+    fn test_pattern_rename() {
+        let fs = MemFs::new()
+            .with_file("/pics/IMG_1.JPG", "1")
+            .and_then(|fs| fs.with_file("/pics/IMG_2.JPG", "2"))
+            .and_then(|fs| fs.with_file("/pics/img_2.jpg", "kept"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/pics").expect("failed to create");
+        let paths = ["/pics/IMG_1.JPG", "/pics/IMG_2.JPG"];
+
+        let lower = RenamePattern {
+            case: Some(Case::Lower),
+            extension: Some("jpg".into()),
+            ..Default::default()
+        };
+        let conflicts = |previews: Vec<RenamePreview>| {
+            previews
+                .into_iter()
+                .map(|preview| preview.conflict)
+                .collect::<Vec<_>>()
+        };
+        let previews = sut.preview_rename(&paths, &lower).unwrap();
+        assert_eq!(previews[0].to, PathBuf::from("/pics/img_1.jpg"));
+        assert_eq!(conflicts(previews), [None, Some(RenameConflict::Exists)]);
+        let error = sut.pattern_rename(&paths, &lower, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "cannot rename /pics/IMG_2.JPG to \"img_2.jpg\": already exists"
+        );
+
+        let same = RenamePattern {
+            replace: "same".into(),
+            ..Default::default()
+        };
+        let previews = sut.preview_rename(&paths, &same).unwrap();
+        let duplicate = Some(RenameConflict::Duplicate);
+        assert_eq!(conflicts(previews), [duplicate.clone(), duplicate]);
+
+        // numbered in reverse, the two items swap names
+        let numbered = RenamePattern {
+            find: r"\d".into(),
+            replace: "{n}".into(),
+            start: 1,
+            ..Default::default()
+        };
+        let plan = sut
+            .pattern_rename(&["/pics/IMG_2.JPG", "/pics/IMG_1.JPG"], &numbered, false)
+            .expect("failed to rename");
+        assert_eq!(plan.len(), 3);
+        let read = |path: &str| sut.backend().read(Path::new(path)).unwrap();
+        assert_eq!(read("/pics/IMG_1.JPG"), b"2");
+        assert_eq!(read("/pics/IMG_2.JPG"), b"1");
+    }
+
+    #[test]
+    fn test_link_targets() {
+        let fs = MemFs::new()
+            .with_file("/home/ada/dotfiles/nvim/init.lua", "-- nvim")
+            .and_then(|fs| fs.with_dir("/home/ada/.config"))
+            .and_then(|fs| fs.with_symlink("/home/ada/.config/nvim", "../dotfiles/nvim"))
+            .and_then(|fs| fs.with_symlink("/home/ada/nvim", ".config/nvim"))
+            .and_then(|fs| fs.with_symlink("/home/ada/gone", "missing.txt"))
+            .expect("failed to set up memory file system");
+        let mut sut = FileManager::with_backend(fs, "/home/ada").expect("failed to create");
+        sut.toggle_hidden().unwrap();
+
+        let link = |sut: &FileManager<MemFs>, name: &str| {
+            let items = &sut.get_state().items;
+            let item = items.iter().find(|item| item.file_name == name).unwrap();
+            item.link.clone()
+        };
+        let nvim = link(&sut, "nvim").expect("nvim is a link");
+        assert_eq!(nvim.path, Path::new(".config/nvim"));
+        assert_eq!(nvim.file_type, Some(FileType::Directory));
+        assert_eq!(link(&sut, "gone").unwrap().file_type, None);
+        assert_eq!(link(&sut, "dotfiles"), None);
+
+        // follows both links
+        let state = sut.reveal_link_target("/home/ada/nvim").unwrap();
+        assert_eq!(state.current_dir, Path::new("/home/ada/dotfiles"));
+        assert_eq!(
+            state.focus.as_deref(),
+            Some(Path::new("/home/ada/dotfiles/nvim"))
+        );
+
+        let error = sut.reveal_link_target("/home/ada/gone").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "/home/ada/gone is a broken link to missing.txt"
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let relative = |from: &str, to: &str| relative_path(Path::new(from), Path::new(to));
+        assert_eq!(
+            relative("/home/ada", "/home/ada/dotfiles/vimrc"),
+            Path::new("dotfiles/vimrc")
+        );
+        assert_eq!(
+            relative("/home/ada/.config", "/home/ada/dotfiles/nvim"),
+            Path::new("../dotfiles/nvim")
+        );
+        assert_eq!(relative("/a/b/c", "/x"), Path::new("../../../x"));
+        assert_eq!(relative("/a", "/a"), Path::new("."));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink() {
+        let (dir, mut sut) = make_sut();
+
+        sut.hardlink("0tempfile.txt", "links/same.txt", false)
+            .unwrap();
+        std::fs::write(dir.path().join("0tempfile.txt"), "changed").unwrap();
+
+        let linked = std::fs::read_to_string(dir.path().join("links/same.txt")).unwrap();
+        assert_eq!(linked, "changed");
+        let error = sut.hardlink("links", "links2", false).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("cannot hard link the directory"));
+    }
+
     #[test]
     fn test_is_file() {
         let (dir, sut) = make_sut();
@@ -48,35 +48,21 @@ pub trait FsOps<S> {
         todo!()
     }
 
-    /// Testes whether a given path is a symbolic link.
+    /// Testes whether a given path is a symbolic link. Implementations check
+    /// it on their own backend, which isn't always the local file system.
     fn is_symlink<P>(&self, path: P) -> anyhow::Result<bool>
     where
-        P: AsRef<Path>,
-    {
-        let metadata = std::fs::symlink_metadata(path)?;
-        let file_type = metadata.file_type();
-        Ok(file_type.is_symlink())
-    }
+        P: AsRef<Path>;
 
     /// Testes whether a given path represents a directory.
     fn is_dir<P>(&self, path: P) -> anyhow::Result<bool>
     where
-        P: AsRef<Path>,
-    {
-        let metadata = std::fs::metadata(path)?;
-        let file_type = metadata.file_type();
-        Ok(file_type.is_dir())
-    }
+        P: AsRef<Path>;
 
     /// Testes whether a given path represents a regular file
     fn is_file<P>(&self, path: P) -> anyhow::Result<bool>
     where
-        P: AsRef<Path>,
-    {
-        let metadata = std::fs::metadata(path)?;
-        let file_type = metadata.file_type();
-        Ok(file_type.is_file())
-    }
+        P: AsRef<Path>;
 
     /// Returns the `FileType` of a given path. by checking if the path is
     /// a symbolic link, directory or a regular file.
@@ -0,0 +1,645 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use git2::build::CheckoutBuilder;
+use git2::{IndexAddOption, Repository, Status, StatusOptions};
+
+/// Git status of a given item, as seen by the repository containing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    /// The item has unresolved merge conflicts
+    Conflicted,
+    /// The item has changes on the working tree that are not staged
+    Modified,
+    /// The item has changes staged on the index
+    Staged,
+    /// The item is not tracked by the repository
+    Untracked,
+    /// The item matches an ignore rule
+    Ignored,
+    /// The item is a directory with changed items somewhere inside of it
+    ContainsChanges,
+}
+
+impl GitStatus {
+    /// Converts a raw libgit2 status into a `GitStatus`, picking the most
+    /// relevant status when an entry has more than one flag set. Returns
+    /// `None` for unchanged entries.
+    fn from_raw(status: Status) -> Option<GitStatus> {
+        if status.is_conflicted() {
+            Some(GitStatus::Conflicted)
+        } else if status.intersects(
+            Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_RENAMED | Status::WT_TYPECHANGE,
+        ) {
+            Some(GitStatus::Modified)
+        } else if status.intersects(
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_RENAMED
+                | Status::INDEX_TYPECHANGE,
+        ) {
+            Some(GitStatus::Staged)
+        } else if status.is_wt_new() {
+            Some(GitStatus::Untracked)
+        } else if status.is_ignored() {
+            Some(GitStatus::Ignored)
+        } else {
+            None
+        }
+    }
+}
+
+/// A git work tree discovered from a path inside of it.
+///
+/// The repository is read directly from disk through libgit2, so no `git`
+/// executable is required.
+pub struct GitRepo {
+    repo: Repository,
+    workdir: PathBuf,
+}
+
+impl std::fmt::Debug for GitRepo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitRepo")
+            .field("workdir", &self.workdir)
+            .finish()
+    }
+}
+
+impl GitRepo {
+    /// Looks for a repository containing `path`, walking up the parent
+    /// directories. Returns `None` when `path` is not inside a work tree, or
+    /// when the repository is bare.
+    pub fn discover<P>(path: P) -> Option<GitRepo>
+    where
+        P: AsRef<Path>,
+    {
+        let repo = Repository::discover(path).ok()?;
+        let workdir = repo.workdir()?.canonicalize().ok()?;
+        Some(GitRepo { repo, workdir })
+    }
+
+    /// Absolute path to the root of the work tree.
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    /// Converts an absolute path into a path relative to the work tree root.
+    pub(crate) fn relative_path<P>(&self, path: P) -> anyhow::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let canonical = match std::fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.is_symlink() => path.canonicalize()?,
+            // the path itself may not exist anymore (e.g. a deleted file),
+            // and a link is an item of its own rather than its target, so
+            // we canonicalize the parent and append the file name instead
+            _ => {
+                let parent = path.parent().unwrap_or(Path::new("/")).canonicalize()?;
+                parent.join(path.file_name().unwrap_or_default())
+            }
+        };
+        let relative = canonical
+            .strip_prefix(&self.workdir)
+            .map_err(|_| anyhow::anyhow!("{} is outside of the repository", path.display()))?;
+        Ok(relative.to_path_buf())
+    }
+
+    /// Computes the status of every direct child of `dir`, keyed by the name
+    /// of the child.
+    ///
+    /// Files get their own status, while directories are reported as
+    /// `GitStatus::ContainsChanges` when anything inside of them changed.
+    /// Untracked and ignored directories are reported as a whole. Unchanged
+    /// children are not present on the returned map.
+    pub fn statuses_in<P>(&self, dir: P) -> anyhow::Result<HashMap<OsString, GitStatus>>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = self.relative_path(dir)?;
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .include_ignored(true)
+            .recurse_untracked_dirs(false)
+            .recurse_ignored_dirs(false);
+        if !dir.as_os_str().is_empty() {
+            options.pathspec(&dir).disable_pathspec_match(true);
+        }
+
+        let mut result: HashMap<OsString, GitStatus> = HashMap::new();
+        for entry in self.repo.statuses(Some(&mut options))?.iter() {
+            let Some(status) = GitStatus::from_raw(entry.status()) else {
+                continue;
+            };
+            let Some(entry_path) = entry.path() else {
+                continue;
+            };
+            let Ok(inside) = Path::new(entry_path).strip_prefix(&dir) else {
+                continue;
+            };
+
+            let mut components = inside.components();
+            let Some(Component::Normal(child)) = components.next() else {
+                continue;
+            };
+            // libgit2 reports untracked and ignored directories with a
+            // trailing slash, which only describe the child itself when
+            // there is nothing past it
+            let is_nested = components.next().is_some();
+            let status = match (is_nested, status) {
+                (true, GitStatus::Ignored) => continue,
+                (true, _) => GitStatus::ContainsChanges,
+                (false, status) => status,
+            };
+
+            result
+                .entry(child.to_os_string())
+                .and_modify(|current| *current = (*current).min(status))
+                .or_insert(status);
+        }
+
+        Ok(result)
+    }
+
+    /// Stages every change inside of `paths`, including deletions. Ignored
+    /// files are never staged.
+    pub fn stage<P>(&self, paths: &[P]) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let specs = self.pathspecs(paths)?;
+        let mut index = self.repo.index()?;
+        index.add_all(&specs, IndexAddOption::DEFAULT, None)?;
+        index.update_all(&specs, None)?;
+        index.write()?;
+        Ok(())
+    }
+
+    /// Removes the staged changes of `paths` from the index, leaving the
+    /// working tree untouched.
+    pub fn unstage<P>(&self, paths: &[P]) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let specs = self.pathspecs(paths)?;
+        match self.repo.head().and_then(|head| head.peel_to_commit()) {
+            Ok(commit) => self.repo.reset_default(Some(commit.as_object()), &specs)?,
+            // on a repository without commits, unstaging means removing the
+            // entries from the index altogether
+            Err(_) => {
+                let mut index = self.repo.index()?;
+                index.remove_all(&specs, None)?;
+                index.write()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Discards the working tree changes inside of `paths`.
+    ///
+    /// This is never destructive: untracked items are moved to the trash,
+    /// and modified files are moved to the trash before being restored from
+    /// the index, so any discarded content can still be recovered.
+    pub fn discard<P>(&self, paths: &[P]) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.discard_with(paths, |paths| Ok(trash::delete_all(paths)?))
+    }
+
+    /// Like [`GitRepo::discard`], moving items out of the way with `trash`
+    /// instead of the trash of the system.
+    pub fn discard_with<P, F>(&self, paths: &[P], trash: F) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&[PathBuf]) -> anyhow::Result<()>,
+    {
+        let (to_trash, to_restore) = self.changes(paths)?;
+        if !to_trash.is_empty() {
+            trash(&to_trash)?;
+        }
+        if !to_restore.is_empty() {
+            let mut checkout = CheckoutBuilder::new();
+            checkout.force().disable_pathspec_match(true);
+            for path in to_restore.iter() {
+                checkout.path(path);
+            }
+            self.repo.checkout_index(None, Some(&mut checkout))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the items that [`GitRepo::discard`] would move to the trash
+    /// for `paths`, without touching anything.
+    pub fn discarded<P>(&self, paths: &[P]) -> anyhow::Result<Vec<PathBuf>>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(self.changes(paths)?.0)
+    }
+
+    /// Splits the working tree changes inside of `paths` into the items to
+    /// move to the trash, and the paths to restore from the index.
+    fn changes<P>(&self, paths: &[P]) -> anyhow::Result<(Vec<PathBuf>, Vec<String>)>
+    where
+        P: AsRef<Path>,
+    {
+        let specs = self.pathspecs(paths)?;
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(false);
+        // the root is only matched by `*` as a pattern, and it covers
+        // everything else anyway
+        if !specs.iter().any(|spec| spec == "*") {
+            options.disable_pathspec_match(true);
+            for spec in specs.iter() {
+                options.pathspec(spec);
+            }
+        }
+
+        let mut to_trash = vec![];
+        let mut to_restore = vec![];
+        for entry in self.repo.statuses(Some(&mut options))?.iter() {
+            let Some(entry_path) = entry.path() else {
+                continue;
+            };
+            let status = entry.status();
+            if status.is_wt_new() {
+                to_trash.push(self.workdir.join(entry_path));
+            } else if status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE) {
+                to_trash.push(self.workdir.join(entry_path));
+                to_restore.push(entry_path.to_string());
+            } else if status.is_wt_deleted() {
+                to_restore.push(entry_path.to_string());
+            }
+        }
+        Ok((to_trash, to_restore))
+    }
+
+    /// Appends `paths` to the `.gitignore` file at the root of the work
+    /// tree, creating it if needed. Directories are added with a trailing
+    /// slash, and paths that are already listed are skipped.
+    pub fn ignore<P>(&self, paths: &[P]) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let gitignore = self.workdir.join(".gitignore");
+        let contents = std::fs::read_to_string(&gitignore).unwrap_or_default();
+        let existing = contents.lines().map(str::trim).collect::<Vec<_>>();
+
+        let mut rules = vec![];
+        for path in paths {
+            let relative = self.relative_path(path)?;
+            let mut rule = format!("/{}", relative.to_string_lossy());
+            // git never follows links, a link to a directory is a file
+            let metadata = std::fs::symlink_metadata(path.as_ref());
+            if metadata.is_ok_and(|metadata| metadata.is_dir()) {
+                rule.push('/');
+            }
+            if !existing.contains(&rule.as_str()) && !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+
+        if rules.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&gitignore)?;
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            writeln!(file)?;
+        }
+        for rule in rules {
+            writeln!(file, "{rule}")?;
+        }
+        Ok(())
+    }
+
+    /// Converts absolute paths into pathspecs relative to the work tree
+    /// root. The root itself becomes `*`, matching everything.
+    fn pathspecs<P>(&self, paths: &[P]) -> anyhow::Result<Vec<String>>
+    where
+        P: AsRef<Path>,
+    {
+        paths
+            .iter()
+            .map(|path| {
+                let relative = self.relative_path(path)?;
+                match relative.as_os_str().is_empty() {
+                    true => Ok(String::from("*")),
+                    false => Ok(relative.to_string_lossy().to_string()),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    fn commit_all(repo: &Repository) {
+        let mut index = repo.index().expect("failed to open index");
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .expect("failed to add files");
+        index.write().expect("failed to write index");
+        let tree_id = index.write_tree().expect("failed to write tree");
+        let tree = repo.find_tree(tree_id).expect("failed to find tree");
+        let signature = git2::Signature::now("glm", "glm@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "test",
+            &tree,
+            &parents,
+        )
+        .expect("failed to commit");
+    }
+
+    fn make_repo() -> (TempDir, Repository) {
+        let dir = tempdir().expect("failed to create tempdir");
+        let repo = Repository::init(dir.path()).expect("failed to init repository");
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("README.md"), "readme").unwrap();
+        fs::write(dir.path().join("staged.txt"), "staged").unwrap();
+        fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        commit_all(&repo);
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_discover_outside_work_tree() {
+        let dir = tempdir().expect("failed to create tempdir");
+
+        assert!(GitRepo::discover(dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_statuses_in_root() {
+        let (dir, repo) = make_repo();
+        fs::write(dir.path().join("README.md"), "changed").unwrap();
+        fs::write(dir.path().join("staged.txt"), "changed").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("staged.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(dir.path().join("new.txt"), "new").unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("target/out"), "out").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "changed").unwrap();
+
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+        let statuses = sut
+            .statuses_in(dir.path())
+            .expect("failed to read statuses");
+
+        assert_eq!(
+            statuses.get(&OsString::from("README.md")),
+            Some(&GitStatus::Modified)
+        );
+        assert_eq!(
+            statuses.get(&OsString::from("staged.txt")),
+            Some(&GitStatus::Staged)
+        );
+        assert_eq!(
+            statuses.get(&OsString::from("new.txt")),
+            Some(&GitStatus::Untracked)
+        );
+        assert_eq!(
+            statuses.get(&OsString::from("target")),
+            Some(&GitStatus::Ignored)
+        );
+        assert_eq!(
+            statuses.get(&OsString::from("src")),
+            Some(&GitStatus::ContainsChanges)
+        );
+        assert_eq!(statuses.get(&OsString::from(".gitignore")), None);
+    }
+
+    fn status_of(sut: &GitRepo, dir: &Path, name: &str) -> Option<GitStatus> {
+        let statuses = sut.statuses_in(dir).expect("failed to read statuses");
+        statuses.get(&OsString::from(name)).copied()
+    }
+
+    #[test]
+    fn test_statuses_of_nested_directories() {
+        let (dir, _repo) = make_repo();
+        fs::create_dir_all(dir.path().join("src/target")).unwrap();
+        fs::write(dir.path().join("src/target/out"), "out").unwrap();
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+
+        // an ignored directory doesn't make its tracked parent ignored
+        assert_eq!(status_of(&sut, dir.path(), "src"), None);
+
+        fs::create_dir(dir.path().join("src/newdir")).unwrap();
+        fs::write(dir.path().join("src/newdir/a.rs"), "new").unwrap();
+        assert_eq!(
+            status_of(&sut, dir.path(), "src"),
+            Some(GitStatus::ContainsChanges)
+        );
+        assert_eq!(
+            status_of(&sut, &dir.path().join("src"), "newdir"),
+            Some(GitStatus::Untracked)
+        );
+    }
+
+    #[test]
+    fn test_statuses_in_subdirectory() {
+        let (dir, _repo) = make_repo();
+        fs::write(dir.path().join("src/lib.rs"), "changed").unwrap();
+        fs::write(dir.path().join("src/new.rs"), "new").unwrap();
+
+        let sut = GitRepo::discover(dir.path().join("src")).expect("should find repository");
+        let statuses = sut
+            .statuses_in(dir.path().join("src"))
+            .expect("failed to read statuses");
+
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(
+            statuses.get(&OsString::from("lib.rs")),
+            Some(&GitStatus::Modified)
+        );
+        assert_eq!(
+            statuses.get(&OsString::from("new.rs")),
+            Some(&GitStatus::Untracked)
+        );
+    }
+
+    #[test]
+    fn test_stage_and_unstage() {
+        let (dir, _repo) = make_repo();
+        fs::write(dir.path().join("README.md"), "changed").unwrap();
+        fs::write(dir.path().join("src/new.rs"), "new").unwrap();
+        fs::remove_file(dir.path().join("staged.txt")).unwrap();
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+
+        sut.stage(&[dir.path().join("README.md"), dir.path().join("src")])
+            .expect("failed to stage");
+        sut.stage(&[dir.path().join("staged.txt")])
+            .expect("failed to stage deletion");
+
+        assert_eq!(
+            status_of(&sut, dir.path(), "README.md"),
+            Some(GitStatus::Staged)
+        );
+        assert_eq!(
+            status_of(&sut, &dir.path().join("src"), "new.rs"),
+            Some(GitStatus::Staged)
+        );
+        let index = sut.repo.index().unwrap();
+        assert!(index.get_path(Path::new("staged.txt"), 0).is_none());
+
+        sut.unstage(&[dir.path().join("README.md")])
+            .expect("failed to unstage");
+
+        assert_eq!(
+            status_of(&sut, dir.path(), "README.md"),
+            Some(GitStatus::Modified)
+        );
+    }
+
+    #[test]
+    fn test_unstage_without_commits() {
+        let dir = tempdir().expect("failed to create tempdir");
+        Repository::init(dir.path()).expect("failed to init repository");
+        fs::write(dir.path().join("new.txt"), "new").unwrap();
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+        sut.stage(&[dir.path().join("new.txt")])
+            .expect("failed to stage");
+
+        sut.unstage(&[dir.path().join("new.txt")])
+            .expect("failed to unstage");
+
+        assert_eq!(
+            status_of(&sut, dir.path(), "new.txt"),
+            Some(GitStatus::Untracked)
+        );
+    }
+
+    /// Moves items into `bin` instead of the trash of the system.
+    fn fake_trash(bin: &Path) -> impl FnOnce(&[PathBuf]) -> anyhow::Result<()> + '_ {
+        move |paths| {
+            for path in paths {
+                fs::rename(path, bin.join(path.file_name().unwrap()))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_discard_restores_modified_files() {
+        let (dir, _repo) = make_repo();
+        let bin = tempdir().expect("failed to create tempdir");
+        fs::write(dir.path().join("README.md"), "changed").unwrap();
+        fs::remove_file(dir.path().join("staged.txt")).unwrap();
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+
+        sut.discard_with(
+            &[dir.path().join("README.md"), dir.path().join("staged.txt")],
+            fake_trash(bin.path()),
+        )
+        .expect("failed to discard");
+
+        let readme = fs::read_to_string(dir.path().join("README.md")).unwrap();
+        let staged = fs::read_to_string(dir.path().join("staged.txt")).unwrap();
+        assert_eq!(readme, "readme");
+        assert_eq!(staged, "staged");
+        assert!(sut.statuses_in(dir.path()).unwrap().is_empty());
+        let trashed = fs::read_to_string(bin.path().join("README.md")).unwrap();
+        assert_eq!(trashed, "changed");
+    }
+
+    #[test]
+    fn test_stage_and_discard_symlink_outside_of_repo() {
+        let (dir, repo) = make_repo();
+        let outside = tempdir().expect("failed to create tempdir");
+        let bin = tempdir().expect("failed to create tempdir");
+        fs::write(outside.path().join("target.txt"), "outside").unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(outside.path().join("target.txt"), &link).unwrap();
+        commit_all(&repo);
+        fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("staged.txt"), &link).unwrap();
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+
+        sut.stage(&[&link]).expect("failed to stage link");
+        fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("changed.txt"), &link).unwrap();
+        sut.discard_with(&[&link], fake_trash(bin.path()))
+            .expect("failed to discard link");
+
+        assert_eq!(status_of(&sut, dir.path(), "link"), Some(GitStatus::Staged));
+        assert_eq!(
+            fs::read_link(&link).unwrap(),
+            outside.path().join("staged.txt")
+        );
+        assert!(fs::symlink_metadata(bin.path().join("link")).is_ok());
+        let target = fs::read_to_string(outside.path().join("target.txt")).unwrap();
+        assert_eq!(target, "outside");
+    }
+
+    #[test]
+    fn test_discard_root() {
+        let (dir, _repo) = make_repo();
+        let bin = tempdir().expect("failed to create tempdir");
+        fs::write(dir.path().join("src/lib.rs"), "changed").unwrap();
+        fs::write(dir.path().join("new.txt"), "new").unwrap();
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+
+        let mut discarded = sut
+            .discarded(&[dir.path()])
+            .expect("failed to plan discard")
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        discarded.sort();
+        assert_eq!(discarded, ["lib.rs", "new.txt"]);
+        assert_eq!(sut.statuses_in(dir.path()).unwrap().len(), 2);
+        sut.discard_with(&[dir.path()], fake_trash(bin.path()))
+            .expect("failed to discard");
+
+        assert!(sut.statuses_in(dir.path()).unwrap().is_empty());
+        assert!(bin.path().join("new.txt").exists());
+        assert!(bin.path().join("lib.rs").exists());
+    }
+
+    #[test]
+    fn test_ignore() {
+        let (dir, _repo) = make_repo();
+        fs::create_dir(dir.path().join("logs")).unwrap();
+        fs::write(dir.path().join("logs/out.log"), "log").unwrap();
+        fs::write(dir.path().join("secret.env"), "secret").unwrap();
+        let sut = GitRepo::discover(dir.path()).expect("should find repository");
+
+        sut.ignore(&[dir.path().join("logs"), dir.path().join("secret.env")])
+            .expect("failed to ignore");
+        sut.ignore(&[dir.path().join("secret.env")])
+            .expect("failed to ignore");
+
+        let gitignore = fs::read_to_string(dir.path().join(".gitignore")).unwrap();
+        assert_eq!(gitignore, "target/\n/logs/\n/secret.env\n");
+        assert_eq!(
+            status_of(&sut, dir.path(), "logs"),
+            Some(GitStatus::Ignored)
+        );
+        assert_eq!(
+            status_of(&sut, dir.path(), "secret.env"),
+            Some(GitStatus::Ignored)
+        );
+    }
+}
@@ -0,0 +1,680 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+use crate::audit::{audited, AuditLog};
+use crate::checksums::{hash_with_progress, HashAlgorithm};
+#[cfg(feature = "archive")]
+use crate::compress::CompressOptions;
+use crate::plan::PlannedOp;
+
+/// Identifies a job of a [`JobQueue`], ids are never reused.
+pub type JobId = u64;
+
+/// An operation to run on the background with a [`JobQueue`]. Jobs only
+/// work on the local file system.
+#[derive(Debug, Clone)]
+pub enum JobKind {
+    /// Copies every source into the directory `dest`
+    Copy {
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+    },
+    /// Moves every source into the directory `dest`. Sources on another
+    /// device are copied and then removed
+    Move {
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+    },
+    /// Moves every path to the trash of the platform
+    Delete { paths: Vec<PathBuf> },
+    /// Creates the archive `dest` with every path, see
+    /// [`crate::create_archive`]
+    #[cfg(feature = "archive")]
+    Archive {
+        paths: Vec<PathBuf>,
+        dest: PathBuf,
+        options: CompressOptions,
+    },
+    /// Computes the BLAKE3 hash of every file, recursively for directories
+    Checksum { paths: Vec<PathBuf> },
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobKind::Copy { sources, dest } => {
+                write!(f, "Copy {} to {}", describe(sources), dest.display())
+            }
+            JobKind::Move { sources, dest } => {
+                write!(f, "Move {} to {}", describe(sources), dest.display())
+            }
+            JobKind::Delete { paths } => write!(f, "Delete {}", describe(paths)),
+            #[cfg(feature = "archive")]
+            JobKind::Archive { paths, dest, .. } => {
+                write!(f, "Compress {} into {}", describe(paths), dest.display())
+            }
+            JobKind::Checksum { paths } => write!(f, "Checksum {}", describe(paths)),
+        }
+    }
+}
+
+/// Names a single path, or the amount of paths.
+fn describe(paths: &[PathBuf]) -> String {
+    match paths {
+        [path] => path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string()),
+        paths => format!("{} items", paths.len()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    /// Waiting for a free slot to run
+    Queued,
+    Running,
+    Paused,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job won't run anymore.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Done | JobStatus::Failed(_) | JobStatus::Cancelled
+        )
+    }
+}
+
+/// A job submitted to a [`JobQueue`], as of the call to [`JobQueue::jobs`].
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    /// Progress of the job, in bytes for everything but deletions, which
+    /// count items
+    pub done: u64,
+    pub total: u64,
+    /// Hashes computed by a checksum job, in hex, in the order files were
+    /// read
+    pub checksums: Vec<(PathBuf, String)>,
+}
+
+/// Events produced by a `JobQueue` as jobs run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobEvent {
+    /// The status of a job changed
+    Status { id: JobId, status: JobStatus },
+    /// A running job made progress, only reported when the percentage
+    /// changes
+    Progress { id: JobId, done: u64, total: u64 },
+}
+
+/// Lets the owner of a job pause or cancel it while it runs.
+#[derive(Debug, Default)]
+struct Control {
+    state: Mutex<ControlState>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ControlState {
+    #[default]
+    Running,
+    Paused,
+    Cancelled,
+}
+
+impl Control {
+    fn set(&self, state: ControlState) {
+        *self.state.lock().unwrap() = state;
+        self.changed.notify_all();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        *self.state.lock().unwrap() == ControlState::Cancelled
+    }
+
+    /// Blocks while the job is paused, and fails once it's cancelled.
+    fn checkpoint(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        while *state == ControlState::Paused {
+            state = self.changed.wait(state).unwrap();
+        }
+        match *state {
+            ControlState::Cancelled => anyhow::bail!("job was cancelled"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    job: Job,
+    control: Arc<Control>,
+    /// Whether a worker already picked the job, paused jobs that didn't
+    /// start yet go back to the queue when resumed
+    started: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    /// Every job that was submitted and not cleared, oldest first
+    jobs: Mutex<Vec<Entry>>,
+    /// Notified when a job is queued or resumed
+    wakeup: Condvar,
+    events: mpsc::Sender<JobEvent>,
+    shutdown: AtomicBool,
+    audit: Mutex<Option<AuditLog>>,
+}
+
+impl Shared {
+    fn update<F>(&self, id: JobId, update: F)
+    where
+        F: FnOnce(&mut Job),
+    {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(entry) = jobs.iter_mut().find(|entry| entry.job.id == id) {
+            update(&mut entry.job);
+        }
+    }
+
+    fn set_status(&self, id: JobId, status: JobStatus) {
+        self.update(id, |job| job.status = status.clone());
+        _ = self.events.send(JobEvent::Status { id, status });
+    }
+}
+
+/// Runs copy, move, delete, archive and checksum jobs on background
+/// threads, at most `max_running` at a time and in the order they were
+/// submitted.
+///
+/// Jobs report their progress as `JobEvent`s, and can be paused, resumed
+/// or cancelled by id. Dropping the queue cancels every job.
+#[derive(Debug)]
+pub struct JobQueue {
+    shared: Arc<Shared>,
+    next_id: JobId,
+    rx: mpsc::Receiver<JobEvent>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl JobQueue {
+    pub fn new(max_running: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            jobs: Mutex::new(vec![]),
+            wakeup: Condvar::new(),
+            events: tx,
+            shutdown: AtomicBool::new(false),
+            audit: Mutex::new(None),
+        });
+        for _ in 0..max_running.max(1) {
+            let shared = shared.clone();
+            std::thread::spawn(move || work(&shared));
+        }
+        Self {
+            shared,
+            next_id: 1,
+            rx,
+        }
+    }
+
+    /// Records every copy, move and delete of jobs started from now on in
+    /// `audit`, or stops recording them with `None`.
+    pub fn set_audit_log(&self, audit: Option<AuditLog>) {
+        *self.shared.audit.lock().unwrap() = audit;
+    }
+
+    /// Queues `kind` to run once there is a free slot, returning its id.
+    pub fn submit(&mut self, kind: JobKind) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let job = Job {
+            id,
+            kind,
+            status: JobStatus::Queued,
+            done: 0,
+            total: 0,
+            checksums: vec![],
+        };
+        self.shared.jobs.lock().unwrap().push(Entry {
+            job,
+            control: Arc::new(Control::default()),
+            started: false,
+        });
+        _ = self.shared.events.send(JobEvent::Status {
+            id,
+            status: JobStatus::Queued,
+        });
+        self.shared.wakeup.notify_all();
+        id
+    }
+
+    /// Pauses a queued or running job. Running jobs stop at the next chunk
+    /// of data, and queued jobs are skipped until they are resumed.
+    pub fn pause(&mut self, id: JobId) -> anyhow::Result<()> {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        let entry = find(&mut jobs, id)?;
+        if !matches!(entry.job.status, JobStatus::Queued | JobStatus::Running) {
+            anyhow::bail!("job {id} is not running");
+        }
+        entry.control.set(ControlState::Paused);
+        entry.job.status = JobStatus::Paused;
+        _ = self.shared.events.send(JobEvent::Status {
+            id,
+            status: JobStatus::Paused,
+        });
+        Ok(())
+    }
+
+    pub fn resume(&mut self, id: JobId) -> anyhow::Result<()> {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        let entry = find(&mut jobs, id)?;
+        if entry.job.status != JobStatus::Paused {
+            anyhow::bail!("job {id} is not paused");
+        }
+        let status = match entry.started {
+            true => JobStatus::Running,
+            false => JobStatus::Queued,
+        };
+        entry.control.set(ControlState::Running);
+        entry.job.status = status.clone();
+        _ = self.shared.events.send(JobEvent::Status { id, status });
+        self.shared.wakeup.notify_all();
+        Ok(())
+    }
+
+    /// Cancels a job that didn't finish yet. Running jobs stop at the next
+    /// chunk of data, leaving whatever was already done in place.
+    pub fn cancel(&mut self, id: JobId) -> anyhow::Result<()> {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        let entry = find(&mut jobs, id)?;
+        if entry.job.status.is_finished() {
+            anyhow::bail!("job {id} already finished");
+        }
+        entry.control.set(ControlState::Cancelled);
+        if !entry.started {
+            entry.job.status = JobStatus::Cancelled;
+            _ = self.shared.events.send(JobEvent::Status {
+                id,
+                status: JobStatus::Cancelled,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns every job that was not cleared, oldest first.
+    pub fn jobs(&self) -> Vec<Job> {
+        let jobs = self.shared.jobs.lock().unwrap();
+        jobs.iter().map(|entry| entry.job.clone()).collect()
+    }
+
+    /// Forgets every job that finished.
+    pub fn clear_finished(&mut self) {
+        let mut jobs = self.shared.jobs.lock().unwrap();
+        jobs.retain(|entry| !entry.job.status.is_finished());
+    }
+
+    /// Returns every event produced since the last call, without blocking.
+    pub fn events(&self) -> Vec<JobEvent> {
+        self.rx.try_iter().collect()
+    }
+
+    /// Blocks until the next event is produced.
+    pub fn next_event(&self) -> anyhow::Result<JobEvent> {
+        Ok(self.rx.recv()?)
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Relaxed);
+        let jobs = self.shared.jobs.lock().unwrap();
+        for entry in jobs.iter() {
+            entry.control.set(ControlState::Cancelled);
+        }
+        self.shared.wakeup.notify_all();
+    }
+}
+
+fn find(jobs: &mut [Entry], id: JobId) -> anyhow::Result<&mut Entry> {
+    jobs.iter_mut()
+        .find(|entry| entry.job.id == id)
+        .ok_or_else(|| anyhow::anyhow!("there is no job {id}"))
+}
+
+/// Runs queued jobs one after the other, until the queue is dropped.
+fn work(shared: &Shared) {
+    loop {
+        let (id, kind, control) = {
+            let mut jobs = shared.jobs.lock().unwrap();
+            loop {
+                if shared.shutdown.load(Ordering::Relaxed) {
+                    return;
+                }
+                let next = jobs
+                    .iter_mut()
+                    .find(|entry| entry.job.status == JobStatus::Queued);
+                if let Some(entry) = next {
+                    entry.started = true;
+                    entry.job.status = JobStatus::Running;
+                    _ = shared.events.send(JobEvent::Status {
+                        id: entry.job.id,
+                        status: JobStatus::Running,
+                    });
+                    break (entry.job.id, entry.job.kind.clone(), entry.control.clone());
+                }
+                jobs = shared.wakeup.wait(jobs).unwrap();
+            }
+        };
+
+        let mut reporter = Reporter {
+            id,
+            shared,
+            control: &control,
+            done: 0,
+            total: 0,
+            percent: None,
+        };
+        let status = match run(&kind, &mut reporter) {
+            Ok(checksums) => {
+                shared.update(id, |job| job.checksums = checksums);
+                JobStatus::Done
+            }
+            Err(_) if control.is_cancelled() => JobStatus::Cancelled,
+            Err(e) => JobStatus::Failed(e.to_string()),
+        };
+        shared.set_status(id, status);
+    }
+}
+
+/// Keeps track of the progress of a running job.
+struct Reporter<'a> {
+    id: JobId,
+    shared: &'a Shared,
+    control: &'a Control,
+    done: u64,
+    total: u64,
+    /// Last percentage sent as an event
+    percent: Option<u64>,
+}
+
+impl Reporter<'_> {
+    fn start(&mut self, total: u64) -> anyhow::Result<()> {
+        self.total = total;
+        self.advance(0)
+    }
+
+    /// Adds `amount` to the progress, blocking while the job is paused and
+    /// failing once it's cancelled.
+    fn advance(&mut self, amount: u64) -> anyhow::Result<()> {
+        self.done += amount;
+        let (done, total) = (self.done, self.total);
+        self.shared.update(self.id, |job| {
+            job.done = done;
+            job.total = total;
+        });
+        let percent = match total {
+            0 => 100,
+            total => done.min(total) * 100 / total,
+        };
+        if self.percent != Some(percent) {
+            self.percent = Some(percent);
+            _ = self.shared.events.send(JobEvent::Progress {
+                id: self.id,
+                done,
+                total,
+            });
+        }
+        self.control.checkpoint()
+    }
+
+    /// Records the outcome of `op` when auditing is enabled, passing
+    /// `result` through.
+    fn audit<T>(&self, op: PlannedOp, result: anyhow::Result<T>) -> anyhow::Result<T> {
+        let audit = self.shared.audit.lock().unwrap().clone();
+        audited(audit.as_ref(), &op, result)
+    }
+}
+
+fn run(kind: &JobKind, reporter: &mut Reporter) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    match kind {
+        JobKind::Copy { sources, dest } => {
+            let targets = targets(sources, dest)?;
+            reporter.start(total_size(sources)?)?;
+            for (src, dst) in sources.iter().zip(targets) {
+                let result =
+                    crate::ops::copy_with_progress(src, &dst, &mut |n| reporter.advance(n));
+                let op = PlannedOp::Copy {
+                    from: src.clone(),
+                    to: dst,
+                };
+                reporter.audit(op, result)?;
+            }
+        }
+        JobKind::Move { sources, dest } => {
+            let targets = targets(sources, dest)?;
+            let sizes = sources
+                .iter()
+                .map(|src| total_size(std::slice::from_ref(src)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            reporter.start(sizes.iter().sum())?;
+            for ((src, dst), size) in sources.iter().zip(targets).zip(sizes) {
+                // progress left to report once moved, copies report their own
+                let result = match std::fs::rename(src, &dst) {
+                    Ok(()) => Ok(size),
+                    Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                        crate::ops::copy_with_progress(src, &dst, &mut |n| reporter.advance(n))
+                            .and_then(|_| remove(src))
+                            .map(|_| 0)
+                    }
+                    Err(e) => Err(e.into()),
+                };
+                let op = PlannedOp::Rename {
+                    from: src.clone(),
+                    to: dst,
+                };
+                let remaining = reporter.audit(op, result)?;
+                reporter.advance(remaining)?;
+            }
+        }
+        JobKind::Delete { paths } => {
+            reporter.start(paths.len() as u64)?;
+            for path in paths {
+                let result = trash::delete(path).map_err(anyhow::Error::from);
+                reporter.audit(PlannedOp::Delete { path: path.clone() }, result)?;
+                reporter.advance(1)?;
+            }
+        }
+        #[cfg(feature = "archive")]
+        JobKind::Archive {
+            paths,
+            dest,
+            options,
+        } => {
+            reporter.start(total_size(paths)?)?;
+            let mut stored = 0;
+            crate::compress::try_create_archive(paths, dest, options, |progress| {
+                let amount = progress.done - stored;
+                stored = progress.done;
+                reporter.advance(amount)
+            })?;
+        }
+        JobKind::Checksum { paths } => {
+            let mut files = vec![];
+            for path in paths {
+                collect_files(path, &mut files)?;
+            }
+            reporter.start(total_size(&files)?)?;
+            let mut checksums = vec![];
+            for file in files {
+                let hash = hash_with_progress(&file, HashAlgorithm::Blake3, |read| {
+                    reporter.advance(read)
+                })?;
+                checksums.push((file, hash));
+            }
+            return Ok(checksums);
+        }
+    }
+    Ok(vec![])
+}
+
+/// Returns where each source ends up inside of `dest`, failing when any
+/// of them already exists.
+fn targets(sources: &[PathBuf], dest: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut targets = vec![];
+    for src in sources {
+        let name = src
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} has no name", src.display()))?;
+        let target = dest.join(name);
+        if std::fs::symlink_metadata(&target).is_ok() {
+            anyhow::bail!("{} already exists", target.display());
+        }
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+/// Sums the length of every file in `paths`, recursively. Symbolic links
+/// are not followed.
+fn total_size(paths: &[PathBuf]) -> anyhow::Result<u64> {
+    let mut files = vec![];
+    for path in paths {
+        collect_files(path, &mut files)?;
+    }
+    let mut total = 0;
+    for file in files {
+        total += std::fs::symlink_metadata(file)?.len();
+    }
+    Ok(total)
+}
+
+/// Pushes `path` when it's a regular file, or every regular file inside of
+/// it when it's a directory, sorted by name.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let file_type = std::fs::symlink_metadata(path)?.file_type();
+    if file_type.is_file() {
+        files.push(path.to_path_buf());
+    } else if file_type.is_dir() {
+        let mut children = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        children.sort();
+        for child in children {
+            collect_files(&child, files)?;
+        }
+    }
+    Ok(())
+}
+
+fn remove(path: &Path) -> anyhow::Result<()> {
+    match std::fs::symlink_metadata(path)?.is_dir() {
+        true => std::fs::remove_dir_all(path)?,
+        false => std::fs::remove_file(path)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_tempdir() -> TempDir {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        fs::create_dir(dir.path().join("dest")).unwrap();
+        fs::write(dir.path().join("src/a.txt"), "a").unwrap();
+        fs::write(dir.path().join("src/nested/b.txt"), vec![1; 200_000]).unwrap();
+        dir
+    }
+
+    fn wait_for(queue: &JobQueue, id: JobId) -> JobStatus {
+        loop {
+            match queue.next_event().expect("failed to wait for job") {
+                JobEvent::Status { id: job, status } if job == id && status.is_finished() => {
+                    return status
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn test_copy_and_checksum() {
+        let dir = setup_tempdir();
+        let mut sut = JobQueue::new(2);
+
+        let copy = sut.submit(JobKind::Copy {
+            sources: vec![dir.path().join("src")],
+            dest: dir.path().join("dest"),
+        });
+        assert_eq!(wait_for(&sut, copy), JobStatus::Done);
+        let checksum = sut.submit(JobKind::Checksum {
+            paths: vec![dir.path().join("dest/src/a.txt")],
+        });
+        assert_eq!(wait_for(&sut, checksum), JobStatus::Done);
+
+        assert!(dir.path().join("dest/src/nested/b.txt").exists());
+        let jobs = sut.jobs();
+        assert_eq!((jobs[0].done, jobs[0].total), (200_001, 200_001));
+        assert_eq!(
+            jobs[1].checksums[0].1,
+            blake3::hash(b"a").to_hex().to_string()
+        );
+        sut.clear_finished();
+        assert!(sut.jobs().is_empty());
+    }
+
+    #[test]
+    fn test_move_never_overwrites() {
+        let dir = setup_tempdir();
+        fs::write(dir.path().join("dest/a.txt"), "precious").unwrap();
+        let mut sut = JobQueue::default();
+
+        let id = sut.submit(JobKind::Move {
+            sources: vec![dir.path().join("src/a.txt")],
+            dest: dir.path().join("dest"),
+        });
+
+        assert!(matches!(wait_for(&sut, id), JobStatus::Failed(_)));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("dest/a.txt")).unwrap(),
+            "precious"
+        );
+        assert!(dir.path().join("src/a.txt").exists());
+    }
+
+    #[test]
+    fn test_pause_and_cancel() {
+        let dir = setup_tempdir();
+        let mut sut = JobQueue::new(1);
+
+        let id = sut.submit(JobKind::Checksum {
+            paths: vec![dir.path().join("src")],
+        });
+        sut.pause(id).expect("failed to pause job");
+        assert_eq!(sut.jobs()[0].status, JobStatus::Paused);
+        assert!(sut.pause(id).is_err());
+        sut.cancel(id).expect("failed to cancel job");
+
+        assert_eq!(wait_for(&sut, id), JobStatus::Cancelled);
+        assert!(sut.jobs()[0].checksums.is_empty());
+        assert!(sut.cancel(id).is_err());
+    }
+}
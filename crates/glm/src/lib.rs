@@ -1,8 +1,71 @@
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "async")]
+mod async_file_manager;
+#[cfg(feature = "async")]
+mod async_fs_ops;
+mod audit;
+mod bulk_rename;
+mod checksums;
+#[cfg(feature = "archive")]
+mod compress;
+mod config;
+mod dir_diff;
+mod disk_usage;
+mod duplicates;
 mod file_manager;
 mod fs_ops;
+#[cfg(feature = "git")]
+mod git;
+mod jobs;
 mod list_state;
+mod mode;
+mod ops;
+mod owners;
+mod plan;
+mod rename_pattern;
+#[cfg(feature = "sftp")]
+mod sftp;
+mod sync;
+mod templates;
+mod timestamps;
+mod vfs;
 
+#[cfg(feature = "archive")]
+pub use archive::{Archive, ArchiveEntry, ArchiveKind};
+#[cfg(feature = "async")]
+pub use async_file_manager::AsyncFileManager;
+#[cfg(feature = "async")]
+pub use async_fs_ops::AsyncFsOps;
+pub use audit::{audited, AuditLog};
+pub use bulk_rename::{BulkChange, BulkEdit};
+pub use checksums::{
+    checksum, hash_file, verify_manifest, write_manifest, Checksum, ChecksumProgress,
+    HashAlgorithm, Mismatch, VerifyReport,
+};
+#[cfg(feature = "archive")]
+pub use compress::{create_archive, CompressOptions, CompressProgress};
+pub use config::{config_dir, state_dir, Config};
+pub use dir_diff::{compare_dirs, CompareOptions, DirDiff};
+pub use disk_usage::{disk_usage, DiskUsage, DiskUsageEvent, DiskUsageScanner};
+pub use duplicates::{find_duplicates, link_duplicates, DuplicateGroup};
 pub use file_manager::FileManager;
 pub use file_manager::FileType;
 pub use file_manager::Item;
+pub use file_manager::LinkTarget;
 pub use fs_ops::FsOps;
+#[cfg(feature = "git")]
+pub use git::{GitRepo, GitStatus};
+pub use jobs::{Job, JobEvent, JobId, JobKind, JobQueue, JobStatus};
+pub use list_state::ListState;
+pub use mode::{format_mode, ModeChange, ModeClause, ModeOp, ModeParseError};
+pub use ops::copy;
+pub use owners::Owners;
+pub use plan::{Plan, PlannedOp};
+pub use rename_pattern::{preview_rename, Case, RenameConflict, RenamePattern, RenamePreview};
+#[cfg(feature = "sftp")]
+pub use sftp::{SftpFs, SftpLocation, TransferProgress};
+pub use sync::{plan_sync, sync, SyncAction, SyncOptions, SyncPlan, SyncProgress, SyncReport};
+pub use templates::Templates;
+pub use timestamps::{TimeSpec, Timestamps};
+pub use vfs::{LocalFs, MemFs, Metadata, Vfs};
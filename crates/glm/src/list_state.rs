@@ -2,8 +2,10 @@ use std::path::PathBuf;
 
 use crate::file_manager::Item;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ListState {
     pub items: Vec<Item>,
     pub current_dir: PathBuf,
+    /// Item the cursor should be placed on, like one that was just created
+    pub focus: Option<PathBuf>,
 }
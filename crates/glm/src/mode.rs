@@ -0,0 +1,270 @@
+use std::fmt;
+use std::str::FromStr;
+
+const SETUID: u32 = 0o4000;
+const SETGID: u32 = 0o2000;
+const STICKY: u32 = 0o1000;
+
+/// A change of permission bits, parsed from an octal mode like `755` or a
+/// symbolic one like `u+x,go-w`, as understood by chmod.
+///
+/// Symbolic clauses without a class apply to everyone, the umask is not
+/// taken into account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModeChange {
+    /// Replaces every permission bit
+    Absolute(u32),
+    /// Applied one clause after the other
+    Symbolic(Vec<ModeClause>),
+}
+
+/// A single action of a symbolic mode, like the `+x` of `u+x`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeClause {
+    /// Bits of the classes the clause applies to
+    pub who: u32,
+    pub op: ModeOp,
+    /// Bits to add, remove or set, before being limited to `who`
+    pub bits: u32,
+    /// Whether `X` was given, execute only for directories or items that
+    /// are already executable by someone
+    pub conditional_execute: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeOp {
+    Add,
+    Remove,
+    Set,
+}
+
+/// Why a mode couldn't be parsed, with the position of the offending
+/// character, counting from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ModeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid mode at {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for ModeParseError {}
+
+fn error(position: usize, message: impl Into<String>) -> ModeParseError {
+    ModeParseError {
+        position,
+        message: message.into(),
+    }
+}
+
+impl ModeChange {
+    /// A change that adds the bits of `add` and removes the bits of
+    /// `remove`, leaving every other bit alone.
+    pub fn toggle(add: u32, remove: u32) -> Self {
+        let clause = |op, bits| ModeClause {
+            who: 0o7777,
+            op,
+            bits,
+            conditional_execute: false,
+        };
+        ModeChange::Symbolic(vec![
+            clause(ModeOp::Add, add & 0o7777),
+            clause(ModeOp::Remove, remove & 0o7777),
+        ])
+    }
+
+    /// Returns the permission bits of an item with `mode` after the change.
+    pub fn apply(&self, mode: u32, is_dir: bool) -> u32 {
+        let clauses = match self {
+            ModeChange::Absolute(mode) => return *mode,
+            ModeChange::Symbolic(clauses) => clauses,
+        };
+        clauses.iter().fold(mode & 0o7777, |mode, clause| {
+            let mut bits = clause.bits;
+            if clause.conditional_execute && (is_dir || mode & 0o111 != 0) {
+                bits |= 0o111;
+            }
+            let bits = bits & clause.who;
+            match clause.op {
+                ModeOp::Add => mode | bits,
+                ModeOp::Remove => mode & !bits,
+                ModeOp::Set => (mode & !clause.who) | bits,
+            }
+        })
+    }
+}
+
+impl FromStr for ModeChange {
+    type Err = ModeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(error(0, "mode is empty"));
+        }
+        if s.starts_with(|c: char| c.is_ascii_digit()) {
+            return parse_octal(s);
+        }
+        parse_symbolic(s)
+    }
+}
+
+fn parse_octal(s: &str) -> Result<ModeChange, ModeParseError> {
+    let mut mode = 0;
+    for (position, c) in s.char_indices() {
+        let digit = c
+            .to_digit(8)
+            .ok_or_else(|| error(position, format!("`{c}` is not an octal digit")))?;
+        if position >= 4 {
+            return Err(error(position, "octal modes have at most 4 digits"));
+        }
+        mode = mode * 8 + digit;
+    }
+    Ok(ModeChange::Absolute(mode))
+}
+
+fn parse_symbolic(s: &str) -> Result<ModeChange, ModeParseError> {
+    let mut clauses = vec![];
+    let mut chars = s.char_indices().peekable();
+    loop {
+        let start = chars.peek().map_or(s.len(), |(position, _)| *position);
+        let mut who = 0;
+        while let Some(&(_, c)) = chars.peek() {
+            who |= match c {
+                'u' => 0o700 | SETUID,
+                'g' => 0o070 | SETGID,
+                'o' => 0o007 | STICKY,
+                'a' => 0o7777,
+                _ => break,
+            };
+            chars.next();
+        }
+        if who == 0 {
+            who = 0o7777;
+        }
+
+        let mut actions = 0;
+        while let Some(&(position, c)) = chars.peek() {
+            let op = match c {
+                '+' => ModeOp::Add,
+                '-' => ModeOp::Remove,
+                '=' => ModeOp::Set,
+                ',' => break,
+                _ => {
+                    let expected = match actions {
+                        0 => "expected a class (u, g, o, a) or an operator (+, -, =)",
+                        _ => "expected a permission (r, w, x, X, s, t) or an operator",
+                    };
+                    return Err(error(position, format!("unexpected `{c}`, {expected}")));
+                }
+            };
+            chars.next();
+            let mut clause = ModeClause {
+                who,
+                op,
+                bits: 0,
+                conditional_execute: false,
+            };
+            while let Some(&(_, c)) = chars.peek() {
+                match c {
+                    'r' => clause.bits |= 0o444,
+                    'w' => clause.bits |= 0o222,
+                    'x' => clause.bits |= 0o111,
+                    'X' => clause.conditional_execute = true,
+                    's' => clause.bits |= SETUID | SETGID,
+                    't' => clause.bits |= STICKY,
+                    _ => break,
+                }
+                chars.next();
+            }
+            clauses.push(clause);
+            actions += 1;
+        }
+        if actions == 0 {
+            return Err(error(start, "clause has no operator (+, -, =)"));
+        }
+
+        match chars.next() {
+            Some(_) => {
+                if chars.peek().is_none() {
+                    return Err(error(s.len(), "expected a clause after `,`"));
+                }
+            }
+            None => return Ok(ModeChange::Symbolic(clauses)),
+        }
+    }
+}
+
+/// Formats permission bits like `ls -l`, such as `rwxr-xr-x`.
+pub fn format_mode(mode: u32) -> String {
+    let mut text = String::with_capacity(9);
+    for (shift, special, special_char) in [(6, SETUID, 's'), (3, SETGID, 's'), (0, STICKY, 't')] {
+        let bits = mode >> shift;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(mode: &str, current: u32, is_dir: bool) -> u32 {
+        let change = mode.parse::<ModeChange>().expect("failed to parse mode");
+        change.apply(current, is_dir)
+    }
+
+    #[test]
+    fn test_octal() {
+        assert_eq!(apply("755", 0o600, false), 0o755);
+        assert_eq!(apply("0644", 0o777, false), 0o644);
+        assert_eq!(apply("4755", 0, false), 0o4755);
+
+        let error = "758".parse::<ModeChange>().unwrap_err();
+        assert_eq!(error.position, 2);
+        assert_eq!(
+            error.to_string(),
+            "invalid mode at 2: `8` is not an octal digit"
+        );
+        assert_eq!("07550".parse::<ModeChange>().unwrap_err().position, 4);
+    }
+
+    #[test]
+    fn test_symbolic() {
+        assert_eq!(apply("u+x,go-w", 0o666, false), 0o744);
+        assert_eq!(apply("a=r", 0o4777, false), 0o444);
+        assert_eq!(apply("+x", 0o644, false), 0o755);
+        assert_eq!(apply("g=", 0o640, false), 0o600);
+        assert_eq!(apply("o+X", 0o640, true), 0o641);
+        assert_eq!(apply("o+X", 0o640, false), 0o640);
+        assert_eq!(apply("u+x-w+s", 0o644, false), 0o4544);
+        assert_eq!(apply("o+t", 0o777, true), 0o1777);
+    }
+
+    #[test]
+    fn test_symbolic_errors() {
+        let position = |mode: &str| mode.parse::<ModeChange>().unwrap_err().position;
+        assert_eq!(position("u+x,gq-w"), 5);
+        assert_eq!(position("u+x,"), 4);
+        assert_eq!(position("u+x,go"), 4);
+        assert_eq!(position("ux"), 1);
+        assert_eq!(position(""), 0);
+    }
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode(0o755), "rwxr-xr-x");
+        assert_eq!(format_mode(0o4754), "rwsr-xr--");
+        assert_eq!(format_mode(0o1776), "rwxrwxrwT");
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use filetime::FileTime;
+
+/// Size of the chunks file contents are copied in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Copies `src` to `dst`, recursively when `src` is a directory.
+///
+/// Symbolic links are copied as links, and permissions and modification
+/// times are preserved, so a copied tree compares equal to the original.
+/// Existing files at the destination are overwritten, while existing
+/// directories are merged. Symbolic links at the destination are replaced,
+/// never written through.
+pub fn copy<P, Q>(src: P, dst: Q) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    copy_with_progress(src.as_ref(), dst.as_ref(), &mut |_| Ok(()))
+}
+
+/// Same as [`copy`], calling `progress` with the amount of bytes written
+/// after every chunk of file contents. The copy stops at the first error
+/// returned by `progress`.
+pub(crate) fn copy_with_progress(
+    src: &Path,
+    dst: &Path,
+    progress: &mut dyn FnMut(u64) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let metadata = std::fs::symlink_metadata(src)?;
+    let file_type = metadata.file_type();
+
+    if file_type.is_dir() {
+        if dst.starts_with(src) {
+            anyhow::bail!(
+                "cannot copy {} into itself at {}",
+                src.display(),
+                dst.display()
+            );
+        }
+        remove_symlink(dst)?;
+        std::fs::create_dir_all(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            copy_with_progress(&entry.path(), &dst.join(entry.file_name()), progress)?;
+        }
+        std::fs::set_permissions(dst, metadata.permissions())?;
+    } else if file_type.is_symlink() {
+        let target = std::fs::read_link(src)?;
+        if std::fs::symlink_metadata(dst).is_ok() {
+            std::fs::remove_file(dst)?;
+        }
+        symlink(&target, dst)?;
+        filetime::set_symlink_file_times(
+            dst,
+            FileTime::from_last_access_time(&metadata),
+            FileTime::from_last_modification_time(&metadata),
+        )?;
+        return Ok(());
+    } else {
+        let mut reader = File::open(src)?;
+        remove_symlink(dst)?;
+        let mut writer = File::create(dst)?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            progress(read as u64)?;
+        }
+        std::fs::set_permissions(dst, metadata.permissions())?;
+    }
+
+    filetime::set_file_mtime(dst, FileTime::from_last_modification_time(&metadata))?;
+    Ok(())
+}
+
+/// Removes `path` when it's a symbolic link, so what's copied over it
+/// lands on the link itself and not wherever it points to.
+fn remove_symlink(path: &Path) -> anyhow::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => Ok(std::fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+pub(crate) fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    match target.is_dir() {
+        true => std::os::windows::fs::symlink_dir(target, link),
+        false => std::os::windows::fs::symlink_file(target, link),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_copy_directory() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("a.txt"), "a").unwrap();
+        fs::write(src.join("nested/b.txt"), "b").unwrap();
+        filetime::set_file_mtime(src.join("a.txt"), FileTime::from_unix_time(1000, 0)).unwrap();
+
+        copy(&src, dir.path().join("dst")).expect("failed to copy");
+
+        let copied = dir.path().join("dst");
+        assert_eq!(fs::read_to_string(copied.join("a.txt")).unwrap(), "a");
+        assert_eq!(
+            fs::read_to_string(copied.join("nested/b.txt")).unwrap(),
+            "b"
+        );
+        let mtime =
+            FileTime::from_last_modification_time(&fs::metadata(copied.join("a.txt")).unwrap());
+        assert_eq!(mtime, FileTime::from_unix_time(1000, 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_symlink_as_link() {
+        let dir = tempdir().expect("failed to create tempdir");
+        std::os::unix::fs::symlink("missing", dir.path().join("link")).unwrap();
+
+        copy(dir.path().join("link"), dir.path().join("copy")).expect("failed to copy");
+
+        let target = fs::read_link(dir.path().join("copy")).unwrap();
+        assert_eq!(target, Path::new("missing"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_replaces_symlinks_at_destination() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("a.txt"), "new").unwrap();
+        fs::write(src.join("nested/b.txt"), "new").unwrap();
+        fs::create_dir_all(dir.path().join("outside")).unwrap();
+        fs::write(dir.path().join("outside.txt"), "outside").unwrap();
+        fs::create_dir(&dst).unwrap();
+        std::os::unix::fs::symlink("../outside.txt", dst.join("a.txt")).unwrap();
+        std::os::unix::fs::symlink("../outside", dst.join("nested")).unwrap();
+
+        copy(&src, &dst).expect("failed to copy");
+
+        let outside = fs::read_to_string(dir.path().join("outside.txt")).unwrap();
+        assert_eq!(outside, "outside");
+        assert!(!dir.path().join("outside/b.txt").exists());
+        assert_eq!(fs::read_to_string(dst.join("a.txt")).unwrap(), "new");
+        assert!(fs::symlink_metadata(dst.join("nested")).unwrap().is_dir());
+    }
+
+    #[test]
+    fn test_copy_into_itself() {
+        let dir = tempdir().expect("failed to create tempdir");
+
+        let result = copy(dir.path(), dir.path().join("inner"));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Names of users and groups, read from files in the format of
+/// `/etc/passwd` and `/etc/group`.
+///
+/// Names only come from those files, users of other sources like LDAP can
+/// still be given by their numeric id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Owners {
+    users: BTreeMap<u32, String>,
+    groups: BTreeMap<u32, String>,
+}
+
+impl Owners {
+    /// Reads `/etc/passwd` and `/etc/group`.
+    pub fn load() -> anyhow::Result<Self> {
+        Self::from_files("/etc/passwd", "/etc/group")
+    }
+
+    /// The users and groups of this machine, read once and kept for the
+    /// rest of the process, to display owners without reading the files
+    /// for every item. It's empty when the files can't be read.
+    pub fn system() -> &'static Owners {
+        static SYSTEM: OnceLock<Owners> = OnceLock::new();
+        SYSTEM.get_or_init(|| Owners::load().unwrap_or_default())
+    }
+
+    pub fn from_files<P, G>(passwd: P, group: G) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+        G: AsRef<Path>,
+    {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", path.display()))
+        };
+        let (passwd, group) = (passwd.as_ref(), group.as_ref());
+        Ok(Owners {
+            users: parse(&read(passwd)?)
+                .map_err(|e| anyhow::anyhow!("{}: {e}", passwd.display()))?,
+            groups: parse(&read(group)?)
+                .map_err(|e| anyhow::anyhow!("{}: {e}", group.display()))?,
+        })
+    }
+
+    /// Parses the contents of a passwd and a group file.
+    pub fn parse(passwd: &str, group: &str) -> anyhow::Result<Self> {
+        Ok(Owners {
+            users: parse(passwd)?,
+            groups: parse(group)?,
+        })
+    }
+
+    pub fn user_name(&self, uid: u32) -> Option<&str> {
+        self.users.get(&uid).map(String::as_str)
+    }
+
+    pub fn group_name(&self, gid: u32) -> Option<&str> {
+        self.groups.get(&gid).map(String::as_str)
+    }
+
+    /// Name of the user, or its id when it has none, for display.
+    pub fn user_label(&self, uid: u32) -> String {
+        self.user_name(uid)
+            .map_or_else(|| uid.to_string(), str::to_string)
+    }
+
+    /// Name of the group, or its id when it has none, for display.
+    pub fn group_label(&self, gid: u32) -> String {
+        self.group_name(gid)
+            .map_or_else(|| gid.to_string(), str::to_string)
+    }
+
+    /// Resolves a user given by name or by numeric id.
+    pub fn uid(&self, user: &str) -> anyhow::Result<u32> {
+        resolve(&self.users, user).ok_or_else(|| anyhow::anyhow!("no user named {user}"))
+    }
+
+    /// Resolves a group given by name or by numeric id.
+    pub fn gid(&self, group: &str) -> anyhow::Result<u32> {
+        resolve(&self.groups, group).ok_or_else(|| anyhow::anyhow!("no group named {group}"))
+    }
+}
+
+fn resolve(names: &BTreeMap<u32, String>, name: &str) -> Option<u32> {
+    if let Ok(id) = name.parse() {
+        return Some(id);
+    }
+    names
+        .iter()
+        .find(|(_, candidate)| *candidate == name)
+        .map(|(id, _)| *id)
+}
+
+/// Reads the name and id of every line, which are the first and third
+/// fields on both passwd and group files. The first name wins when an id
+/// is repeated.
+fn parse(text: &str) -> anyhow::Result<BTreeMap<u32, String>> {
+    let mut names = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        // NIS includes like `+@admins` carry no names of their own
+        if line.trim().is_empty() || line.starts_with(['#', '+', '-']) {
+            continue;
+        }
+        let fields = line.split(':').collect::<Vec<_>>();
+        let (Some(name), Some(id)) = (fields.first(), fields.get(2)) else {
+            anyhow::bail!("line {}: expected `name:password:id:...`", number + 1);
+        };
+        let id = id
+            .parse()
+            .map_err(|_| anyhow::anyhow!("line {}: `{id}` is not a numeric id", number + 1))?;
+        names.entry(id).or_insert_with(|| name.to_string());
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const PASSWD: &str = "\
+root:x:0:0:root:/root:/bin/bash
+# people
+ada:x:1000:1000:Ada Lovelace:/home/ada:/bin/zsh
+toor:x:0:0:root again:/root:/bin/sh
++@netusers
+";
+    const GROUP: &str = "root:x:0:\nstaff:x:50:ada\nada:x:1000:\n";
+
+    #[test]
+    fn test_resolve_names_and_ids() {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::write(dir.path().join("passwd"), PASSWD).unwrap();
+        fs::write(dir.path().join("group"), GROUP).unwrap();
+
+        let sut = Owners::from_files(dir.path().join("passwd"), dir.path().join("group"))
+            .expect("failed to read owners");
+
+        assert_eq!(sut.uid("ada").unwrap(), 1000);
+        assert_eq!(sut.uid("4242").unwrap(), 4242);
+        assert_eq!(sut.gid("staff").unwrap(), 50);
+        assert_eq!(sut.user_name(0), Some("root"));
+        assert_eq!(sut.group_label(50), "staff");
+        assert_eq!(sut.user_label(4242), "4242");
+        assert_eq!(
+            sut.uid("grace").unwrap_err().to_string(),
+            "no user named grace"
+        );
+    }
+
+    #[test]
+    fn test_malformed_files() {
+        let error = Owners::parse("root:x:0:0::/root:/bin/sh\nbroken\n", "").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: expected `name:password:id:...`");
+        let error = Owners::parse("", "wheel:x:ten:\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: `ten` is not a numeric id");
+
+        let dir = tempdir().expect("failed to create tempdir");
+        let error =
+            Owners::from_files(dir.path().join("passwd"), dir.path().join("group")).unwrap_err();
+        assert!(error.to_string().starts_with("failed to read"));
+    }
+}
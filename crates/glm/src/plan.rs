@@ -0,0 +1,110 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::timestamps::format_time;
+
+/// A single change to the file system, part of a [`Plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedOp {
+    /// Renames or moves an item, on the same backend
+    Rename { from: PathBuf, to: PathBuf },
+    /// Copies an item, recursively for directories
+    Copy { from: PathBuf, to: PathBuf },
+    /// Moves an item to the trash
+    Delete { path: PathBuf },
+    /// Creates a single directory
+    CreateDir { path: PathBuf },
+    /// Creates a file, never replacing an existing one
+    CreateFile { path: PathBuf, contents: Vec<u8> },
+    /// Changes the permission bits of an item
+    Chmod { path: PathBuf, from: u32, to: u32 },
+    /// Creates a symbolic link at `link`, storing `target` as given
+    Symlink { target: PathBuf, link: PathBuf },
+    /// Creates a hard link at `link` to the file at `target`
+    HardLink { target: PathBuf, link: PathBuf },
+    /// Changes the owning user and group of an item, as `(uid, gid)`
+    Chown {
+        path: PathBuf,
+        from: (u32, u32),
+        to: (u32, u32),
+    },
+    /// Sets the access and modification times of an item, `None` keeps
+    /// the current one
+    SetTimes {
+        path: PathBuf,
+        accessed: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    },
+}
+
+impl fmt::Display for PlannedOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedOp::Rename { from, to } => {
+                write!(f, "rename {} -> {}", from.display(), to.display())
+            }
+            PlannedOp::Copy { from, to } => {
+                write!(f, "copy {} -> {}", from.display(), to.display())
+            }
+            PlannedOp::Delete { path } => write!(f, "trash {}", path.display()),
+            PlannedOp::CreateDir { path } => write!(f, "mkdir {}", path.display()),
+            PlannedOp::CreateFile { path, .. } => write!(f, "create {}", path.display()),
+            PlannedOp::Chmod { path, from, to } => {
+                write!(f, "chmod {from:03o} -> {to:03o} {}", path.display())
+            }
+            PlannedOp::Symlink { target, link } => {
+                write!(f, "symlink {} -> {}", link.display(), target.display())
+            }
+            PlannedOp::HardLink { target, link } => {
+                write!(f, "hardlink {} -> {}", link.display(), target.display())
+            }
+            PlannedOp::Chown { path, from, to } => write!(
+                f,
+                "chown {}:{} -> {}:{} {}",
+                from.0,
+                from.1,
+                to.0,
+                to.1,
+                path.display()
+            ),
+            PlannedOp::SetTimes {
+                path,
+                accessed,
+                modified,
+            } => {
+                write!(f, "touch")?;
+                if let Some(accessed) = accessed {
+                    write!(f, " atime {}", format_time(*accessed))?;
+                }
+                if let Some(modified) = modified {
+                    write!(f, " mtime {}", format_time(*modified))?;
+                }
+                write!(f, " {}", path.display())
+            }
+        }
+    }
+}
+
+/// Every change a mutating operation of [`crate::FileManager`] makes, in
+/// order. A dry run only produces the plan, without touching anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub ops: Vec<PlannedOp>,
+    /// Whether the plan was only computed, and not executed
+    pub dry_run: bool,
+}
+
+impl Plan {
+    pub fn new(ops: Vec<PlannedOp>, dry_run: bool) -> Self {
+        Plan { ops, dry_run }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// How to change the letter case of a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    /// First letter of every word in upper case, the rest in lower case
+    Title,
+    /// Words in lower case joined by `_`, splitting camelCase too
+    Snake,
+    /// Words in lower case joined by `-`, splitting camelCase too
+    Kebab,
+}
+
+impl Case {
+    pub const ALL: [Case; 5] = [
+        Case::Upper,
+        Case::Lower,
+        Case::Title,
+        Case::Snake,
+        Case::Kebab,
+    ];
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Case::Upper => text.to_uppercase(),
+            Case::Lower => text.to_lowercase(),
+            Case::Title => {
+                let mut title = String::with_capacity(text.len());
+                let mut is_start = true;
+                for c in text.chars() {
+                    match is_start {
+                        true => title.extend(c.to_uppercase()),
+                        false => title.extend(c.to_lowercase()),
+                    }
+                    is_start = !c.is_alphanumeric();
+                }
+                title
+            }
+            Case::Snake => words(text).join("_"),
+            Case::Kebab => words(text).join("-"),
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Case::Upper => "upper",
+            Case::Lower => "lower",
+            Case::Title => "title",
+            Case::Snake => "snake",
+            Case::Kebab => "kebab",
+        })
+    }
+}
+
+impl FromStr for Case {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Case::ALL
+            .into_iter()
+            .find(|case| case.to_string() == s.to_lowercase())
+            .ok_or_else(|| {
+                anyhow::anyhow!("unknown case {s}, use upper, lower, title, snake or kebab")
+            })
+    }
+}
+
+/// Splits `text` into lower case words, on anything that isn't a letter
+/// or a digit and where a lower case letter or digit meets an upper case
+/// one.
+fn words(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            previous = None;
+            continue;
+        }
+        let is_boundary = c.is_uppercase()
+            && previous.is_some_and(|previous| previous.is_lowercase() || previous.is_numeric());
+        if is_boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+        previous = Some(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// A transform of the names of many items at once. Steps run in order:
+/// find and replace over the whole name, then the case of the name
+/// without its extension, then the extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenamePattern {
+    /// Regex searched in the name, every match is replaced. When empty,
+    /// the replacement is the new name without the extension, if any
+    pub find: String,
+    /// Replacement of every match, with `$1` or `${name}` for capture
+    /// groups, and `{n}` for the number of the item
+    pub replace: String,
+    /// Number of the first item for `{n}`, the next ones count up from it
+    pub start: u64,
+    /// Minimum amount of digits of `{n}`, padded with zeros
+    pub padding: usize,
+    pub case: Option<Case>,
+    /// New extension, without the dot. Empty removes the extension
+    pub extension: Option<String>,
+}
+
+/// Why an item can't take its new name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameConflict {
+    /// Another item of the selection gets the same name
+    Duplicate,
+    /// An item that isn't renamed already has the name
+    Exists,
+    /// The name is empty or has a `/`
+    Invalid,
+}
+
+impl fmt::Display for RenameConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RenameConflict::Duplicate => "used twice",
+            RenameConflict::Exists => "already exists",
+            RenameConflict::Invalid => "invalid name",
+        })
+    }
+}
+
+/// The new name of an item, as computed before renaming anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenamePreview {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub conflict: Option<RenameConflict>,
+}
+
+impl RenamePreview {
+    pub fn is_changed(&self) -> bool {
+        self.from != self.to
+    }
+}
+
+impl RenamePattern {
+    /// Compiles the regex, so many names can be transformed with it.
+    pub fn compile(&self) -> anyhow::Result<Option<Regex>> {
+        if self.find.is_empty() {
+            return Ok(None);
+        }
+        Regex::new(&self.find)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("invalid regex: {e}"))
+    }
+
+    /// The new name of the item at position `index` of the selection,
+    /// with the regex given by [`RenamePattern::compile`].
+    pub fn apply(&self, name: &str, index: usize, find: Option<&Regex>) -> String {
+        let number = format!(
+            "{:0width$}",
+            self.start + index as u64,
+            width = self.padding
+        );
+        let replace = self.replace.replace("{n}", &number);
+        let name = match find {
+            Some(find) => find.replace_all(name, replace.as_str()).into_owned(),
+            None if replace.is_empty() => name.to_string(),
+            None => match split_extension(name) {
+                (_, Some(extension)) => format!("{replace}.{extension}"),
+                (_, None) => replace,
+            },
+        };
+
+        let (stem, extension) = split_extension(&name);
+        let stem = match self.case {
+            Some(case) => case.apply(stem),
+            None => stem.to_string(),
+        };
+        match (self.extension.as_deref(), extension) {
+            (Some(""), _) | (None, None) => stem,
+            (Some(extension), _) | (None, Some(extension)) => format!("{stem}.{extension}"),
+        }
+    }
+}
+
+/// Computes the new name of every path with `pattern`, in the order given,
+/// flagging the ones that can't be renamed. `exists` tells whether an item
+/// outside of `paths` already has a name.
+pub fn preview_rename<P, F>(
+    paths: &[P],
+    pattern: &RenamePattern,
+    exists: F,
+) -> anyhow::Result<Vec<RenamePreview>>
+where
+    P: AsRef<Path>,
+    F: Fn(&Path) -> bool,
+{
+    let find = pattern.compile()?;
+    let mut previews = vec![];
+    for (index, path) in paths.iter().enumerate() {
+        let from = path.as_ref();
+        let name = from
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} has no name", from.display()))?
+            .to_string_lossy();
+        let new_name = pattern.apply(&name, index, find.as_ref());
+        let is_invalid =
+            matches!(new_name.as_str(), "" | "." | "..") || new_name.contains(['/', '\0']);
+        previews.push(RenamePreview {
+            from: from.to_path_buf(),
+            to: from.with_file_name(&new_name),
+            conflict: is_invalid.then_some(RenameConflict::Invalid),
+        });
+    }
+
+    let sources = paths
+        .iter()
+        .map(|path| path.as_ref())
+        .collect::<HashSet<_>>();
+    let mut targets: HashMap<PathBuf, usize> = HashMap::new();
+    for preview in &previews {
+        *targets.entry(preview.to.clone()).or_default() += 1;
+    }
+    for preview in previews.iter_mut() {
+        if !preview.is_changed() || preview.conflict.is_some() {
+            continue;
+        }
+        if targets[&preview.to] > 1 {
+            preview.conflict = Some(RenameConflict::Duplicate);
+        } else if !sources.contains(preview.to.as_path()) && exists(&preview.to) {
+            preview.conflict = Some(RenameConflict::Exists);
+        }
+    }
+    Ok(previews)
+}
+
+/// Splits the name on its last dot, leaving dotfiles like `.bashrc`
+/// without an extension.
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rfind('.') {
+        Some(0) | None => (name, None),
+        Some(dot) => (&name[..dot], Some(&name[dot + 1..])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(pattern: &RenamePattern, name: &str, index: usize) -> String {
+        let find = pattern.compile().expect("failed to compile");
+        pattern.apply(name, index, find.as_ref())
+    }
+
+    #[test]
+    fn test_find_replace_and_numbering() {
+        let pattern = RenamePattern {
+            find: r"IMG_(?<day>\d{4})(\d+)".into(),
+            replace: "${day}-$2-{n}".into(),
+            start: 9,
+            padding: 3,
+            ..Default::default()
+        };
+        assert_eq!(apply(&pattern, "IMG_0412123.jpg", 0), "0412-123-009.jpg");
+        assert_eq!(apply(&pattern, "IMG_0412124.jpg", 1), "0412-124-010.jpg");
+        assert_eq!(apply(&pattern, "notes.txt", 2), "notes.txt");
+
+        // without a regex the replacement is the whole name
+        let pattern = RenamePattern {
+            replace: "photo {n}".into(),
+            start: 1,
+            padding: 2,
+            extension: Some("jpeg".into()),
+            ..Default::default()
+        };
+        assert_eq!(apply(&pattern, "IMG_1.JPG", 0), "photo 01.jpeg");
+
+        let invalid = RenamePattern {
+            find: "(".into(),
+            ..Default::default()
+        };
+        assert!(invalid
+            .compile()
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn test_case_and_extension() {
+        let with = |case, extension: Option<&str>| RenamePattern {
+            case: Some(case),
+            extension: extension.map(str::to_string),
+            ..Default::default()
+        };
+        assert_eq!(
+            apply(&with(Case::Upper, None), "read me.md", 0),
+            "READ ME.md"
+        );
+        assert_eq!(apply(&with(Case::Lower, Some("")), "LOG.TXT", 0), "log");
+        assert_eq!(
+            apply(&with(Case::Title, None), "the_great gatsby.epub", 0),
+            "The_Great Gatsby.epub"
+        );
+        assert_eq!(
+            apply(&with(Case::Snake, None), "myFile Name-2024.tar", 0),
+            "my_file_name_2024.tar"
+        );
+        assert_eq!(
+            apply(&with(Case::Kebab, Some("yml")), "Docker_Compose.yaml", 0),
+            "docker-compose.yml"
+        );
+        assert_eq!(apply(&with(Case::Upper, None), ".bashrc", 0), ".BASHRC");
+    }
+}
@@ -0,0 +1,536 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ssh2::{
+    CheckResult, FileStat, KnownHostFileKind, OpenFlags, OpenType, RenameFlags, Session, Sftp,
+};
+
+use crate::file_manager::FileType;
+use crate::vfs::{Metadata, Vfs};
+
+/// Where to connect, parsed from a `sftp://[user@]host[:port][/path]` url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SftpLocation {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+    /// Remote directory to start on, or `None` for the home directory of
+    /// the user
+    pub path: Option<PathBuf>,
+}
+
+impl SftpLocation {
+    /// Parses a `sftp://` url, the user defaults to `$USER` and the port
+    /// to 22.
+    pub fn parse(url: &str) -> anyhow::Result<Self> {
+        let rest = url
+            .strip_prefix("sftp://")
+            .ok_or_else(|| anyhow::anyhow!("{url} is not a sftp:// url"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], Some(&rest[index..])),
+            None => (rest, None),
+        };
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (user.to_string(), host_port),
+            None => (std::env::var("USER").unwrap_or_default(), authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| anyhow::anyhow!("invalid port {port} in {url}"))?,
+            ),
+            None => (host_port, 22),
+        };
+        if host.is_empty() {
+            anyhow::bail!("missing host in {url}");
+        }
+        if user.is_empty() {
+            anyhow::bail!("missing user in {url}");
+        }
+        Ok(SftpLocation {
+            user,
+            host: host.to_string(),
+            port,
+            path: path.filter(|path| *path != "/").map(PathBuf::from),
+        })
+    }
+}
+
+impl fmt::Display for SftpLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sftp://{}@{}", self.user, self.host)?;
+        if self.port != 22 {
+            write!(f, ":{}", self.port)?;
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a file transfer, reported after every chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferProgress {
+    pub done: u64,
+    pub total: u64,
+}
+
+/// A remote file system reached over SFTP.
+pub struct SftpFs {
+    location: SftpLocation,
+    session: Session,
+    sftp: Sftp,
+    /// Metadata of the last listed directory, so listing doesn't need one
+    /// round trip per item
+    listed: Mutex<HashMap<PathBuf, Metadata>>,
+}
+
+impl fmt::Debug for SftpFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SftpFs")
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+impl From<FileStat> for Metadata {
+    fn from(stat: FileStat) -> Self {
+        let file_type = match stat.file_type() {
+            t if t.is_symlink() => FileType::Symlink,
+            t if t.is_dir() => FileType::Directory,
+            _ => FileType::File,
+        };
+        Metadata {
+            file_type,
+            len: stat.size.unwrap_or(0),
+            accessed: stat
+                .atime
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            modified: stat
+                .mtime
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            mode: stat.perm.map(|perm| perm & 0o7777),
+            uid: stat.uid,
+            gid: stat.gid,
+        }
+    }
+}
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+impl SftpFs {
+    /// Connects and authenticates to the host of `location`.
+    ///
+    /// The host key must already be in `~/.ssh/known_hosts`, and the user
+    /// is authenticated with the ssh agent, or the default key files on
+    /// `~/.ssh` without a passphrase.
+    pub fn connect(location: &SftpLocation) -> anyhow::Result<Self> {
+        let stream = TcpStream::connect((location.host.as_str(), location.port))
+            .map_err(|e| anyhow::anyhow!("failed to connect to {location}: {e}"))?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(stream);
+        session.handshake()?;
+        verify_host_key(&session, location)?;
+
+        if session.userauth_agent(&location.user).is_err() {
+            let ssh_dir = home_dir()?.join(".ssh");
+            for key in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+                let private = ssh_dir.join(key);
+                if private.exists()
+                    && session
+                        .userauth_pubkey_file(&location.user, None, &private, None)
+                        .is_ok()
+                {
+                    break;
+                }
+            }
+        }
+        if !session.authenticated() {
+            anyhow::bail!(
+                "authentication failed for {}, add a key to the ssh agent",
+                location
+            );
+        }
+
+        let sftp = session.sftp()?;
+        Ok(SftpFs {
+            location: location.clone(),
+            session,
+            sftp,
+            listed: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Opens another handle over the same connection, to transfer files
+    /// without blocking this one.
+    pub fn try_clone(&self) -> anyhow::Result<Self> {
+        Ok(SftpFs {
+            location: self.location.clone(),
+            session: self.session.clone(),
+            sftp: self.session.sftp()?,
+            listed: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn location(&self) -> &SftpLocation {
+        &self.location
+    }
+
+    /// Returns the directory the location points to, or the home directory
+    /// of the user when it has no path.
+    pub fn initial_dir(&self) -> anyhow::Result<PathBuf> {
+        match &self.location.path {
+            Some(path) => Ok(path.clone()),
+            None => Ok(self.sftp.realpath(Path::new("."))?),
+        }
+    }
+
+    /// Copies the remote `src` to the local `dst`, recursively for
+    /// directories. An existing `dst` is never overwritten.
+    pub fn download<F>(&self, src: &Path, dst: &Path, mut progress: F) -> anyhow::Result<()>
+    where
+        F: FnMut(TransferProgress),
+    {
+        if std::fs::symlink_metadata(dst).is_ok() {
+            anyhow::bail!("{} already exists", dst.display());
+        }
+        let mut files = vec![];
+        let mut pending = vec![(src.to_path_buf(), dst.to_path_buf())];
+        while let Some((src, dst)) = pending.pop() {
+            let stat = self.sftp.lstat(&src)?;
+            if stat.is_dir() {
+                std::fs::create_dir_all(&dst)?;
+                for (child, _) in self.sftp.readdir(&src)? {
+                    let name = child.file_name().unwrap_or_default();
+                    pending.push((child.clone(), dst.join(name)));
+                }
+            } else if stat.file_type().is_symlink() {
+                crate::ops::symlink(&self.sftp.readlink(&src)?, &dst)?;
+            } else {
+                files.push((src, dst, stat.size.unwrap_or(0)));
+            }
+        }
+
+        let total = files.iter().map(|(.., size)| size).sum();
+        let mut done = 0;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        for (src, dst, _) in files {
+            let mut remote = self.sftp.open(&src)?;
+            let mut local = File::options().write(true).create_new(true).open(&dst)?;
+            loop {
+                let read = remote.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                local.write_all(&buffer[..read])?;
+                done += read as u64;
+                progress(TransferProgress { done, total });
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the local `src` to the remote `dst`, recursively for
+    /// directories. An existing `dst` is never overwritten.
+    pub fn upload<F>(&self, src: &Path, dst: &Path, mut progress: F) -> anyhow::Result<()>
+    where
+        F: FnMut(TransferProgress),
+    {
+        if self.sftp.lstat(dst).is_ok() {
+            anyhow::bail!("{} already exists", dst.display());
+        }
+        let mut files = vec![];
+        let mut pending = vec![(src.to_path_buf(), dst.to_path_buf())];
+        while let Some((src, dst)) = pending.pop() {
+            let metadata = std::fs::symlink_metadata(&src)?;
+            if metadata.is_dir() {
+                if self.sftp.stat(&dst).is_err() {
+                    self.sftp.mkdir(&dst, 0o755)?;
+                }
+                for entry in std::fs::read_dir(&src)? {
+                    let entry = entry?;
+                    pending.push((entry.path(), dst.join(entry.file_name())));
+                }
+            } else if metadata.is_symlink() {
+                self.sftp.symlink(&std::fs::read_link(&src)?, &dst)?;
+            } else {
+                files.push((src, dst, metadata.len()));
+            }
+        }
+
+        let total = files.iter().map(|(.., size)| size).sum();
+        let mut done = 0;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        for (src, dst, _) in files {
+            if self.sftp.lstat(&dst).is_ok() {
+                anyhow::bail!("{} already exists", dst.display());
+            }
+            let mut local = File::open(&src)?;
+            let mut remote = self.sftp.create(&dst)?;
+            loop {
+                let read = local.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                remote.write_all(&buffer[..read])?;
+                done += read as u64;
+                progress(TransferProgress { done, total });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn home_dir() -> anyhow::Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow::anyhow!("HOME is not set"))
+}
+
+/// Refuses to talk to hosts that are not in `~/.ssh/known_hosts`, or whose
+/// key changed, the same way `ssh` does.
+fn verify_host_key(session: &Session, location: &SftpLocation) -> anyhow::Result<()> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| anyhow::anyhow!("{} sent no host key", location.host))?;
+    let mut known_hosts = session.known_hosts()?;
+    let file = home_dir()?.join(".ssh/known_hosts");
+    if file.exists() {
+        known_hosts.read_file(&file, KnownHostFileKind::OpenSSH)?;
+    }
+    match known_hosts.check_port(&location.host, location.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => anyhow::bail!(
+            "{} is not a known host, connect once with ssh to trust it",
+            location.host
+        ),
+        CheckResult::Mismatch => anyhow::bail!(
+            "the host key of {} changed, refusing to connect",
+            location.host
+        ),
+        CheckResult::Failure => anyhow::bail!("failed to check the host key of {}", location.host),
+    }
+}
+
+impl Vfs for SftpFs {
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let entries = self.sftp.readdir(path)?;
+        let mut listed = self.listed.lock().expect("listing cache is poisoned");
+        listed.clear();
+        // readdir reports the attributes of links themselves, like lstat
+        Ok(entries
+            .into_iter()
+            .map(|(path, stat)| {
+                listed.insert(path.clone(), stat.into());
+                path
+            })
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        Ok(self.sftp.stat(path)?.into())
+    }
+
+    fn lstat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        let listed = self.listed.lock().expect("listing cache is poisoned");
+        match listed.get(path) {
+            Some(metadata) => Ok(metadata.clone()),
+            None => Ok(self.sftp.lstat(path)?.into()),
+        }
+    }
+
+    fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        let mut contents = vec![];
+        self.sftp.open(path)?.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.invalidate();
+        self.sftp.create(path)?.write_all(contents)?;
+        Ok(())
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.invalidate();
+        let flags = OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE;
+        let mut file = self
+            .sftp
+            .open_mode(path, flags, 0o644, OpenType::File)
+            .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", path.display()))?;
+        file.write_all(contents)?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        self.invalidate();
+        let flags = RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE;
+        Ok(self.sftp.rename(from, to, Some(flags))?)
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        self.invalidate();
+        match self.sftp.lstat(path)?.is_dir() {
+            true => self.sftp.rmdir(path)?,
+            false => self.sftp.unlink(path)?,
+        }
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        self.invalidate();
+        Ok(self.sftp.mkdir(path, 0o755)?)
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        self.invalidate();
+        let stat = FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: Some(mode),
+            atime: None,
+            mtime: None,
+        };
+        Ok(self.sftp.setstat(path, stat)?)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        self.invalidate();
+        // the link is created at the second path, pointing to the first
+        Ok(self.sftp.symlink(target, link)?)
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(self.sftp.readlink(path)?)
+    }
+
+    fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(self.sftp.realpath(path)?)
+    }
+
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> anyhow::Result<()> {
+        self.invalidate();
+        let stat = FileStat {
+            size: None,
+            uid: Some(uid),
+            gid: Some(gid),
+            perm: None,
+            atime: None,
+            mtime: None,
+        };
+        Ok(self.sftp.setstat(path, stat)?)
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        accessed: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    ) -> anyhow::Result<()> {
+        self.invalidate();
+        // sftp only sets both times at once, the one kept is read first
+        let current = match (accessed, modified) {
+            (Some(_), Some(_)) => None,
+            _ => Some(self.sftp.stat(path)?),
+        };
+        let seconds = |time: Option<SystemTime>, current: Option<u64>| match time {
+            Some(time) => time
+                .duration_since(UNIX_EPOCH)
+                .map(|since| Some(since.as_secs()))
+                .map_err(|_| anyhow::anyhow!("cannot set times before 1970 over sftp")),
+            None => Ok(current),
+        };
+        let stat = FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: None,
+            atime: seconds(accessed, current.as_ref().and_then(|stat| stat.atime))?,
+            mtime: seconds(modified, current.as_ref().and_then(|stat| stat.mtime))?,
+        };
+        Ok(self.sftp.setstat(path, stat)?)
+    }
+}
+
+impl SftpFs {
+    fn invalidate(&self) {
+        self.listed
+            .lock()
+            .expect("listing cache is poisoned")
+            .clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location() {
+        let location = SftpLocation::parse("sftp://ci@build-01:2222/srv/builds").unwrap();
+
+        assert_eq!(
+            location,
+            SftpLocation {
+                user: "ci".into(),
+                host: "build-01".into(),
+                port: 2222,
+                path: Some("/srv/builds".into()),
+            }
+        );
+        assert_eq!(location.to_string(), "sftp://ci@build-01:2222");
+    }
+
+    #[test]
+    fn test_parse_location_defaults() {
+        let location = SftpLocation::parse("sftp://ci@build-01/").unwrap();
+
+        assert_eq!(location.port, 22);
+        assert_eq!(location.path, None);
+    }
+
+    #[test]
+    fn test_parse_invalid_location() {
+        assert!(SftpLocation::parse("ssh://ci@host").is_err());
+        assert!(SftpLocation::parse("sftp://ci@host:port").is_err());
+        assert!(SftpLocation::parse("sftp://ci@/path").is_err());
+    }
+
+    /// Runs against a real server, set `GLM_SFTP_TEST_URL` to a writable
+    /// directory such as `sftp://user@localhost/tmp/glm` and run with
+    /// `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_remote_round_trip() {
+        let url = std::env::var("GLM_SFTP_TEST_URL").expect("GLM_SFTP_TEST_URL is not set");
+        let location = SftpLocation::parse(&url).expect("invalid url");
+        let sut = SftpFs::connect(&location).expect("failed to connect");
+        let dir = sut.initial_dir().expect("failed to resolve directory");
+        let local = tempfile::tempdir().expect("failed to create tempdir");
+        std::fs::write(local.path().join("up.txt"), "uploaded").unwrap();
+
+        sut.upload(&local.path().join("up.txt"), &dir.join("up.txt"), |_| {})
+            .expect("failed to upload");
+        sut.rename(&dir.join("up.txt"), &dir.join("moved.txt"))
+            .expect("failed to rename");
+        sut.download(
+            &dir.join("moved.txt"),
+            &local.path().join("down.txt"),
+            |_| {},
+        )
+        .expect("failed to download");
+        sut.remove(&dir.join("moved.txt"))
+            .expect("failed to remove");
+
+        assert_eq!(
+            std::fs::read_to_string(local.path().join("down.txt")).unwrap(),
+            "uploaded"
+        );
+    }
+}
@@ -0,0 +1,526 @@
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::audit::{audited, AuditLog};
+use crate::dir_diff::{compare_dirs, CompareOptions};
+use crate::plan::PlannedOp;
+
+/// Options to customize how a directory is synced into another.
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Compare files with the same size by content hash instead of by
+    /// modification time
+    pub checksum: bool,
+    /// Delete items on the destination that don't exist on the source
+    pub delete_extraneous: bool,
+    /// Glob patterns of items to leave untouched on both sides. Patterns
+    /// are matched against paths relative to the synced roots, and
+    /// excluding a directory excludes everything inside of it
+    pub exclude: Vec<String>,
+}
+
+/// A single step of a sync. Every path is relative to the synced roots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncAction {
+    /// Copy an item that doesn't exist on the destination. Directories are
+    /// only created, their contents have actions of their own
+    Copy(PathBuf),
+    /// Overwrite an item on the destination that differs from the source
+    Update(PathBuf),
+    /// Move an item that only exists on the destination to the trash
+    Delete(PathBuf),
+}
+
+impl SyncAction {
+    pub fn path(&self) -> &Path {
+        match self {
+            SyncAction::Copy(path) | SyncAction::Update(path) | SyncAction::Delete(path) => path,
+        }
+    }
+}
+
+/// Everything a sync would do, computed without touching the disk. Useful
+/// to be displayed as a dry run before being executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncPlan {
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    /// Actions in the order they are executed: parents are created before
+    /// their children, and deleted after them
+    pub actions: Vec<SyncAction>,
+}
+
+/// Progress of a running sync, reported after every action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub done: usize,
+    pub total: usize,
+    pub action: SyncAction,
+}
+
+/// Outcome of executing a `SyncPlan`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub completed: Vec<SyncAction>,
+    pub failed: Vec<(SyncAction, String)>,
+}
+
+/// Computes the plan to make `dst` a mirror of `src`.
+///
+/// A missing destination is treated as an empty directory, and will be
+/// created when the plan is executed.
+pub fn plan_sync<P, Q>(src: P, dst: Q, options: &SyncOptions) -> anyhow::Result<SyncPlan>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    let exclude = build_globset(&options.exclude)?;
+    let is_excluded = |path: &Path| path.ancestors().any(|p| exclude.is_match(p));
+
+    let (only_src, only_dst, differing) = match dst.exists() {
+        true => {
+            let compare = CompareOptions {
+                compare_contents: options.checksum,
+            };
+            let diff = compare_dirs(src, dst, &compare)?;
+            (diff.only_left, diff.only_right, diff.differing)
+        }
+        false => (list_dir(src, Path::new(""))?, vec![], vec![]),
+    };
+
+    let mut copies = vec![];
+    for path in only_src {
+        copies.extend(walk(src, &path)?);
+    }
+    copies.retain(|path| !is_excluded(path));
+    copies.sort();
+
+    let mut deletes = vec![];
+    if options.delete_extraneous {
+        for path in only_dst {
+            deletes.extend(walk(dst, &path)?);
+        }
+        let excluded = deletes
+            .iter()
+            .filter(|path| is_excluded(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        // directories with excluded items inside can't be deleted, as that
+        // would delete the excluded items as well
+        deletes.retain(|path| !excluded.iter().any(|excluded| excluded.starts_with(path)));
+        deletes.sort_by(|a, b| b.cmp(a));
+    }
+
+    let mut updates = vec![];
+    let mut retyped = vec![];
+    for path in differing {
+        if is_excluded(&path) {
+            continue;
+        }
+        // an item that changed type is replaced as a whole, which must
+        // neither drop excluded items from the destination nor copy
+        // excluded ones from the source
+        let dst_is_dir = std::fs::symlink_metadata(dst.join(&path))?.is_dir();
+        if dst_is_dir && walk(dst, &path)?.iter().any(|path| is_excluded(path)) {
+            continue;
+        }
+        if std::fs::symlink_metadata(src.join(&path))?.is_dir() {
+            let contents = walk(src, &path)?;
+            retyped.extend(
+                contents
+                    .into_iter()
+                    .filter(|inner| inner != &path && !is_excluded(inner)),
+            );
+        }
+        updates.push(path);
+    }
+    retyped.sort();
+
+    let actions = copies
+        .into_iter()
+        .map(SyncAction::Copy)
+        .chain(updates.into_iter().map(SyncAction::Update))
+        .chain(retyped.into_iter().map(SyncAction::Copy))
+        .chain(deletes.into_iter().map(SyncAction::Delete))
+        .collect();
+
+    Ok(SyncPlan {
+        src: src.to_path_buf(),
+        dst: dst.to_path_buf(),
+        actions,
+    })
+}
+
+/// Plans and executes a sync of `src` into `dst` in one go. See
+/// [`plan_sync`] and [`SyncPlan::execute`].
+pub fn sync<P, Q>(src: P, dst: Q, options: &SyncOptions) -> anyhow::Result<SyncReport>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let plan = plan_sync(src, dst, options)?;
+    Ok(plan.execute(|_| {}, None))
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Executes every action of the plan, calling `progress` after each one
+    /// and recording every change in `audit`, when there is one.
+    ///
+    /// A failing action doesn't stop the sync, every failure is reported
+    /// with its error on the returned `SyncReport`.
+    pub fn execute<F>(&self, progress: F, audit: Option<&AuditLog>) -> SyncReport
+    where
+        F: FnMut(SyncProgress),
+    {
+        self.execute_with(progress, |path| Ok(trash::delete(path)?), audit)
+    }
+
+    /// Same as [`SyncPlan::execute`], with `trash` called to get rid of
+    /// every item that is deleted or replaced on the destination.
+    pub fn execute_with<F, T>(
+        &self,
+        mut progress: F,
+        mut trash: T,
+        audit: Option<&AuditLog>,
+    ) -> SyncReport
+    where
+        F: FnMut(SyncProgress),
+        T: FnMut(&Path) -> anyhow::Result<()>,
+    {
+        let mut report = SyncReport::default();
+        if let Err(e) = std::fs::create_dir_all(&self.dst) {
+            for action in self.actions.iter() {
+                report.failed.push((action.clone(), e.to_string()));
+            }
+            return report;
+        }
+
+        let mut trash = |path: &Path| {
+            let op = PlannedOp::Delete {
+                path: path.to_path_buf(),
+            };
+            audited(audit, &op, trash(path))
+        };
+        for (index, action) in self.actions.iter().enumerate() {
+            match self.execute_action(action, &mut trash, audit) {
+                Ok(_) => report.completed.push(action.clone()),
+                Err(e) => report.failed.push((action.clone(), e.to_string())),
+            }
+            progress(SyncProgress {
+                done: index + 1,
+                total: self.actions.len(),
+                action: action.clone(),
+            });
+        }
+        report
+    }
+
+    fn execute_action<T>(
+        &self,
+        action: &SyncAction,
+        trash: &mut T,
+        audit: Option<&AuditLog>,
+    ) -> anyhow::Result<()>
+    where
+        T: FnMut(&Path) -> anyhow::Result<()>,
+    {
+        self.check_parents(action.path())?;
+        let src = self.src.join(action.path());
+        let dst = self.dst.join(action.path());
+        if let SyncAction::Delete(_) = action {
+            return trash(&dst);
+        }
+        let src_is_dir = std::fs::symlink_metadata(&src)?.is_dir();
+        if let SyncAction::Update(_) = action {
+            // the item may have changed type, e.g. a file that became a
+            // directory, so the old one is trashed first. A link is
+            // replaced rather than written through
+            let dst_metadata = std::fs::symlink_metadata(&dst)?;
+            if src_is_dir || dst_metadata.is_dir() {
+                trash(&dst)?;
+            } else if dst_metadata.is_symlink() {
+                std::fs::remove_file(&dst)?;
+            }
+        }
+        // directories are only created, their contents have actions of
+        // their own
+        let (op, result) = match src_is_dir {
+            true => (
+                PlannedOp::CreateDir { path: dst.clone() },
+                std::fs::create_dir_all(&dst).map_err(anyhow::Error::from),
+            ),
+            false => (
+                PlannedOp::Copy {
+                    from: src.clone(),
+                    to: dst.clone(),
+                },
+                crate::ops::copy(&src, &dst),
+            ),
+        };
+        audited(audit, &op, result)
+    }
+
+    /// Fails when a parent of `path` on the destination is a symbolic link,
+    /// as acting through it would change items outside of the destination.
+    fn check_parents(&self, path: &Path) -> anyhow::Result<()> {
+        let parents = path.ancestors().skip(1);
+        for parent in parents.take_while(|parent| !parent.as_os_str().is_empty()) {
+            let dst = self.dst.join(parent);
+            if std::fs::symlink_metadata(&dst).is_ok_and(|metadata| metadata.is_symlink()) {
+                anyhow::bail!(
+                    "{} is a symbolic link, not syncing through it",
+                    dst.display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn build_globset(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Lists the direct children of `root/relative`, relative to `root`.
+fn list_dir(root: &Path, relative: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    std::fs::read_dir(root.join(relative))?
+        .map(|entry| Ok(relative.join(entry?.file_name())))
+        .collect()
+}
+
+/// Returns `relative` and, when it is a directory, everything inside of
+/// it, recursively. Paths are relative to `root`.
+fn walk(root: &Path, relative: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    let mut pending = vec![relative.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        if std::fs::symlink_metadata(root.join(&path))?.is_dir() {
+            pending.extend(list_dir(root, &path)?);
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_tempdir() -> TempDir {
+        let dir = tempdir().expect("failed to create tempdir");
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        fs::create_dir_all(src.join("new_dir/nested")).unwrap();
+        fs::create_dir_all(dst.join("extra_dir/target")).unwrap();
+        fs::write(src.join("new_dir/nested/file.txt"), "new").unwrap();
+        fs::write(src.join("changed.txt"), "new contents").unwrap();
+        fs::write(dst.join("changed.txt"), "old").unwrap();
+        fs::write(dst.join("extra.txt"), "extra").unwrap();
+        fs::write(dst.join("extra_dir/target/out.o"), "out").unwrap();
+        fs::write(src.join("skip.log"), "log").unwrap();
+        dir
+    }
+
+    fn fake_trash(bin: &Path) -> impl FnMut(&Path) -> anyhow::Result<()> + '_ {
+        move |path| Ok(fs::rename(path, bin.join(path.file_name().unwrap()))?)
+    }
+
+    fn options() -> SyncOptions {
+        SyncOptions {
+            checksum: false,
+            delete_extraneous: true,
+            exclude: vec!["*.log".into(), "**/target".into()],
+        }
+    }
+
+    #[test]
+    fn test_plan_sync() {
+        let dir = setup_tempdir();
+
+        let plan = plan_sync(dir.path().join("src"), dir.path().join("dst"), &options())
+            .expect("failed to plan");
+
+        assert_eq!(
+            plan.actions,
+            vec![
+                SyncAction::Copy("new_dir".into()),
+                SyncAction::Copy("new_dir/nested".into()),
+                SyncAction::Copy("new_dir/nested/file.txt".into()),
+                SyncAction::Update("changed.txt".into()),
+                SyncAction::Delete("extra.txt".into()),
+            ]
+        );
+        // planning should never touch the disk
+        assert!(!dir.path().join("dst/new_dir").exists());
+    }
+
+    #[test]
+    fn test_plan_sync_keeping_extras() {
+        let dir = setup_tempdir();
+        let options = SyncOptions {
+            delete_extraneous: false,
+            ..options()
+        };
+
+        let plan = plan_sync(dir.path().join("src"), dir.path().join("dst"), &options)
+            .expect("failed to plan");
+
+        assert!(plan
+            .actions
+            .iter()
+            .all(|action| !matches!(action, SyncAction::Delete(_))));
+    }
+
+    #[test]
+    fn test_execute_plan() {
+        let dir = setup_tempdir();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        let plan = plan_sync(&src, &dst, &options()).expect("failed to plan");
+        let bin = tempdir().expect("failed to create tempdir");
+        let mut reported = vec![];
+
+        let audit = AuditLog::new(bin.path().join("state"));
+
+        let report = plan.execute_with(
+            |progress| reported.push(progress.done),
+            fake_trash(bin.path()),
+            Some(&audit),
+        );
+
+        assert!(report.failed.is_empty());
+        assert_eq!(report.completed.len(), 5);
+        assert_eq!(reported, vec![1, 2, 3, 4, 5]);
+        assert_eq!(
+            fs::read_to_string(dst.join("new_dir/nested/file.txt")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(dst.join("changed.txt")).unwrap(),
+            "new contents"
+        );
+        assert!(!dst.join("extra.txt").exists());
+        assert!(bin.path().join("extra.txt").exists());
+        assert!(!dst.join("skip.log").exists());
+        assert!(dst.join("extra_dir/target/out.o").exists());
+        let log = fs::read_to_string(audit.path()).expect("failed to read audit log");
+        let ops = log
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["op"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ops, ["mkdir", "mkdir", "copy", "copy", "trash"]);
+
+        let plan = plan_sync(&src, &dst, &options()).expect("failed to plan");
+        assert!(plan.is_empty());
+    }
+
+    #[test]
+    fn test_sync_into_missing_destination() {
+        let dir = setup_tempdir();
+        let dst = dir.path().join("missing");
+
+        let report = sync(dir.path().join("src"), &dst, &options()).expect("failed to sync");
+
+        assert!(report.failed.is_empty());
+        assert!(dst.join("new_dir/nested/file.txt").exists());
+        assert!(dst.join("changed.txt").exists());
+    }
+
+    #[test]
+    fn test_execute_reports_failures() {
+        let dir = setup_tempdir();
+        let plan = SyncPlan {
+            src: dir.path().join("src"),
+            dst: dir.path().join("dst"),
+            actions: vec![
+                SyncAction::Copy("missing.txt".into()),
+                SyncAction::Update("changed.txt".into()),
+            ],
+        };
+
+        let report = plan.execute(|_| {}, None);
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, SyncAction::Copy("missing.txt".into()));
+        assert_eq!(
+            report.completed,
+            vec![SyncAction::Update("changed.txt".into())]
+        );
+    }
+
+    #[test]
+    fn test_execute_replaces_symlinks() {
+        let dir = setup_tempdir();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        fs::write(dir.path().join("outside.txt"), "outside").unwrap();
+        fs::remove_file(dst.join("changed.txt")).unwrap();
+        std::os::unix::fs::symlink("../outside.txt", dst.join("changed.txt")).unwrap();
+        fs::create_dir(dir.path().join("outside")).unwrap();
+        std::os::unix::fs::symlink("../outside", dst.join("new_dir")).unwrap();
+        let plan = SyncPlan {
+            src: src.clone(),
+            dst: dst.clone(),
+            actions: vec![
+                SyncAction::Update("changed.txt".into()),
+                SyncAction::Copy("new_dir/nested".into()),
+            ],
+        };
+
+        let report = plan.execute_with(|_| {}, |_| anyhow::bail!("nothing to trash"), None);
+
+        assert_eq!(
+            report.completed,
+            vec![SyncAction::Update("changed.txt".into())]
+        );
+        assert_eq!(
+            report.failed[0].1,
+            format!(
+                "{} is a symbolic link, not syncing through it",
+                dst.join("new_dir").display()
+            )
+        );
+        let outside = fs::read_to_string(dir.path().join("outside.txt")).unwrap();
+        assert_eq!(outside, "outside");
+        let changed = fs::symlink_metadata(dst.join("changed.txt")).unwrap();
+        assert!(changed.is_file());
+        assert!(!dir.path().join("outside/nested").exists());
+    }
+
+    #[test]
+    fn test_sync_retyped_items_keeps_excluded_ones() {
+        let dir = setup_tempdir();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        fs::create_dir_all(dst.join("build/target")).unwrap();
+        fs::write(dst.join("build/target/out.o"), "out").unwrap();
+        fs::write(src.join("build"), "now a file").unwrap();
+        fs::create_dir_all(src.join("logs")).unwrap();
+        fs::write(src.join("logs/app.log"), "log").unwrap();
+        fs::write(src.join("logs/app.txt"), "text").unwrap();
+        fs::write(dst.join("logs"), "was a file").unwrap();
+        let bin = tempdir().expect("failed to create tempdir");
+
+        let plan = plan_sync(&src, &dst, &options()).expect("failed to plan");
+        let report = plan.execute_with(|_| {}, fake_trash(bin.path()), None);
+
+        assert!(plan.actions.contains(&SyncAction::Update("logs".into())));
+        assert!(plan
+            .actions
+            .contains(&SyncAction::Copy("logs/app.txt".into())));
+        assert!(!plan.actions.contains(&SyncAction::Update("build".into())));
+        assert!(report.failed.is_empty());
+        assert!(dst.join("build/target/out.o").exists());
+        assert!(dst.join("logs/app.txt").exists());
+        assert!(!dst.join("logs/app.log").exists());
+        assert!(bin.path().join("logs").is_file());
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+
+use crate::config::{config_dir, Config};
+
+/// Contents for new files, read from a directory of templates.
+///
+/// A template applies to new files with the same name, like `mod.rs` or
+/// `Makefile`, and `default.<ext>` applies to any new file ending in
+/// `.<ext>`. Templates can also be picked by name for any file.
+///
+/// Templates may use the `{{name}}`, `{{stem}}`, `{{date}}`, `{{year}}`
+/// and `{{author}}` variables, other text is copied as is.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    dir: PathBuf,
+    author: Option<String>,
+}
+
+impl Templates {
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            author: None,
+        }
+    }
+
+    /// Reads templates from `templates/` in the [`config_dir`], with the
+    /// author of `config`.
+    pub fn in_config_dir(config: &Config) -> Option<Self> {
+        let templates = Templates::new(config_dir()?.join("templates"));
+        Some(templates.with_author(config.author.clone()))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn with_author(mut self, author: Option<String>) -> Self {
+        self.author = author;
+        self
+    }
+
+    /// Names of every template, sorted. There are none when the directory
+    /// doesn't exist.
+    pub fn list(&self) -> anyhow::Result<Vec<String>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut names = vec![];
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the name of the template that applies to a new file at
+    /// `path`, if any.
+    pub fn find(&self, path: &Path) -> anyhow::Result<Option<String>> {
+        let names = self.list()?;
+        let file_name = path.file_name().map(|name| name.to_string_lossy());
+        if let Some(name) = file_name.filter(|name| names.iter().any(|n| n == name)) {
+            return Ok(Some(name.to_string()));
+        }
+        let Some(ext) = path.extension() else {
+            return Ok(None);
+        };
+        let default = format!("default.{}", ext.to_string_lossy());
+        Ok(names.into_iter().find(|name| *name == default))
+    }
+
+    /// Renders the template `name` for a new file at `path`.
+    pub fn render(&self, name: &str, path: &Path) -> anyhow::Result<String> {
+        let template_path = self.dir.join(name);
+        let template = std::fs::read_to_string(&template_path)
+            .map_err(|e| anyhow::anyhow!("failed to read template {name}: {e}"))?;
+        let now = Local::now();
+        let variables = [
+            ("name", file_name(path)),
+            (
+                "stem",
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("date", now.format("%Y-%m-%d").to_string()),
+            ("year", now.format("%Y").to_string()),
+            ("author", self.author.clone().unwrap_or_default()),
+        ];
+        Ok(substitute(&template, &variables))
+    }
+
+    /// Renders the template that applies to a new file at `path`, see
+    /// [`Templates::find`].
+    pub fn render_for(&self, path: &Path) -> anyhow::Result<Option<String>> {
+        match self.find(path)? {
+            Some(name) => self.render(&name, path).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Replaces every `{{variable}}` of `template` with its value, leaving
+/// unknown variables untouched.
+fn substitute(template: &str, variables: &[(&str, String)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let key = after[..end].trim();
+        match variables.iter().find(|(name, _)| *name == key) {
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_by_name_then_extension() {
+        let dir = tempdir().expect("failed to create tempdir");
+        fs::write(dir.path().join("mod.rs"), "//! {{stem}}\n").unwrap();
+        fs::write(dir.path().join("default.rs"), "// {{name}}\n").unwrap();
+        fs::write(dir.path().join("default.sh"), "#!/bin/bash\n").unwrap();
+        let sut = Templates::new(dir.path());
+
+        assert_eq!(sut.list().unwrap(), ["default.rs", "default.sh", "mod.rs"]);
+        let find = |path: &str| sut.find(Path::new(path)).unwrap();
+        assert_eq!(find("src/net/mod.rs").as_deref(), Some("mod.rs"));
+        assert_eq!(find("src/lib.rs").as_deref(), Some("default.rs"));
+        assert_eq!(find("README.md"), None);
+        assert_eq!(find("Makefile"), None);
+        assert!(Templates::new(dir.path().join("missing"))
+            .list()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let template = "# {{ stem }} by {{author}}, {{name}}\n{{unknown}} {{";
+        fs::write(dir.path().join("default.md"), template).unwrap();
+        let sut = Templates::new(dir.path()).with_author(Some("Ada".into()));
+
+        let rendered = sut.render_for(Path::new("/docs/notes.md")).unwrap();
+
+        assert_eq!(
+            rendered.as_deref(),
+            Some("# notes by Ada, notes.md\n{{unknown}} {{")
+        );
+        let dated = substitute("{{date}}", &[("date", "2024-01-31".into())]);
+        assert_eq!(dated, "2024-01-31");
+    }
+}
@@ -0,0 +1,203 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+
+/// Formats accepted for a date and time without an offset, in local time.
+const LOCAL_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// The time to give to items, see [`crate::FileManager::touch`]. Parsed
+/// from `now`, an ISO-8601 date like `2024-01-31` or `2024-01-31T12:00:00Z`,
+/// seconds since the epoch like `@1706702400`, or an offset from now like
+/// `-2d`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeSpec {
+    Now,
+    /// A fixed point in time
+    At(SystemTime),
+    /// Seconds from now, negative for the past
+    Offset(i64),
+    /// The times of another item, each time copied from the same one
+    Reference(PathBuf),
+}
+
+/// Which times of an item to change.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timestamps {
+    #[default]
+    Both,
+    Access,
+    Modification,
+}
+
+impl Timestamps {
+    pub fn access(&self) -> bool {
+        matches!(self, Timestamps::Both | Timestamps::Access)
+    }
+
+    pub fn modification(&self) -> bool {
+        matches!(self, Timestamps::Both | Timestamps::Modification)
+    }
+}
+
+impl TimeSpec {
+    /// The time given by the spec when it's taken at `now`, `None` for a
+    /// reference, which has to be read from its backend. Fails when an
+    /// offset goes past the times the system can represent.
+    pub fn at(&self, now: SystemTime) -> anyhow::Result<Option<SystemTime>> {
+        Ok(match self {
+            TimeSpec::Now => Some(now),
+            TimeSpec::At(time) => Some(*time),
+            TimeSpec::Offset(seconds) => {
+                let offset = Duration::from_secs(seconds.unsigned_abs());
+                let time = match *seconds >= 0 {
+                    true => now.checked_add(offset),
+                    false => now.checked_sub(offset),
+                };
+                let time = time.ok_or_else(|| {
+                    anyhow::anyhow!("offset of {seconds} seconds is out of range")
+                })?;
+                Some(time)
+            }
+            TimeSpec::Reference(_) => None,
+        })
+    }
+}
+
+impl FromStr for TimeSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("now") {
+            return Ok(TimeSpec::Now);
+        }
+        if let Some(seconds) = s.strip_prefix('@') {
+            let seconds = seconds
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("invalid time {s}, expected seconds after @"))?;
+            let time = UNIX_EPOCH
+                .checked_add(Duration::from_secs(seconds))
+                .ok_or_else(|| anyhow::anyhow!("time {s} is out of range"))?;
+            return Ok(TimeSpec::At(time));
+        }
+        if s.starts_with(['+', '-']) {
+            return parse_offset(s).map(TimeSpec::Offset);
+        }
+        parse_date(s).map(TimeSpec::At).ok_or_else(|| {
+            anyhow::anyhow!(
+                "invalid time {s}, use now, a date like 2024-01-31T12:00:00Z, \
+                 @<seconds> or an offset like -2d"
+            )
+        })
+    }
+}
+
+/// Parses `-2d` or `+90m` into seconds, with units s, m, h, d and w.
+fn parse_offset(s: &str) -> anyhow::Result<i64> {
+    let error = || anyhow::anyhow!("invalid offset {s}, expected a number and s, m, h, d or w");
+    let (sign, rest) = s.split_at(1);
+    let unit = rest.chars().last().ok_or_else(error)?;
+    let scale = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(error()),
+    };
+    let amount = rest[..rest.len() - 1].parse::<i64>().map_err(|_| error())?;
+    let seconds = amount.checked_mul(scale).ok_or_else(error)?;
+    Ok(match sign {
+        "-" => -seconds,
+        _ => seconds,
+    })
+}
+
+/// Parses an ISO-8601 date, with an offset or in local time.
+fn parse_date(s: &str) -> Option<SystemTime> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(time.into());
+    }
+    let naive = LOCAL_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(SystemTime::from)
+}
+
+/// Formats `time` as ISO-8601 in UTC, like `2024-01-31T12:00:00Z`.
+pub(crate) fn format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> TimeSpec {
+        s.parse().expect("failed to parse time")
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("now"), TimeSpec::Now);
+        assert_eq!(parse("-2d"), TimeSpec::Offset(-2 * 24 * 60 * 60));
+        assert_eq!(parse("+90m"), TimeSpec::Offset(90 * 60));
+        assert_eq!(
+            parse("@1706702400"),
+            TimeSpec::At(UNIX_EPOCH + Duration::from_secs(1706702400))
+        );
+        assert_eq!(parse("2024-01-31T12:00:00Z"), parse("@1706702400"));
+        assert_eq!(parse("2024-01-31T14:00:00+02:00"), parse("@1706702400"));
+
+        let local = Local
+            .with_ymd_and_hms(2024, 1, 31, 0, 0, 0)
+            .earliest()
+            .expect("midnight exists");
+        assert_eq!(parse("2024-01-31"), TimeSpec::At(local.into()));
+        assert!(matches!(parse("2024-01-31 08:30"), TimeSpec::At(_)));
+
+        let error = "-2y".parse::<TimeSpec>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid offset -2y, expected a number and s, m, h, d or w"
+        );
+        assert!("yesterday".parse::<TimeSpec>().is_err());
+        let error = "@18446744073709551615".parse::<TimeSpec>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "time @18446744073709551615 is out of range"
+        );
+    }
+
+    #[test]
+    fn test_at() {
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        assert_eq!(
+            TimeSpec::Offset(-100).at(now).unwrap(),
+            Some(UNIX_EPOCH + Duration::from_secs(900))
+        );
+        assert_eq!(TimeSpec::Now.at(now).unwrap(), Some(now));
+        assert_eq!(TimeSpec::Reference("/a".into()).at(now).unwrap(), None);
+        let error = TimeSpec::Offset(i64::MAX).at(now).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("offset of {} seconds is out of range", i64::MAX)
+        );
+        assert_eq!(format_time(now), "1970-01-01T00:16:40Z");
+    }
+}
@@ -0,0 +1,887 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use filetime::FileTime;
+
+use crate::file_manager::FileType;
+
+/// Metadata of an item, as reported by a [`Vfs`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub file_type: FileType,
+    /// Size in bytes, for symbolic links this is the size of the link
+    /// itself unless it was followed
+    pub len: u64,
+    pub accessed: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    /// Permission bits, like `0o755`, when the backend has them
+    pub mode: Option<u32>,
+    /// Numeric ids of the owning user and group, when the backend has them
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+/// A file system backend, every file manager operation that touches the
+/// file system goes through one.
+///
+/// Paths are always absolute. Implementations only need to provide the
+/// primitive operations, anything built on top of them lives on
+/// [`crate::FileManager`].
+pub trait Vfs: Debug {
+    /// Returns the absolute path of every item directly inside of `path`.
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>>;
+
+    /// Returns the metadata of `path`, following symbolic links.
+    fn stat(&self, path: &Path) -> anyhow::Result<Metadata>;
+
+    /// Returns the metadata of `path` itself, without following symbolic
+    /// links.
+    fn lstat(&self, path: &Path) -> anyhow::Result<Metadata>;
+
+    /// Reads the whole contents of a file.
+    fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>>;
+
+    /// Writes `contents` to a file, creating it when it doesn't exist and
+    /// truncating it when it does.
+    fn write(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()>;
+
+    /// Creates a file with `contents`, failing when `path` already exists.
+    fn create_new(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        if self.lstat(path).is_ok() {
+            anyhow::bail!("{} already exists", path.display());
+        }
+        self.write(path, contents)
+    }
+
+    /// Renames `from` to `to`, replacing `to` when it is a file.
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()>;
+
+    /// Removes a file, a symbolic link or an empty directory.
+    fn remove(&self, path: &Path) -> anyhow::Result<()>;
+
+    /// Creates a directory, its parent must already exist.
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()>;
+
+    /// Sets the permission bits of `path`, following symbolic links.
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "cannot set the mode of {} to {mode:o}, permissions are not supported here",
+            path.display()
+        )
+    }
+
+    /// Creates a symbolic link at `link` pointing to `target`, which is
+    /// stored as given and may be relative to the directory of the link.
+    fn symlink(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "cannot link {} to {}, symbolic links are not supported here",
+            link.display(),
+            target.display()
+        )
+    }
+
+    /// Returns the target of the symbolic link at `path`, as stored.
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        anyhow::bail!(
+            "cannot read the link {}, symbolic links are not supported here",
+            path.display()
+        )
+    }
+
+    /// Creates a hard link at `link` to the file at `target`.
+    fn hard_link(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "cannot link {} to {}, hard links are not supported here",
+            link.display(),
+            target.display()
+        )
+    }
+
+    /// Returns the absolute path of `path` with every symbolic link
+    /// resolved, which must exist.
+    fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf>;
+
+    /// Sets the owning user and group of `path`, following symbolic links.
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "cannot set the owner of {} to {uid}:{gid}, ownership is not supported here",
+            path.display()
+        )
+    }
+
+    /// Sets the access and modification times of `path`, following
+    /// symbolic links. `None` keeps the current time.
+    fn set_times(
+        &self,
+        path: &Path,
+        _accessed: Option<SystemTime>,
+        _modified: Option<SystemTime>,
+    ) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "cannot set the times of {}, times can't be changed here",
+            path.display()
+        )
+    }
+
+    /// Moves `path` to the trash, when the backend has one.
+    fn trash(&self, path: &Path) -> anyhow::Result<()> {
+        anyhow::bail!("cannot trash {}, there is no trash here", path.display())
+    }
+
+    /// Whether paths of this backend refer to the local file system, so
+    /// they can be handed to tools that only work with local files, like
+    /// git.
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+impl<V> Vfs for Box<V>
+where
+    V: Vfs + ?Sized,
+{
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        (**self).list(path)
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        (**self).stat(path)
+    }
+
+    fn lstat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        (**self).lstat(path)
+    }
+
+    fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        (**self).create_new(path, contents)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        (**self).rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        (**self).remove(path)
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        (**self).mkdir(path)
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        (**self).set_mode(path, mode)
+    }
+
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> anyhow::Result<()> {
+        (**self).set_owner(path, uid, gid)
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        accessed: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    ) -> anyhow::Result<()> {
+        (**self).set_times(path, accessed, modified)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        (**self).symlink(target, link)
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        (**self).read_link(path)
+    }
+
+    fn hard_link(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        (**self).hard_link(target, link)
+    }
+
+    fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        (**self).canonicalize(path)
+    }
+
+    fn trash(&self, path: &Path) -> anyhow::Result<()> {
+        (**self).trash(path)
+    }
+
+    fn is_local(&self) -> bool {
+        (**self).is_local()
+    }
+}
+
+/// The local file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl From<std::fs::Metadata> for Metadata {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        let file_type = match metadata.file_type() {
+            t if t.is_symlink() => FileType::Symlink,
+            t if t.is_dir() => FileType::Directory,
+            _ => FileType::File,
+        };
+        Metadata {
+            file_type,
+            len: metadata.len(),
+            accessed: metadata.accessed().ok(),
+            modified: metadata.modified().ok(),
+            mode: unix_mode(&metadata),
+            uid: unix_owner(&metadata).map(|(uid, _)| uid),
+            gid: unix_owner(&metadata).map(|(_, gid)| gid),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn unix_owner(metadata: &std::fs::Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn unix_owner(_metadata: &std::fs::Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+impl Vfs for LocalFs {
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect()
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        Ok(std::fs::metadata(path)?.into())
+    }
+
+    fn lstat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        Ok(std::fs::symlink_metadata(path)?.into())
+    }
+
+    fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        Ok(std::fs::read(path)?)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, contents)?)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    anyhow::anyhow!("{} already exists", path.display())
+                }
+                _ => e.into(),
+            })?;
+        Ok(file.write_all(contents)?)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        Ok(std::fs::rename(from, to)?)
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        match std::fs::symlink_metadata(path)?.is_dir() {
+            true => std::fs::remove_dir(path)?,
+            false => std::fs::remove_file(path)?,
+        }
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(std::fs::create_dir(path)?)
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(std::fs::set_permissions(
+            path,
+            std::fs::Permissions::from_mode(mode),
+        )?)
+    }
+
+    #[cfg(unix)]
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> anyhow::Result<()> {
+        match std::os::unix::fs::chown(path, Some(uid), Some(gid)) {
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => anyhow::bail!(
+                "not allowed to change the owner of {} to {uid}:{gid}, only root can give \
+                 items away, and other users can only pick groups they belong to",
+                path.display()
+            ),
+            result => Ok(result?),
+        }
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        accessed: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    ) -> anyhow::Result<()> {
+        let time = FileTime::from_system_time;
+        match (accessed, modified) {
+            (Some(accessed), Some(modified)) => {
+                filetime::set_file_times(path, time(accessed), time(modified))?
+            }
+            (Some(accessed), None) => filetime::set_file_atime(path, time(accessed))?,
+            (None, Some(modified)) => filetime::set_file_mtime(path, time(modified))?,
+            (None, None) => (),
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn symlink(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        Ok(std::os::unix::fs::symlink(target, link)?)
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(std::fs::read_link(path)?)
+    }
+
+    fn hard_link(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        match std::fs::hard_link(target, link) {
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => anyhow::bail!(
+                "cannot hard link {} to {}, they are on different file systems, use a \
+                 symbolic link instead",
+                link.display(),
+                target.display()
+            ),
+            result => Ok(result?),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(std::fs::canonicalize(path)?)
+    }
+
+    fn trash(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(trash::delete(path)?)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    File(Vec<u8>),
+    Dir,
+    Symlink(PathBuf),
+}
+
+impl Node {
+    /// Mode of new nodes, as if created with the usual umask of 022.
+    fn default_mode(&self) -> u32 {
+        match self {
+            Node::File(_) => 0o644,
+            Node::Dir => 0o755,
+            Node::Symlink(_) => 0o777,
+        }
+    }
+}
+
+/// A node of [`MemFs`] along with its metadata.
+#[derive(Debug, Clone)]
+struct Entry {
+    node: Node,
+    accessed: SystemTime,
+    modified: SystemTime,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+}
+
+impl Entry {
+    fn new(node: Node) -> Self {
+        Entry {
+            mode: node.default_mode(),
+            node,
+            accessed: SystemTime::now(),
+            modified: SystemTime::now(),
+            uid: MemFs::UID,
+            gid: MemFs::GID,
+        }
+    }
+}
+
+/// A file system that only lives in memory, starting with an empty root
+/// directory. Useful to test anything built on top of a [`Vfs`] without
+/// touching the disk.
+#[derive(Debug)]
+pub struct MemFs {
+    nodes: Mutex<BTreeMap<PathBuf, Entry>>,
+    /// Directory trashed items are moved into, there is no trash without
+    trash: Option<PathBuf>,
+}
+
+impl Default for MemFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolves `.` and `..` components without touching any file system.
+fn normalize(path: &Path) -> anyhow::Result<PathBuf> {
+    if !path.is_absolute() {
+        anyhow::bail!("{} is not an absolute path", path.display());
+    }
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => _ = normalized.pop(),
+            Component::Normal(part) => normalized.push(part),
+            _ => (),
+        }
+    }
+    Ok(normalized)
+}
+
+fn not_found(path: &Path) -> anyhow::Error {
+    anyhow::anyhow!("{}: no such file or directory", path.display())
+}
+
+impl MemFs {
+    /// Owner of every new node
+    pub const UID: u32 = 1000;
+    pub const GID: u32 = 1000;
+
+    pub fn new() -> Self {
+        let root = (PathBuf::from("/"), Entry::new(Node::Dir));
+        MemFs {
+            nodes: Mutex::new(BTreeMap::from([root])),
+            trash: None,
+        }
+    }
+
+    /// Creates the directory `path`, and moves trashed items into it.
+    pub fn with_trash<P>(self, path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut fs = self.with_dir(path.as_ref())?;
+        fs.trash = Some(normalize(path.as_ref())?);
+        Ok(fs)
+    }
+
+    /// Creates a directory with every missing parent, and returns `self`
+    /// so a tree can be set up in a single expression.
+    pub fn with_dir<P>(self, path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = normalize(path.as_ref())?;
+        for ancestor in path.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            if self.lstat(ancestor).is_err() {
+                self.mkdir(ancestor)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Writes a file, creating every missing parent directory.
+    pub fn with_file<P, C>(self, path: P, contents: C) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+        C: AsRef<[u8]>,
+    {
+        let path = normalize(path.as_ref())?;
+        let fs = match path.parent() {
+            Some(parent) => self.with_dir(parent)?,
+            None => self,
+        };
+        fs.write(&path, contents.as_ref())?;
+        Ok(fs)
+    }
+
+    /// Creates a symbolic link at `path` pointing to `target`.
+    pub fn with_symlink<P, T>(self, path: P, target: T) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+        T: AsRef<Path>,
+    {
+        self.symlink(target.as_ref(), path.as_ref())?;
+        Ok(self)
+    }
+
+    /// Adds a node, failing when it already exists or when its parent is
+    /// not a directory.
+    fn insert(&self, path: &Path, node: Node) -> anyhow::Result<()> {
+        let path = self.locate(path)?;
+        let parent = path.parent().ok_or_else(|| not_found(&path))?.to_path_buf();
+        let mut nodes = self.nodes.lock().expect("memory file system is poisoned");
+        if nodes.contains_key(&path) {
+            anyhow::bail!("{} already exists", path.display());
+        }
+        match nodes.get(&parent) {
+            Some(Entry {
+                node: Node::Dir, ..
+            }) => (),
+            Some(_) => anyhow::bail!("{} is not a directory", parent.display()),
+            None => return Err(not_found(&parent)),
+        }
+        nodes.insert(path, Entry::new(node));
+        Ok(())
+    }
+
+    fn node(&self, path: &Path) -> anyhow::Result<Entry> {
+        let nodes = self.nodes.lock().expect("memory file system is poisoned");
+        nodes.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+
+    /// Follows every symbolic link on the way to `path`, and `path` itself
+    /// when it's a link, until a node that isn't a link is found.
+    fn resolve(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        self.resolve_nested(path, 0)
+    }
+
+    fn resolve_nested(&self, path: &Path, depth: usize) -> anyhow::Result<PathBuf> {
+        let mut resolved = PathBuf::from("/");
+        for component in normalize(path)?.components().skip(1) {
+            resolved.push(component);
+            if let Node::Symlink(target) = self.node(&resolved)?.node {
+                // same limit as linux, to stop on cyclic links
+                if depth >= 40 {
+                    anyhow::bail!("{}: too many levels of symbolic links", path.display());
+                }
+                let parent = resolved.parent().unwrap_or(Path::new("/"));
+                resolved = self.resolve_nested(&parent.join(target), depth + 1)?;
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Follows the symbolic links on the way to `path`, but not `path`
+    /// itself, which may not exist.
+    fn locate(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let path = normalize(path)?;
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => Ok(self.resolve(parent)?.join(name)),
+            _ => Ok(path),
+        }
+    }
+}
+
+impl Vfs for MemFs {
+    fn list(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let path = self.resolve(path)?;
+        if !matches!(self.node(&path)?.node, Node::Dir) {
+            anyhow::bail!("{} is not a directory", path.display());
+        }
+        let nodes = self.nodes.lock().expect("memory file system is poisoned");
+        Ok(nodes
+            .keys()
+            .filter(|child| child.parent() == Some(path.as_path()))
+            .cloned()
+            .collect())
+    }
+
+    fn stat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        self.lstat(&self.resolve(path)?)
+    }
+
+    fn lstat(&self, path: &Path) -> anyhow::Result<Metadata> {
+        let entry = self.node(&self.locate(path)?)?;
+        let (file_type, len) = match entry.node {
+            Node::File(contents) => (FileType::File, contents.len() as u64),
+            Node::Dir => (FileType::Directory, 0),
+            Node::Symlink(target) => (FileType::Symlink, target.as_os_str().len() as u64),
+        };
+        Ok(Metadata {
+            file_type,
+            len,
+            accessed: Some(entry.accessed),
+            modified: Some(entry.modified),
+            mode: Some(entry.mode),
+            uid: Some(entry.uid),
+            gid: Some(entry.gid),
+        })
+    }
+
+    fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        let path = self.resolve(path)?;
+        match self.node(&path)?.node {
+            Node::File(contents) => Ok(contents),
+            _ => anyhow::bail!("{} is not a file", path.display()),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        let path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(_) => self.locate(path)?,
+        };
+        match self.node(&path) {
+            Ok(Entry {
+                node: Node::File(_),
+                ..
+            }) => {
+                let mut nodes = self.nodes.lock().expect("memory file system is poisoned");
+                let entry = nodes.get_mut(&path).ok_or_else(|| not_found(&path))?;
+                entry.node = Node::File(contents.to_vec());
+                entry.modified = SystemTime::now();
+                Ok(())
+            }
+            Ok(_) => anyhow::bail!("{} is not a file", path.display()),
+            Err(_) => self.insert(&path, Node::File(contents.to_vec())),
+        }
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.insert(path, Node::File(contents.to_vec()))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        let (from, to) = (self.locate(from)?, self.locate(to)?);
+        let node = self.node(&from)?.node;
+        if from == to {
+            return Ok(());
+        }
+        if to.starts_with(&from) && to != from {
+            anyhow::bail!("cannot move {} into itself", from.display());
+        }
+        match self.node(&to).map(|entry| entry.node) {
+            Ok(Node::Dir) => anyhow::bail!("{} already exists", to.display()),
+            Ok(_) if matches!(node, Node::Dir) => {
+                anyhow::bail!("{} is not a directory", to.display())
+            }
+            Ok(_) => self.remove(&to)?,
+            Err(_) => (),
+        }
+        self.insert(&to, node)?;
+
+        let mut nodes = self.nodes.lock().expect("memory file system is poisoned");
+        let moved = nodes
+            .keys()
+            .filter(|path| path.starts_with(&from))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in moved {
+            // the moved nodes keep their modification time, mode and owner
+            let value = nodes.remove(&path).expect("node was just listed");
+            let target = match path.strip_prefix(&from).expect("node is inside of from") {
+                relative if relative.as_os_str().is_empty() => to.clone(),
+                relative => to.join(relative),
+            };
+            nodes.insert(target, value);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        let path = self.locate(path)?;
+        if path == Path::new("/") {
+            anyhow::bail!("cannot remove the root directory");
+        }
+        self.node(&path)?;
+        let mut nodes = self.nodes.lock().expect("memory file system is poisoned");
+        if nodes
+            .keys()
+            .any(|child| child.parent() == Some(path.as_path()))
+        {
+            anyhow::bail!("{} is not empty", path.display());
+        }
+        nodes.remove(&path);
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path) -> anyhow::Result<()> {
+        self.insert(path, Node::Dir)
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> anyhow::Result<()> {
+        let path = self.resolve(path)?;
+        let mut nodes = self.nodes.lock().expect("memory file system is poisoned");
+        let entry = nodes.get_mut(&path).ok_or_else(|| not_found(&path))?;
+        entry.mode = mode & 0o7777;
+        Ok(())
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        self.insert(link, Node::Symlink(target.to_path_buf()))
+    }
+
+    fn read_link(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let path = self.locate(path)?;
+        match self.node(&path)?.node {
+            Node::Symlink(target) => Ok(target),
+            _ => anyhow::bail!("{} is not a symbolic link", path.display()),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        self.resolve(path)
+    }
+
+    fn set_owner(&self, path: &Path, uid: u32, gid: u32) -> anyhow::Result<()> {
+        let path = self.resolve(path)?;
+        let mut nodes = self.nodes.lock().expect("memory file system is poisoned");
+        let entry = nodes.get_mut(&path).ok_or_else(|| not_found(&path))?;
+        (entry.uid, entry.gid) = (uid, gid);
+        Ok(())
+    }
+
+    fn trash(&self, path: &Path) -> anyhow::Result<()> {
+        let Some(trash) = &self.trash else {
+            anyhow::bail!("cannot trash {}, there is no trash here", path.display());
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        // like a real trash, items with the same name are all kept
+        let target = (0..)
+            .map(|n| match n {
+                0 => trash.join(&*name),
+                n => trash.join(format!("{name}.{n}")),
+            })
+            .find(|target| self.lstat(target).is_err())
+            .expect("there is always a free name");
+        self.rename(path, &target)
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        accessed: Option<SystemTime>,
+        modified: Option<SystemTime>,
+    ) -> anyhow::Result<()> {
+        let path = self.resolve(path)?;
+        let mut nodes = self.nodes.lock().expect("memory file system is poisoned");
+        let entry = nodes.get_mut(&path).ok_or_else(|| not_found(&path))?;
+        entry.accessed = accessed.unwrap_or(entry.accessed);
+        entry.modified = modified.unwrap_or(entry.modified);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn make_sut() -> MemFs {
+        MemFs::new()
+            .with_file("/home/user/notes.txt", "notes")
+            .and_then(|fs| fs.with_dir("/home/user/projects/glm"))
+            .and_then(|fs| fs.with_symlink("/home/user/latest", "projects/glm"))
+            .expect("failed to set up memory file system")
+    }
+
+    #[test]
+    fn test_list_and_stat() {
+        let sut = make_sut();
+
+        let items = sut.list(Path::new("/home/user")).expect("failed to list");
+
+        assert_eq!(
+            items,
+            vec![
+                PathBuf::from("/home/user/latest"),
+                PathBuf::from("/home/user/notes.txt"),
+                PathBuf::from("/home/user/projects"),
+            ]
+        );
+        let link = Path::new("/home/user/latest");
+        assert_eq!(sut.lstat(link).unwrap().file_type, FileType::Symlink);
+        assert_eq!(sut.stat(link).unwrap().file_type, FileType::Directory);
+        assert_eq!(sut.stat(Path::new("/home/user/notes.txt")).unwrap().len, 5);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let sut = make_sut();
+        let path = Path::new("/home/user/notes.txt");
+
+        sut.write(path, b"new notes").expect("failed to write");
+
+        assert_eq!(sut.read(path).unwrap(), b"new notes");
+        assert!(sut.write(Path::new("/missing/file"), b"").is_err());
+        assert!(sut.read(Path::new("/home")).is_err());
+    }
+
+    #[test]
+    fn test_rename_directory() {
+        let sut = make_sut();
+
+        sut.rename(Path::new("/home/user/projects"), Path::new("/home/work"))
+            .expect("failed to rename");
+
+        assert!(sut.lstat(Path::new("/home/work/glm")).is_ok());
+        assert!(sut.lstat(Path::new("/home/user/projects")).is_err());
+        assert!(sut
+            .rename(Path::new("/home"), Path::new("/home/user/home"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_remove() {
+        let sut = make_sut();
+
+        assert!(sut.remove(Path::new("/home/user/projects")).is_err());
+        sut.remove(Path::new("/home/user/projects/glm"))
+            .expect("failed to remove empty directory");
+        sut.remove(Path::new("/home/user/latest"))
+            .expect("failed to remove link");
+
+        assert!(sut.lstat(Path::new("/home/user/projects/glm")).is_err());
+        assert!(sut.trash(Path::new("/home/user/notes.txt")).is_err());
+
+        let sut = make_sut().with_trash("/trash").unwrap();
+        sut.trash(Path::new("/home/user/notes.txt")).unwrap();
+        sut.write(Path::new("/home/user/notes.txt"), b"again")
+            .unwrap();
+        sut.trash(Path::new("/home/user/notes.txt")).unwrap();
+        assert_eq!(sut.read(Path::new("/trash/notes.txt")).unwrap(), b"notes");
+        assert_eq!(sut.read(Path::new("/trash/notes.txt.1")).unwrap(), b"again");
+    }
+
+    #[test]
+    fn test_local_fs() {
+        let dir = tempdir().expect("failed to create tempdir");
+        let sut = LocalFs;
+        let path = dir.path().join("nested");
+
+        sut.mkdir(&path).expect("failed to create directory");
+        sut.write(&path.join("file.txt"), b"local").unwrap();
+
+        assert_eq!(sut.list(&path).unwrap(), vec![path.join("file.txt")]);
+        assert_eq!(sut.stat(&path).unwrap().file_type, FileType::Directory);
+        assert_eq!(sut.read(&path.join("file.txt")).unwrap(), b"local");
+
+        let epoch = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1706702400);
+        let before = sut.stat(&path.join("file.txt")).unwrap();
+        sut.set_times(&path.join("file.txt"), None, Some(epoch))
+            .unwrap();
+        let after = sut.stat(&path.join("file.txt")).unwrap();
+        assert_eq!(after.modified, Some(epoch));
+        assert_eq!(after.accessed, before.accessed);
+    }
+}
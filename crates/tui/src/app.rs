@@ -1,4 +1,14 @@
-use glm::{FileManager, FileType, FsOps};
+use std::collections::HashSet;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use glm::{
+    ArchiveKind, AsyncFileManager, AsyncFsOps, AuditLog, BulkEdit, Checksum, CompressOptions,
+    DiskUsageEvent, DiskUsageScanner, DuplicateGroup, FileType, HashAlgorithm, Item, JobEvent,
+    JobKind, JobStatus, LinkTarget, ListState, ModeChange, Owners, Plan, SftpFs, Templates, Vfs,
+};
 
 use crossterm::event::{KeyCode, KeyEvent};
 use ratatui::layout::{Constraint, Layout, Rect};
@@ -6,37 +16,1630 @@ use ratatui::style::Stylize;
 use ratatui::text::Line;
 use ratatui::widgets::{Block, Borders, Padding, Paragraph, Wrap};
 use ratatui::Frame;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
+use crate::components::checksums::ChecksumsComponent;
+use crate::components::confirm::ConfirmComponent;
+use crate::components::dir_diff::{CopyDirection, DirDiffComponent};
+use crate::components::duplicates::DuplicatesComponent;
 use crate::components::help::HelpComponent;
+use crate::components::jobs::JobsComponent;
 use crate::components::line_numbers::LineNumbersComponent;
-use crate::components::{file_list::FileListComponent, Component};
+use crate::components::permissions::PermissionsComponent;
+use crate::components::prompt::PromptComponent;
+use crate::components::rename::RenameComponent;
+use crate::components::sync::SyncComponent;
+use crate::components::{
+    file_list::{FileListComponent, ItemSize},
+    Component,
+};
+use crate::format::format_size;
 
 #[derive(Debug)]
 pub struct App {
     file_list: FileListComponent,
-    file_manager: FileManager,
+    file_manager: Files,
+    /// Directory of the last listing produced by the file manager
+    current_dir: PathBuf,
+    /// Archive file being browsed, when inside of one
+    archive_root: Option<PathBuf>,
+    is_local: bool,
+    listings: UnboundedSender<Listing>,
+    pending_listings: UnboundedReceiver<Listing>,
+    /// Number of file manager operations that haven't finished yet
+    loading: usize,
     line_numbers: LineNumbersComponent,
     help_pane: HelpComponent,
+    disk_usage: DiskUsageScanner,
+    view: View,
+    duplicates_search: Option<mpsc::Receiver<anyhow::Result<Vec<DuplicateGroup>>>>,
+    task: Option<Task>,
+    /// Connection used to transfer files when browsing a remote host
+    remote: Option<SftpFs>,
+    /// Contents of new files, when glm has a configuration directory
+    templates: Option<Templates>,
+    /// Where changes made outside of the file manager are recorded, when
+    /// auditing is enabled
+    audit: Option<AuditLog>,
+    prompt: Option<(PromptComponent, PromptAction)>,
+    /// Plan of an operation waiting to be confirmed
+    confirm: Option<(ConfirmComponent, ConfirmAction)>,
+    /// Permissions being edited, for the listed items
+    permissions: Option<(PermissionsComponent, Vec<PathBuf>)>,
+    /// Hashes of the last hashed items
+    checksums: Option<ChecksumsComponent>,
+    /// Pattern to rename the selected items with, being typed
+    rename: Option<RenameComponent>,
+    /// Items being renamed in an editor, with the file they were written to
+    bulk_edit: Option<(BulkEdit, PathBuf)>,
+    /// File to open in an editor, taken by the main loop which owns the
+    /// terminal
+    editor_request: Option<PathBuf>,
+    jobs: JobsComponent,
+    /// Items yanked to be pasted as a copy or move job
+    yanked: Option<(Paste, Vec<PathBuf>)>,
     is_help_open: bool,
+    /// The jobs pane takes input while it's open
+    is_jobs_open: bool,
+    message: Option<Message>,
     pub is_running: bool,
 }
 
+/// What is displayed on the body of the app, in place of the file list.
+#[derive(Debug)]
+enum View {
+    Files,
+    Duplicates(DuplicatesComponent),
+    DirDiff(DirDiffComponent),
+    Sync(SyncComponent),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paste {
+    Copy,
+    Move,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Link {
+    /// A symbolic link storing the path from the link to its target
+    Relative,
+    Absolute,
+    Hard,
+}
+
+/// What to do with the answer of the open prompt.
+#[derive(Debug)]
+enum PromptAction {
+    ArchiveName(Vec<PathBuf>),
+    ArchiveLevel {
+        paths: Vec<PathBuf>,
+        dest: PathBuf,
+    },
+    Upload,
+    /// Creates a file, or a directory when the name ends with a slash
+    Create,
+    CreateDir,
+    /// Picks the template to create a file from
+    TemplateName,
+    CreateFromTemplate(String),
+    /// Links the yanked items into the current directory, asking for the
+    /// kind of link
+    Link(Vec<PathBuf>),
+    /// Changes the permissions of the items to a typed mode
+    Chmod {
+        paths: Vec<PathBuf>,
+        recursive: bool,
+    },
+    /// Hashes the items, asking for the algorithm
+    Checksum(Vec<PathBuf>),
+    /// Replaces the excluded globs of the open sync, separated by commas
+    SyncExclude,
+}
+
+/// What to run once the plan of the open confirmation is accepted.
+#[derive(Debug)]
+enum ConfirmAction {
+    Trash(Vec<PathBuf>),
+    /// Discards the working tree changes of the paths
+    Discard(Vec<PathBuf>),
+    Paste {
+        paste: Paste,
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+    },
+    Chmod {
+        paths: Vec<PathBuf>,
+        change: ModeChange,
+    },
+    /// Applies the edited buffer of a bulk rename
+    BulkRename {
+        edit: BulkEdit,
+        edited: String,
+    },
+}
+
+/// Events sent by a long running task on a background thread, like
+/// creating an archive or transferring files.
+#[derive(Debug)]
+enum TaskEvent {
+    Progress {
+        done: u64,
+        total: u64,
+    },
+    /// Items were hashed, sent before the task is done
+    Checksums(HashAlgorithm, Vec<Checksum>),
+    /// The task finished, with a message describing what was done
+    Done(anyhow::Result<String>),
+}
+
+#[derive(Debug)]
+struct Task {
+    label: String,
+    events: mpsc::Receiver<TaskEvent>,
+}
+
+type Files = AsyncFileManager<Box<dyn Vfs + Send>>;
+
+/// A state produced by the file manager on the background, along with how
+/// to display it.
+#[derive(Debug)]
+pub struct Listing {
+    reload: Reload,
+    result: anyhow::Result<(ListState, Option<PathBuf>)>,
+}
+
+#[derive(Debug)]
+enum Reload {
+    /// A different directory, the cursor goes back to the top
+    Show,
+    /// The same directory, keeping the cursor in place. Reports the message
+    /// when there is one
+    Refresh(Option<String>),
+    /// Duplicates were moved to the trash
+    TrashedDuplicates(Vec<PathBuf>),
+    /// Duplicates were replaced with hard links, until `error` if any
+    LinkedDuplicates {
+        linked: Vec<PathBuf>,
+        error: Option<String>,
+    },
+    /// The dry run of an operation, to be confirmed
+    Confirm(Plan, ConfirmAction),
+}
+
+/// Feedback from the last action, displayed on the header.
+#[derive(Debug)]
+enum Message {
+    Info(String),
+    Error(String),
+}
+
 impl App {
-    pub fn new(file_manager: FileManager, size: Rect) -> anyhow::Result<Self> {
-        let list = file_manager.get_state().items.clone();
+    pub async fn new(
+        file_manager: Files,
+        remote: Option<SftpFs>,
+        audit: Option<AuditLog>,
+        templates: Option<Templates>,
+        size: Rect,
+    ) -> anyhow::Result<Self> {
+        let jobs = JobsComponent::new();
+        jobs.set_audit_log(audit.clone());
+        let fm_audit = audit.clone();
+        file_manager
+            .run(move |fm| {
+                fm.set_audit_log(fm_audit);
+                Ok(())
+            })
+            .await?;
+        let state = file_manager.get_state().await?;
+        let is_local = file_manager.run(|fm| Ok(fm.backend().is_local())).await?;
+        let list = state.items;
+        let (listings, pending_listings) = unbounded_channel();
 
-        Ok(Self {
+        let mut app = Self {
             is_running: true,
             file_manager,
+            current_dir: state.current_dir,
+            archive_root: None,
+            is_local,
+            listings,
+            pending_listings,
+            loading: 0,
             is_help_open: false,
+            message: None,
             line_numbers: LineNumbersComponent::new(list.len(), size, 0),
             file_list: FileListComponent::new(list, size),
             help_pane: HelpComponent::new(),
-        })
+            disk_usage: DiskUsageScanner::new(),
+            view: View::Files,
+            duplicates_search: None,
+            task: None,
+            remote,
+            templates,
+            audit,
+            prompt: None,
+            confirm: None,
+            permissions: None,
+            checksums: None,
+            rename: None,
+            bulk_edit: None,
+            editor_request: None,
+            jobs,
+            yanked: None,
+            is_jobs_open: false,
+        };
+        app.scan_directory_sizes();
+        Ok(app)
+    }
+
+    /// Applies the results of background work, such as directory sizes.
+    pub fn tick(&mut self) -> anyhow::Result<()> {
+        for event in self.disk_usage.events() {
+            match event {
+                DiskUsageEvent::Progress { path, usage } => self
+                    .file_list
+                    .set_size(&path, ItemSize::Computing(usage.apparent)),
+                DiskUsageEvent::Done { path, usage } => self
+                    .file_list
+                    .set_size(&path, ItemSize::Known(usage.apparent)),
+                DiskUsageEvent::Failed { path, .. } => {
+                    self.file_list.set_size(&path, ItemSize::Unknown)
+                }
+            }
+        }
+
+        if let Some(result) = self
+            .duplicates_search
+            .as_ref()
+            .and_then(|search| search.try_recv().ok())
+        {
+            self.duplicates_search = None;
+            match result {
+                Ok(groups) if groups.is_empty() => {
+                    self.message = Some(Message::Info("No duplicates found".into()))
+                }
+                Ok(groups) => {
+                    let bounds = self.file_list.bounds();
+                    self.view = View::Duplicates(DuplicatesComponent::new(groups, bounds));
+                    self.message = None;
+                }
+                Err(e) => self.message = Some(Message::Error(e.to_string())),
+            }
+        }
+
+        if let View::Sync(sync) = &mut self.view {
+            sync.poll();
+        }
+
+        if let View::DirDiff(dir_diff) = &mut self.view {
+            match dir_diff.poll() {
+                Some(Ok(done)) => self.message = done.map(Message::Info),
+                // there is nothing to show when the trees can't be compared
+                Some(Err(e)) if !dir_diff.is_compared() => {
+                    self.view = View::Files;
+                    self.message = Some(Message::Error(e.to_string()));
+                }
+                Some(Err(e)) => self.message = Some(Message::Error(e.to_string())),
+                None => (),
+            }
+        }
+
+        for event in self.jobs.poll() {
+            let JobEvent::Status { id, status } = event else {
+                continue;
+            };
+            let Some(job) = self.jobs.job(id) else {
+                continue;
+            };
+            let message = match status {
+                JobStatus::Done => Message::Info(format!("{}: done", job.kind)),
+                JobStatus::Failed(e) => Message::Error(format!("{}: {e}", job.kind)),
+                JobStatus::Cancelled => Message::Info(format!("{}: cancelled", job.kind)),
+                _ => continue,
+            };
+            self.message = Some(message);
+            if self.archive_root.is_none() {
+                self.load(Reload::Refresh(None), self.file_manager.refresh());
+            }
+        }
+
+        let events = self
+            .task
+            .as_ref()
+            .map(|task| task.events.try_iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for event in events {
+            match event {
+                TaskEvent::Progress { done, total } => {
+                    let percent = match total {
+                        0 => 100,
+                        total => done * 100 / total,
+                    };
+                    let label = self.task.as_ref().map_or("", |task| &task.label);
+                    self.message = Some(Message::Info(format!("{label}... {percent}%")));
+                }
+                TaskEvent::Checksums(algorithm, checksums) => {
+                    let dir = self.current_dir.clone();
+                    self.checksums = Some(ChecksumsComponent::new(algorithm, checksums, dir));
+                }
+                TaskEvent::Done(result) => {
+                    self.task = None;
+                    self.message = match result {
+                        Ok(done) => Some(Message::Info(done)),
+                        Err(e) => Some(Message::Error(e.to_string())),
+                    };
+                    if self.archive_root.is_none() {
+                        self.load(Reload::Refresh(None), self.file_manager.refresh());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs an operation of the file manager without blocking input,
+    /// `apply_listing` displays the produced state once it's ready.
+    fn load<F>(&mut self, reload: Reload, operation: F)
+    where
+        F: Future<Output = anyhow::Result<ListState>> + Send + 'static,
+    {
+        let file_manager = self.file_manager.clone();
+        let listings = self.listings.clone();
+        tokio::spawn(async move {
+            let result = match operation.await {
+                Ok(state) => file_manager.archive_root().await.map(|root| (state, root)),
+                Err(e) => Err(e),
+            };
+            _ = listings.send(Listing { reload, result });
+        });
+        self.loading += 1;
+    }
+
+    /// Computes the plan of an operation in the background, and asks to
+    /// confirm it before running `action`.
+    fn confirm<F>(&mut self, action: ConfirmAction, dry_run: F)
+    where
+        F: Future<Output = anyhow::Result<Plan>> + Send + 'static,
+    {
+        let file_manager = self.file_manager.clone();
+        let listings = self.listings.clone();
+        tokio::spawn(async move {
+            let (reload, result) = match dry_run.await {
+                Ok(plan) => {
+                    let state = file_manager.get_state().await;
+                    let root = file_manager.archive_root().await;
+                    let result = state.and_then(|state| Ok((state, root?)));
+                    (Reload::Confirm(plan, action), result)
+                }
+                Err(e) => (Reload::Show, Err(e)),
+            };
+            _ = listings.send(Listing { reload, result });
+        });
+        self.loading += 1;
+    }
+
+    /// Waits for the next operation of the file manager to finish.
+    pub async fn next_listing(&mut self) -> Listing {
+        self.pending_listings
+            .recv()
+            .await
+            .expect("app holds a sender of listings")
+    }
+
+    pub fn apply_listing(&mut self, listing: Listing) -> anyhow::Result<()> {
+        self.loading = self.loading.saturating_sub(1);
+        let (state, archive_root) = match listing.result {
+            Ok(listed) => listed,
+            Err(e) => {
+                self.message = Some(Message::Error(e.to_string()));
+                return Ok(());
+            }
+        };
+        self.current_dir = state.current_dir;
+        self.archive_root = archive_root;
+        let focus = state.focus;
+        match listing.reload {
+            Reload::Show => self.show_items(state.items)?,
+            Reload::Refresh(done) => {
+                if let Some(done) = done {
+                    self.message = Some(Message::Info(done));
+                }
+                self.refresh_items(state.items)?;
+            }
+            Reload::TrashedDuplicates(paths) => {
+                if let View::Duplicates(duplicates) = &mut self.view {
+                    duplicates.remove_paths(&paths);
+                    if duplicates.is_empty() {
+                        self.view = View::Files;
+                    }
+                }
+                self.message = Some(Message::Info(format!("Trashed {} item(s)", paths.len())));
+                self.refresh_items(state.items)?;
+            }
+            Reload::LinkedDuplicates { linked, error } => {
+                if let View::Duplicates(duplicates) = &mut self.view {
+                    duplicates.remove_paths(&linked);
+                    if duplicates.is_empty() {
+                        self.view = View::Files;
+                    }
+                }
+                self.message = match error {
+                    Some(error) => Some(Message::Error(error)),
+                    None => Some(Message::Info(format!("Linked {} item(s)", linked.len()))),
+                };
+                self.refresh_items(state.items)?;
+            }
+            Reload::Confirm(plan, action) => {
+                self.refresh_items(state.items)?;
+                if plan.is_empty() {
+                    // restoring deleted files loses nothing, so there is
+                    // nothing to confirm
+                    if let ConfirmAction::Discard(_) = action {
+                        self.run_confirmed(action);
+                        return Ok(());
+                    }
+                    self.message = Some(Message::Info("Nothing to do".into()));
+                    return Ok(());
+                }
+                let title = match &action {
+                    ConfirmAction::Trash(_) => format!("Trash {} item(s)?", plan.len()),
+                    ConfirmAction::Discard(paths) => {
+                        format!("Discard changes of {} item(s)?", paths.len())
+                    }
+                    ConfirmAction::Paste { paste, dest, .. } => format!(
+                        "{} {} item(s) into {}?",
+                        match paste {
+                            Paste::Copy => "Copy",
+                            Paste::Move => "Move",
+                        },
+                        plan.len(),
+                        dest.display()
+                    ),
+                    ConfirmAction::Chmod { .. } => {
+                        format!("Change permissions of {} item(s)?", plan.len())
+                    }
+                    ConfirmAction::BulkRename { edit, edited } => {
+                        let changes = edit.diff(edited).map_or(plan.len(), |c| c.len());
+                        format!("Apply {changes} change(s)?")
+                    }
+                };
+                self.confirm = Some((ConfirmComponent::new(title, &plan), action));
+            }
+        }
+        if let Some(focus) = focus {
+            self.file_list.focus(&focus);
+        }
+        Ok(())
+    }
+
+    /// Runs `work` on a background thread, only one task runs at a time.
+    /// `tick` reports its progress on the header.
+    fn spawn_task<F>(&mut self, label: &str, work: F)
+    where
+        F: FnOnce(&mpsc::Sender<TaskEvent>) -> anyhow::Result<String> + Send + 'static,
+    {
+        if let Some(task) = &self.task {
+            self.message = Some(Message::Error(format!(
+                "Wait for {} to finish",
+                task.label.to_lowercase()
+            )));
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = work(&tx);
+            _ = tx.send(TaskEvent::Done(result));
+        });
+        self.task = Some(Task {
+            label: label.into(),
+            events: rx,
+        });
+        self.message = Some(Message::Info(format!("{label}...")));
+    }
+
+    /// Reports an error and returns `false` when browsing a remote host,
+    /// for features that only work on local files.
+    fn require_local(&mut self) -> bool {
+        if self.is_local {
+            return true;
+        }
+        self.message = Some(Message::Error(
+            "Only available on the local file system".into(),
+        ));
+        false
+    }
+
+    /// Asks for the name of an archive to create with the selected items.
+    fn open_compress_prompt(&mut self) {
+        if !self.require_local() {
+            return;
+        }
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error("Can't compress items of an archive".into()));
+            return;
+        }
+        let items = self.file_list.selected_items();
+        let stem = match items.as_slice() {
+            [item] => item.file_name.clone(),
+            _ => {
+                let dir = &self.current_dir;
+                dir.file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "archive".into())
+            }
+        };
+        let paths = items.iter().map(|item| item.file_path.clone()).collect();
+        let mut prompt = PromptComponent::new("Archive name:", &format!("{stem}.tar.gz"));
+        prompt.set_cursor(stem.chars().count());
+        self.prompt = Some((prompt, PromptAction::ArchiveName(paths)));
+    }
+
+    fn handle_prompt_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        let Some((prompt, _)) = &mut self.prompt else {
+            return Ok(());
+        };
+        match event.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                let (prompt, action) = self.prompt.take().expect("prompt is open");
+                self.submit_prompt(action, prompt.value())?;
+            }
+            _ => prompt.handle_key_event(event)?,
+        }
+        Ok(())
+    }
+
+    fn submit_prompt(&mut self, action: PromptAction, value: String) -> anyhow::Result<()> {
+        match action {
+            PromptAction::ArchiveName(paths) => {
+                let dest = self.current_dir.join(value.trim());
+                if ArchiveKind::detect(&dest).is_none() {
+                    self.message = Some(Message::Error(
+                        "Archive name must end in .zip, .tar, .tar.gz, .tar.xz or .tar.zst".into(),
+                    ));
+                    return Ok(());
+                }
+                let prompt = PromptComponent::new("Compression level (empty for default):", "");
+                self.prompt = Some((prompt, PromptAction::ArchiveLevel { paths, dest }));
+            }
+            PromptAction::ArchiveLevel { paths, dest } => {
+                let level = match value.trim() {
+                    "" => None,
+                    level => match level.parse() {
+                        Ok(level) => Some(level),
+                        Err(_) => {
+                            self.message =
+                                Some(Message::Error(format!("Invalid compression level {level}")));
+                            return Ok(());
+                        }
+                    },
+                };
+                self.compress(paths, dest, CompressOptions { level });
+            }
+            PromptAction::Upload => self.upload(PathBuf::from(value.trim()))?,
+            PromptAction::Create if value.trim().ends_with('/') => {
+                self.create(value.trim(), None, true)
+            }
+            PromptAction::Create => self.create(value.trim(), None, false),
+            PromptAction::CreateDir => self.create(value.trim(), None, true),
+            PromptAction::TemplateName => {
+                let name = value.trim().to_string();
+                let prompt = PromptComponent::new(format!("New file from {name}:"), "");
+                self.prompt = Some((prompt, PromptAction::CreateFromTemplate(name)));
+            }
+            PromptAction::CreateFromTemplate(template) => {
+                self.create(value.trim(), Some(template), false)
+            }
+            PromptAction::Link(sources) => match value.trim() {
+                "r" => self.link(sources, Link::Relative),
+                "s" => self.link(sources, Link::Absolute),
+                "h" => self.link(sources, Link::Hard),
+                kind => self.message = Some(Message::Error(format!("Unknown link kind {kind}"))),
+            },
+            PromptAction::Chmod { paths, recursive } => match value.trim().parse() {
+                Ok(change) => self.change_mode(paths, change, recursive),
+                Err(e) => self.message = Some(Message::Error(format!("{e}"))),
+            },
+            PromptAction::Checksum(paths) => match value.trim().parse() {
+                Ok(algorithm) => self.checksum(paths, algorithm),
+                Err(e) => self.message = Some(Message::Error(format!("{e}"))),
+            },
+            PromptAction::SyncExclude => {
+                let View::Sync(sync) = &mut self.view else {
+                    return Ok(());
+                };
+                let exclude = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|glob| !glob.is_empty())
+                    .map(String::from)
+                    .collect();
+                if let Err(e) = sync.set_exclude(exclude) {
+                    self.message = Some(Message::Error(format!("Invalid exclude globs: {e}")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a file or a directory relative to the current directory,
+    /// missing parents included, and moves the cursor onto it. Files get
+    /// the contents of `template`, or of the template that applies to
+    /// their name.
+    fn create(&mut self, name: &str, template: Option<String>, is_dir: bool) {
+        let path = PathBuf::from(name);
+        let templates = self.templates.clone();
+        let done = match &template {
+            Some(template) => format!("Created {name} from {template}"),
+            None => format!("Created {name}"),
+        };
+        let create = self.file_manager.run(move |fm| {
+            if is_dir {
+                fm.create_dir(path, false)?;
+                return Ok(fm.get_state().clone());
+            }
+            let contents = match (templates, template) {
+                (Some(templates), Some(template)) => Some(templates.render(&template, &path)?),
+                (Some(templates), None) => templates.render_for(&path)?,
+                (None, _) => None,
+            };
+            let contents = contents.unwrap_or_default();
+            fm.create_file_with(path, contents.as_bytes(), false)?;
+            Ok(fm.get_state().clone())
+        });
+        self.load(Reload::Refresh(Some(done)), create);
+    }
+
+    fn open_create_prompt(&mut self, action: PromptAction) {
+        let question = match action {
+            PromptAction::CreateDir => "New directory:",
+            _ => "New file (end with / for a directory):",
+        };
+        self.prompt = Some((PromptComponent::new(question, ""), action));
+    }
+
+    /// Asks for the template to create a new file from, listing them.
+    fn open_template_prompt(&mut self) -> anyhow::Result<()> {
+        let Some(templates) = &self.templates else {
+            self.message = Some(Message::Error("No configuration directory".into()));
+            return Ok(());
+        };
+        let names = templates.list()?;
+        if names.is_empty() {
+            self.message = Some(Message::Error(format!(
+                "No templates in {}",
+                templates.dir().display()
+            )));
+            return Ok(());
+        }
+        let question = format!("Template ({}):", names.join(", "));
+        self.prompt = Some((
+            PromptComponent::new(question, ""),
+            PromptAction::TemplateName,
+        ));
+        Ok(())
+    }
+
+    fn compress(&mut self, paths: Vec<PathBuf>, dest: PathBuf, options: CompressOptions) {
+        self.submit_job(JobKind::Archive {
+            paths,
+            dest,
+            options,
+        });
+    }
+
+    fn submit_job(&mut self, kind: JobKind) {
+        let description = kind.to_string();
+        let id = self.jobs.submit(kind);
+        self.message = Some(Message::Info(format!(
+            "Queued job #{id}: {description}, J to see jobs"
+        )));
+    }
+
+    /// Remembers the selected items, to be copied or moved by `paste`.
+    fn yank_selection(&mut self, paste: Paste) {
+        if !self.require_local() {
+            return;
+        }
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error(
+                "Extract items of an archive with x instead".into(),
+            ));
+            return;
+        }
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        let verb = match paste {
+            Paste::Copy => "copy",
+            Paste::Move => "move",
+        };
+        self.message = Some(Message::Info(format!(
+            "Yanked {} item(s) to {verb}, p to paste, L to link",
+            paths.len()
+        )));
+        self.yanked = Some((paste, paths));
+    }
+
+    /// Copies or moves the yanked items into the current directory as a
+    /// job, so browsing can go on while it runs.
+    fn paste(&mut self) {
+        if !self.require_local() {
+            return;
+        }
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error("Can't paste into an archive".into()));
+            return;
+        }
+        let Some((paste, sources)) = self.yanked.clone() else {
+            self.message = Some(Message::Error("Nothing yanked, use y or m".into()));
+            return;
+        };
+        let dest = self.current_dir.clone();
+        let (paths, into) = (sources.clone(), dest.clone());
+        let dry_run = self.file_manager.run(move |fm| match paste {
+            Paste::Copy => fm.copy_to(&paths, into, true),
+            Paste::Move => fm.move_to(&paths, into, true),
+        });
+        let action = ConfirmAction::Paste {
+            paste,
+            sources,
+            dest,
+        };
+        self.confirm(action, dry_run);
+    }
+
+    /// Asks how to link the yanked items into the current directory.
+    fn open_link_prompt(&mut self) {
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error("Can't link into an archive".into()));
+            return;
+        }
+        let Some((_, sources)) = self.yanked.clone() else {
+            self.message = Some(Message::Error("Nothing yanked, use y or m".into()));
+            return;
+        };
+        let question = format!(
+            "Link {} item(s) here as (r)elative symlinks, (s)ymlinks or (h)ard links:",
+            sources.len()
+        );
+        self.prompt = Some((
+            PromptComponent::new(question, "r"),
+            PromptAction::Link(sources),
+        ));
+    }
+
+    /// Creates a link to every source in the current directory, with the
+    /// name of the source.
+    fn link(&mut self, sources: Vec<PathBuf>, kind: Link) {
+        let dest = self.current_dir.clone();
+        let done = format!("Linked {} item(s)", sources.len());
+        let link = self.file_manager.run(move |fm| {
+            for source in &sources {
+                let name = source
+                    .file_name()
+                    .ok_or_else(|| anyhow::anyhow!("{} has no name", source.display()))?;
+                let link = dest.join(name);
+                match kind {
+                    Link::Relative => fm.symlink(source, link, true, false)?,
+                    Link::Absolute => fm.symlink(source, link, false, false)?,
+                    Link::Hard => fm.hardlink(source, link, false)?,
+                };
+            }
+            Ok(fm.get_state().clone())
+        });
+        self.load(Reload::Refresh(Some(done)), link);
+    }
+
+    /// Asks to confirm trashing the selected items.
+    fn trash_selection(&mut self) {
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return;
+        }
+        let dry_run = self.file_manager.delete(&paths, true);
+        self.confirm(ConfirmAction::Trash(paths), dry_run);
+    }
+
+    /// Asks to confirm discarding the git changes of the selected items,
+    /// listing what would be moved to the trash.
+    fn discard_selection(&mut self) {
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return;
+        }
+        let dry_run = self.file_manager.git_discard_plan(&paths);
+        self.confirm(ConfirmAction::Discard(paths), dry_run);
+    }
+
+    /// Opens the permissions dialog for the selected items, starting from
+    /// the permissions of the first one.
+    fn open_permissions(&mut self) {
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error(
+                "Can't change permissions inside of an archive".into(),
+            ));
+            return;
+        }
+        let items = self.file_list.selected_items();
+        let Some(first) = items.first() else {
+            return;
+        };
+        let Some(mode) = first.mode else {
+            self.message = Some(Message::Error("Permissions aren't available here".into()));
+            return;
+        };
+        let title = match items.as_slice() {
+            [Item {
+                file_name,
+                uid: Some(uid),
+                gid: Some(gid),
+                ..
+            }] => {
+                let owners = Owners::system();
+                let (user, group) = (owners.user_label(*uid), owners.group_label(*gid));
+                format!("Permissions of {file_name}, owned by {user}:{group}")
+            }
+            [item] => format!("Permissions of {}", item.file_name),
+            _ => format!("Permissions of {} items", items.len()),
+        };
+        let paths = items.iter().map(|item| item.file_path.clone()).collect();
+        self.permissions = Some((PermissionsComponent::new(title, mode & 0o7777), paths));
+    }
+
+    fn handle_permissions_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        let Some((permissions, _)) = &mut self.permissions else {
+            return Ok(());
+        };
+        match event.code {
+            KeyCode::Enter => {
+                let (permissions, paths) = self.permissions.take().expect("dialog is open");
+                match (permissions.is_changed(), permissions.is_recursive()) {
+                    (false, _) => self.message = Some(Message::Info("Nothing to do".into())),
+                    (true, recursive) => self.change_mode(paths, permissions.change(), recursive),
+                }
+            }
+            KeyCode::Char('e') => {
+                let (permissions, paths) = self.permissions.take().expect("dialog is open");
+                let mode = format!("{:o}", permissions.mode());
+                let prompt = PromptComponent::new("Mode (755 or u+x,go-w):", &mode);
+                let recursive = permissions.is_recursive();
+                self.prompt = Some((prompt, PromptAction::Chmod { paths, recursive }));
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.permissions = None,
+            _ => permissions.handle_key_event(event)?,
+        }
+        Ok(())
+    }
+
+    /// Applies `change` to the items, recursive changes are confirmed
+    /// first since they may touch many more items than were selected.
+    fn change_mode(&mut self, paths: Vec<PathBuf>, change: ModeChange, recursive: bool) {
+        if recursive {
+            let dry_run = self
+                .file_manager
+                .change_mode(&paths, change.clone(), true, true);
+            self.confirm(ConfirmAction::Chmod { paths, change }, dry_run);
+            return;
+        }
+        let done = format!("Changed permissions of {} item(s)", paths.len());
+        let chmod = self.file_manager.change_mode(&paths, change, false, false);
+        let file_manager = self.file_manager.clone();
+        self.load(Reload::Refresh(Some(done)), async move {
+            chmod.await?;
+            file_manager.get_state().await
+        });
+    }
+
+    fn handle_confirm_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        let Some((confirm, _)) = &mut self.confirm else {
+            return Ok(());
+        };
+        match event.code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                let (_, action) = self.confirm.take().expect("confirmation is open");
+                self.run_confirmed(action);
+            }
+            KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => self.confirm = None,
+            _ => confirm.handle_key_event(event)?,
+        }
+        Ok(())
+    }
+
+    fn run_confirmed(&mut self, action: ConfirmAction) {
+        match action {
+            ConfirmAction::Trash(paths) => {
+                let done = format!("Trashed {} item(s)", paths.len());
+                let trash = self.file_manager.trash(&paths);
+                self.load(Reload::Refresh(Some(done)), trash);
+            }
+            ConfirmAction::Discard(paths) => {
+                let done = format!("Discarded {} item(s)", paths.len());
+                let discard = self.file_manager.git_discard(&paths);
+                self.load(Reload::Refresh(Some(done)), discard);
+            }
+            ConfirmAction::Paste {
+                paste: Paste::Copy,
+                sources,
+                dest,
+            } => self.submit_job(JobKind::Copy { sources, dest }),
+            ConfirmAction::Paste {
+                paste: Paste::Move,
+                sources,
+                dest,
+            } => {
+                // moved items are gone from where they were yanked
+                self.yanked = None;
+                self.submit_job(JobKind::Move { sources, dest });
+            }
+            ConfirmAction::Chmod { paths, change } => {
+                let done = format!("Changed permissions of {} item(s)", paths.len());
+                let chmod = self.file_manager.change_mode(&paths, change, true, false);
+                let file_manager = self.file_manager.clone();
+                self.load(Reload::Refresh(Some(done)), async move {
+                    chmod.await?;
+                    file_manager.get_state().await
+                });
+            }
+            ConfirmAction::BulkRename { edit, edited } => {
+                let changes = edit.diff(&edited).map_or(0, |changes| changes.len());
+                let done = format!("Applied {changes} change(s)");
+                let rename = self.file_manager.bulk_rename(edit, edited, false);
+                let file_manager = self.file_manager.clone();
+                self.load(Reload::Refresh(Some(done)), async move {
+                    rename.await?;
+                    file_manager.get_state().await
+                });
+            }
+        }
+    }
+
+    /// Opens the dialog to rename the selected items with a pattern.
+    fn open_rename(&mut self) {
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error("Can't rename items of an archive".into()));
+            return;
+        }
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return;
+        }
+        self.rename = Some(RenameComponent::new(paths));
+        self.update_rename_preview();
+    }
+
+    /// Computes the new names for the pattern of the rename dialog. Remote
+    /// names are only checked against the listed items, the rename itself
+    /// checks the rest.
+    fn update_rename_preview(&mut self) {
+        let Some(rename) = &mut self.rename else {
+            return;
+        };
+        let listed = match self.is_local {
+            true => None,
+            false => Some(
+                self.file_list
+                    .items
+                    .iter()
+                    .map(|line| line.item.file_path.as_path())
+                    .collect::<HashSet<_>>(),
+            ),
+        };
+        let exists = |path: &Path| match &listed {
+            Some(listed) => listed.contains(path),
+            None => path.symlink_metadata().is_ok(),
+        };
+        let preview = rename.pattern().and_then(|pattern| {
+            glm::preview_rename(rename.paths(), &pattern, exists).map_err(|e| e.to_string())
+        });
+        rename.set_preview(preview);
+    }
+
+    fn handle_rename_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        let Some(rename) = &mut self.rename else {
+            return Ok(());
+        };
+        match event.code {
+            KeyCode::Esc => self.rename = None,
+            KeyCode::Enter => {
+                let changes = rename.changes().map(|changes| changes.len());
+                let pattern = rename.pattern();
+                match (changes, pattern) {
+                    (Err(e), _) | (_, Err(e)) => self.message = Some(Message::Error(e)),
+                    (Ok(0), _) => self.message = Some(Message::Info("Nothing to rename".into())),
+                    (Ok(changes), Ok(pattern)) => {
+                        let rename = self.rename.take().expect("dialog is open");
+                        let done = format!("Renamed {changes} item(s)");
+                        let file_manager = self.file_manager.clone();
+                        let rename =
+                            self.file_manager
+                                .pattern_rename(rename.paths(), pattern, false);
+                        self.load(Reload::Refresh(Some(done)), async move {
+                            rename.await?;
+                            file_manager.get_state().await
+                        });
+                    }
+                }
+            }
+            _ => {
+                rename.handle_key_event(event)?;
+                self.update_rename_preview();
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the selected items, or every listed item when nothing is
+    /// selected, to a file to rename them in an editor.
+    fn open_bulk_rename(&mut self) -> anyhow::Result<()> {
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error("Can't rename items of an archive".into()));
+            return Ok(());
+        }
+        let selected = self
+            .file_list
+            .items
+            .iter()
+            .filter(|line| line.is_selected)
+            .collect::<Vec<_>>();
+        let lines = match selected.is_empty() {
+            true => self.file_list.items.iter().collect(),
+            false => selected,
+        };
+        let paths = lines
+            .iter()
+            .map(|line| line.item.file_path.clone())
+            .collect::<Vec<_>>();
+        let edit = match BulkEdit::new(&self.current_dir, paths) {
+            Ok(edit) => edit,
+            Err(e) => {
+                self.message = Some(Message::Error(e.to_string()));
+                return Ok(());
+            }
+        };
+        // a fresh file only this user can read, never one planted there
+        let (mut temp, file) = tempfile::Builder::new()
+            .prefix("glm-rename-")
+            .suffix(".txt")
+            .tempfile()?
+            .keep()?;
+        temp.write_all(edit.buffer().as_bytes())?;
+        self.editor_request = Some(file.clone());
+        self.bulk_edit = Some((edit, file));
+        Ok(())
+    }
+
+    /// A file to open in an editor, once the terminal is handed over.
+    pub fn take_editor_request(&mut self) -> Option<PathBuf> {
+        self.editor_request.take()
+    }
+
+    /// Reads back the file of the bulk rename, asking to confirm the
+    /// changes made to it.
+    pub fn editor_closed(&mut self, result: anyhow::Result<()>) {
+        let Some((edit, file)) = self.bulk_edit.take() else {
+            // a file opened from the list, which may have changed
+            if let Err(e) = result {
+                self.message = Some(Message::Error(e.to_string()));
+            }
+            self.load(Reload::Refresh(None), self.file_manager.refresh());
+            return;
+        };
+        let edited = result.and_then(|_| Ok(std::fs::read_to_string(&file)?));
+        _ = std::fs::remove_file(&file);
+        match edited {
+            Ok(edited) => {
+                let dry_run = self
+                    .file_manager
+                    .bulk_rename(edit.clone(), edited.clone(), true);
+                self.confirm(ConfirmAction::BulkRename { edit, edited }, dry_run);
+            }
+            Err(e) => {
+                let message = format!("{e}, nothing was renamed");
+                self.message = Some(Message::Error(message));
+            }
+        }
+    }
+
+    /// Asks for the algorithm to hash the selected items with.
+    fn open_checksum_prompt(&mut self) {
+        if !self.require_local() {
+            return;
+        }
+        if self.archive_root.is_some() {
+            self.message = Some(Message::Error("Can't hash items of an archive".into()));
+            return;
+        }
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return;
+        }
+        let prompt = PromptComponent::new("Hash with (sha256, blake3, md5):", "sha256");
+        self.prompt = Some((prompt, PromptAction::Checksum(paths)));
+    }
+
+    /// Hashes the items in the background, the hashes are displayed on a
+    /// popup once they are ready.
+    fn checksum(&mut self, paths: Vec<PathBuf>, algorithm: HashAlgorithm) {
+        self.spawn_task("Hashing", move |tx| {
+            let checksums = glm::checksum(&paths, algorithm, |progress| {
+                _ = tx.send(TaskEvent::Progress {
+                    done: progress.done,
+                    total: progress.total,
+                })
+            })?;
+            let done = format!("Hashed {} file(s) with {algorithm}", checksums.len());
+            _ = tx.send(TaskEvent::Checksums(algorithm, checksums));
+            Ok(done)
+        });
+    }
+
+    /// Checks the files listed on the manifest under the cursor, reporting
+    /// the ones that changed or are gone.
+    fn verify_manifest(&mut self) {
+        if !self.require_local() {
+            return;
+        }
+        let Some(manifest) = self
+            .file_list
+            .selected_items()
+            .first()
+            .map(|item| item.file_path.clone())
+        else {
+            self.message = Some(Message::Error("No manifest to verify".into()));
+            return;
+        };
+        let dir = self.current_dir.clone();
+        self.spawn_task("Verifying", move |tx| {
+            let report = glm::verify_manifest(&manifest, None, |progress| {
+                _ = tx.send(TaskEvent::Progress {
+                    done: progress.done,
+                    total: progress.total,
+                })
+            })?;
+            if report.is_ok() {
+                return Ok(format!("All {} file(s) match", report.verified.len()));
+            }
+            let names = |paths: Vec<&PathBuf>| {
+                paths
+                    .iter()
+                    .map(|path| {
+                        path.strip_prefix(&dir)
+                            .unwrap_or(path)
+                            .display()
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let mut problems = vec![];
+            if !report.mismatched.is_empty() {
+                let paths = report.mismatched.iter().map(|m| &m.path).collect();
+                problems.push(format!(
+                    "{} mismatched: {}",
+                    report.mismatched.len(),
+                    names(paths)
+                ));
+            }
+            if !report.missing.is_empty() {
+                let paths = report.missing.iter().collect();
+                problems.push(format!(
+                    "{} missing: {}",
+                    report.missing.len(),
+                    names(paths)
+                ));
+            }
+            anyhow::bail!(
+                "{} file(s) match, {}",
+                report.verified.len(),
+                problems.join(", ")
+            )
+        });
+    }
+
+    fn handle_checksums_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        let Some(checksums) = &mut self.checksums else {
+            return Ok(());
+        };
+        match event.code {
+            KeyCode::Char('c') => {
+                if let Some(checksum) = checksums.selected() {
+                    crate::clipboard::copy(&checksum.hash)?;
+                    let name = checksum.path.file_name().unwrap_or_default();
+                    let message = format!("Copied the hash of {}", name.to_string_lossy());
+                    self.message = Some(Message::Info(message));
+                }
+            }
+            KeyCode::Char('C') => {
+                crate::clipboard::copy(&checksums.lines())?;
+                let message = format!("Copied {} hash(es)", checksums.checksums().len());
+                self.message = Some(Message::Info(message));
+            }
+            KeyCode::Char('w') => {
+                let algorithm = checksums.algorithm();
+                let manifest = self.current_dir.join(algorithm.manifest_name());
+                let done = format!("Wrote {}", algorithm.manifest_name());
+                match glm::write_manifest(&manifest, checksums.checksums()) {
+                    Ok(()) => {
+                        self.checksums = None;
+                        self.load(Reload::Refresh(Some(done)), self.file_manager.refresh());
+                    }
+                    Err(e) => self.message = Some(Message::Error(e.to_string())),
+                }
+            }
+            KeyCode::Char('q') | KeyCode::Esc => self.checksums = None,
+            _ => checksums.handle_key_event(event)?,
+        }
+        Ok(())
+    }
+
+    fn handle_jobs_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('J') | KeyCode::Char('q') | KeyCode::Esc => self.toggle_jobs(),
+            _ => {
+                if let Err(e) = self.jobs.handle_key_event(event) {
+                    self.message = Some(Message::Error(e.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Downloads the selected items of the remote host into the directory
+    /// glm was started from.
+    fn download_selection(&mut self) -> anyhow::Result<()> {
+        let Some(remote) = &self.remote else {
+            self.message = Some(Message::Error("Not browsing a remote host".into()));
+            return Ok(());
+        };
+        let remote = remote.try_clone()?;
+        let dest = std::env::current_dir()?;
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        let audit = self.audit.clone();
+        self.spawn_task("Downloading", move |tx| {
+            for path in paths.iter() {
+                let target = dest.join(path.file_name().unwrap_or_default());
+                let result = remote.download(path, &target, |progress| {
+                    _ = tx.send(TaskEvent::Progress {
+                        done: progress.done,
+                        total: progress.total,
+                    })
+                });
+                if let Some(audit) = &audit {
+                    audit.record_other("download", path, Some(&target), &result)?;
+                }
+                result?;
+            }
+            Ok(format!(
+                "Downloaded {} item(s) to {}",
+                paths.len(),
+                dest.display()
+            ))
+        });
+        Ok(())
+    }
+
+    /// Asks for a local path to upload into the current remote directory.
+    fn open_upload_prompt(&mut self) -> anyhow::Result<()> {
+        if self.remote.is_none() {
+            self.message = Some(Message::Error("Not browsing a remote host".into()));
+            return Ok(());
+        }
+        let cwd = format!("{}/", std::env::current_dir()?.display());
+        self.prompt = Some((
+            PromptComponent::new("Upload local path:", &cwd),
+            PromptAction::Upload,
+        ));
+        Ok(())
+    }
+
+    fn upload(&mut self, src: PathBuf) -> anyhow::Result<()> {
+        let Some(remote) = &self.remote else {
+            return Ok(());
+        };
+        let remote = remote.try_clone()?;
+        let Some(name) = src.file_name() else {
+            self.message = Some(Message::Error(format!("Can't upload {}", src.display())));
+            return Ok(());
+        };
+        let dest = self.current_dir.join(name);
+        let audit = self.audit.clone();
+        self.spawn_task("Uploading", move |tx| {
+            let result = remote.upload(&src, &dest, |progress| {
+                _ = tx.send(TaskEvent::Progress {
+                    done: progress.done,
+                    total: progress.total,
+                })
+            });
+            if let Some(audit) = &audit {
+                audit.record_other("upload", &src, Some(&dest), &result)?;
+            }
+            result?;
+            Ok(format!("Uploaded {}", dest.display()))
+        });
+        Ok(())
+    }
+
+    /// Searches for duplicate files inside of the current directory on a
+    /// background thread, the results are displayed once they are ready.
+    fn find_duplicates(&mut self) {
+        if !self.require_local() {
+            return;
+        }
+        let dir = self.current_dir.clone();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || tx.send(glm::find_duplicates(&[dir])));
+        self.duplicates_search = Some(rx);
+        self.message = Some(Message::Info("Searching for duplicates...".into()));
+    }
+
+    fn trash_duplicates(&mut self) -> anyhow::Result<()> {
+        let View::Duplicates(duplicates) = &mut self.view else {
+            return Ok(());
+        };
+        let paths = duplicates.marked_paths();
+        let trash = self.file_manager.trash(&paths);
+        self.load(Reload::TrashedDuplicates(paths), trash);
+        Ok(())
+    }
+
+    /// Replaces the marked duplicates with hard links to the original of
+    /// their group in the background, stopping at the first failure.
+    fn link_duplicates(&mut self) -> anyhow::Result<()> {
+        let View::Duplicates(duplicates) = &self.view else {
+            return Ok(());
+        };
+        let targets = duplicates.link_targets();
+        let audit = self.audit.clone();
+        let file_manager = self.file_manager.clone();
+        let listings = self.listings.clone();
+        tokio::spawn(async move {
+            let linked = tokio::task::spawn_blocking(move || {
+                let mut linked = vec![];
+                for (original, extras) in targets {
+                    if let Err(e) = glm::link_duplicates(&original, &extras, audit.as_ref()) {
+                        return (linked, Some(e.to_string()));
+                    }
+                    linked.extend(extras);
+                }
+                (linked, None)
+            })
+            .await;
+            let (reload, result) = match linked {
+                Ok((linked, error)) => {
+                    let state = file_manager.refresh().await;
+                    let root = file_manager.archive_root().await;
+                    let result = state.and_then(|state| Ok((state, root?)));
+                    (Reload::LinkedDuplicates { linked, error }, result)
+                }
+                Err(e) => (Reload::Show, Err(e.into())),
+            };
+            _ = listings.send(Listing { reload, result });
+        });
+        self.loading += 1;
+        Ok(())
+    }
+
+    /// Compares the two selected directories, displaying them side by side.
+    fn compare_selected_dirs(&mut self) -> anyhow::Result<()> {
+        if !self.require_local() {
+            return Ok(());
+        }
+        let dirs = self
+            .file_list
+            .selected_items()
+            .into_iter()
+            .filter(|item| item.file_type == FileType::Directory)
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        let [left, right] = dirs.as_slice() else {
+            self.message = Some(Message::Error(
+                "Select exactly two directories to compare".into(),
+            ));
+            return Ok(());
+        };
+
+        let bounds = self.file_list.bounds();
+        self.view = View::DirDiff(DirDiffComponent::new(left.clone(), right.clone(), bounds));
+        self.message = Some(Message::Info("Comparing...".into()));
+        Ok(())
+    }
+
+    fn copy_diff_entry(&mut self, direction: CopyDirection) -> anyhow::Result<()> {
+        let View::DirDiff(dir_diff) = &mut self.view else {
+            return Ok(());
+        };
+        self.message = match dir_diff.copy(direction, self.audit.clone()) {
+            true => Some(Message::Info("Copying...".into())),
+            false => Some(Message::Error("Nothing to copy in this direction".into())),
+        };
+        Ok(())
+    }
+
+    /// Opens the plan to sync the left side of the comparison into the
+    /// right side, to be reviewed before it is executed.
+    fn plan_sync(&mut self) -> anyhow::Result<()> {
+        let View::DirDiff(dir_diff) = &self.view else {
+            return Ok(());
+        };
+        let (src, dst) = (
+            dir_diff.left().to_path_buf(),
+            dir_diff.right().to_path_buf(),
+        );
+        match SyncComponent::new(src, dst, self.file_list.bounds()) {
+            Ok(mut sync) => {
+                sync.set_audit_log(self.audit.clone());
+                self.view = View::Sync(sync);
+            }
+            Err(e) => self.message = Some(Message::Error(e.to_string())),
+        }
+        Ok(())
+    }
+
+    fn handle_dir_diff_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.view = View::Files,
+            KeyCode::Char('S') => self.plan_sync()?,
+            KeyCode::Char('>') => self.copy_diff_entry(CopyDirection::LeftToRight)?,
+            KeyCode::Char('<') => self.copy_diff_entry(CopyDirection::RightToLeft)?,
+            KeyCode::Char('?') => self.toggle_help(),
+            _ => {
+                if let View::DirDiff(dir_diff) = &mut self.view {
+                    let result = match event.code {
+                        KeyCode::Char('c') => {
+                            dir_diff.toggle_compare_contents();
+                            self.message = Some(Message::Info("Comparing...".into()));
+                            Ok(())
+                        }
+                        _ => dir_diff.handle_key_event(event),
+                    };
+                    if let Err(e) = result {
+                        self.message = Some(Message::Error(e.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_sync_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        let View::Sync(sync) = &mut self.view else {
+            return Ok(());
+        };
+        match event.code {
+            KeyCode::Char('q') | KeyCode::Esc if sync.is_running() => {
+                self.message = Some(Message::Error("Wait for the sync to finish".into()))
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                // go back to the comparison, refreshed with the synced state
+                let (src, dst) = (sync.src().to_path_buf(), sync.dst().to_path_buf());
+                self.view = View::DirDiff(DirDiffComponent::new(src, dst, self.file_list.bounds()));
+            }
+            KeyCode::Char('?') => self.toggle_help(),
+            KeyCode::Char('x') if !sync.is_running() => {
+                let exclude = sync.exclude().join(", ");
+                let prompt = PromptComponent::new("Exclude (globs separated by commas):", &exclude);
+                self.prompt = Some((prompt, PromptAction::SyncExclude));
+            }
+            _ => {
+                if let Err(e) = sync.handle_key_event(event) {
+                    self.message = Some(Message::Error(e.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_duplicates_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.view = View::Files,
+            KeyCode::Char('d') => self.trash_duplicates()?,
+            KeyCode::Char('L') => self.link_duplicates()?,
+            KeyCode::Char('?') => self.toggle_help(),
+            _ => {
+                if let View::Duplicates(duplicates) = &mut self.view {
+                    duplicates.handle_key_event(event)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts computing the size of every directory on the list in the
+    /// background, `tick` fills them in as they are computed.
+    fn scan_directory_sizes(&mut self) {
+        if !self.is_local {
+            return;
+        }
+        if self.archive_root.is_some() {
+            // archives already know the size of their directories
+            self.disk_usage.cancel();
+            let sizes = self
+                .file_list
+                .items
+                .iter()
+                .filter(|i| i.item.file_type == FileType::Directory)
+                .map(|i| (i.item.file_path.clone(), i.item.size))
+                .collect::<Vec<_>>();
+            for (path, size) in sizes {
+                self.file_list.set_size(&path, ItemSize::Known(size));
+            }
+            return;
+        }
+        let directories = self
+            .file_list
+            .items
+            .iter()
+            .filter(|i| i.item.file_type == FileType::Directory)
+            .map(|i| i.item.file_path.clone())
+            .collect();
+        self.disk_usage.scan(directories);
+    }
+
+    /// Replaces the listed items with the items of a new directory.
+    fn show_items(&mut self, items: Vec<Item>) -> anyhow::Result<()> {
+        self.line_numbers.update(items.len(), 0);
+        self.file_list.update(items)?;
+        self.scan_directory_sizes();
+        Ok(())
+    }
+
+    /// Replaces the listed items with a refreshed version of the same
+    /// directory, keeping the cursor in place.
+    fn refresh_items(&mut self, items: Vec<Item>) -> anyhow::Result<()> {
+        self.line_numbers.update(items.len(), self.file_list.scroll);
+        self.file_list.refresh(items)?;
+        self.scan_directory_sizes();
+        Ok(())
     }
 
     pub fn update_cursor(&mut self) -> anyhow::Result<()> {
-        self.file_list.draw_cursor()?;
+        if let Some((prompt, _)) = &mut self.prompt {
+            return prompt.tick();
+        }
+        if let Some(rename) = &mut self.rename {
+            return rename.tick();
+        }
+        if self.confirm.is_some() || self.permissions.is_some() || self.checksums.is_some() {
+            return Ok(());
+        }
+        if self.is_jobs_open {
+            return self.jobs.tick();
+        }
+        match &mut self.view {
+            View::Files => self.file_list.draw_cursor()?,
+            View::Duplicates(duplicates) => duplicates.tick()?,
+            View::DirDiff(dir_diff) => dir_diff.tick()?,
+            View::Sync(sync) => sync.tick()?,
+        }
         Ok(())
     }
 
@@ -58,25 +1661,125 @@ impl App {
         let list = body[1];
 
         self.draw_hint(f, header[0])?;
+        if let Some((prompt, _)) = &mut self.prompt {
+            // the prompt takes the place of the message, below the title
+            let area = Rect {
+                x: header[0].x + 4,
+                y: header[0].y + 1,
+                width: header[0].width.saturating_sub(4),
+                height: 1,
+            };
+            prompt.draw(f, area)?;
+        }
 
-        if self.should_open_bottom_pane() {
+        if self.is_jobs_open {
+            self.jobs.resize(footer[0])?;
+            self.jobs.draw(f, footer[0])?;
+        } else if self.is_help_open {
             self.help_pane.draw(f, footer[0])?;
         }
 
+        match &mut self.view {
+            View::Duplicates(duplicates) => {
+                duplicates.resize(page[1])?;
+                duplicates.draw(f, page[1])?;
+                return Ok(());
+            }
+            View::DirDiff(dir_diff) => {
+                dir_diff.resize(page[1])?;
+                dir_diff.draw(f, page[1])?;
+                return Ok(());
+            }
+            View::Sync(sync) => {
+                sync.resize(page[1])?;
+                sync.draw(f, page[1])?;
+                return Ok(());
+            }
+            View::Files => (),
+        }
+
         self.file_list.resize(list)?;
         self.line_numbers.resize(line_numbers)?;
         self.line_numbers
             .update(self.file_list.items.len(), self.file_list.scroll);
         self.line_numbers.draw(f, line_numbers)?;
         self.file_list.draw(f, list)?;
+        if let Some((confirm, _)) = &mut self.confirm {
+            confirm.draw(f, page[1])?;
+        }
+        if let Some((permissions, _)) = &mut self.permissions {
+            permissions.draw(f, page[1])?;
+        }
+        if let Some(checksums) = &mut self.checksums {
+            checksums.draw(f, page[1])?;
+        }
+        if let Some(rename) = &mut self.rename {
+            rename.draw(f, page[1])?;
+        }
         Ok(())
     }
 
     fn draw_hint(&self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
-        let text = vec![
-            Line::from("Welcome to Glm v0.1.0, use h,j,k,l to move".gray().dim()),
-            Line::from("Press `?` to see help".gray().dim()),
-        ];
+        let message = match &self.message {
+            Some(Message::Info(message)) => Line::from(message.clone().green()),
+            Some(Message::Error(message)) => Line::from(message.clone().red()),
+            None if self.loading > 0 => Line::from("Loading...".gray().dim()),
+            None => Line::from("Press `?` to see help".gray().dim()),
+        };
+        let title = match (&self.view, self.file_list.is_usage_view()) {
+            (View::Duplicates(duplicates), _) => Line::from(
+                format!(
+                    "Duplicates in {}, {} wasted. Space to mark, a to mark all extras, d to trash, L to hard link",
+                    self.current_dir.display(),
+                    format_size(duplicates.wasted_size())
+                )
+                .cyan(),
+            ),
+            (View::DirDiff(dir_diff), _) => Line::from(
+                format!(
+                    "Comparing by {}. > and < to copy across, = to toggle identical, S to sync left into right, c to compare {}",
+                    match dir_diff.compares_contents() {
+                        true => "contents",
+                        false => "size and modification time",
+                    },
+                    match dir_diff.compares_contents() {
+                        true => "by modification time",
+                        false => "contents",
+                    }
+                )
+                .cyan(),
+            ),
+            (View::Sync(sync), _) => Line::from(sync.summary().cyan()),
+            (View::Files, true) => Line::from(
+                format!(
+                    "Disk usage of {}, total {}",
+                    self.current_dir.display(),
+                    format_size(self.file_list.total_size())
+                )
+                .cyan(),
+            ),
+            (View::Files, false) if self.remote.is_some() => Line::from(
+                format!(
+                    "Browsing {}, G to download selection, P to upload",
+                    self.remote
+                        .as_ref()
+                        .map(|remote| remote.location().to_string())
+                        .unwrap_or_default()
+                )
+                .cyan(),
+            ),
+            (View::Files, false) if self.archive_root.is_some() => Line::from(
+                format!(
+                    "Browsing {}, x to extract selection next to the archive",
+                    self.current_dir.display()
+                )
+                .cyan(),
+            ),
+            (View::Files, false) => {
+                Line::from("Welcome to Glm v0.1.0, use h,j,k,l to move".gray().dim())
+            }
+        };
+        let text = vec![title, message];
         let p = Paragraph::new(text)
             .block(
                 Block::new()
@@ -90,51 +1793,206 @@ impl App {
     }
 
     fn should_open_bottom_pane(&self) -> bool {
-        self.is_help_open
+        self.is_help_open || self.is_jobs_open
     }
 
     fn select_current_item(&mut self) -> anyhow::Result<()> {
+        if self.file_list.items.is_empty() {
+            return Ok(());
+        }
         let list_item = self.file_list.get_line_under_cursor();
-        match list_item.item.file_type {
-            FileType::Directory => {
-                let new_state = self.file_manager.change_dir(&list_item.item.file_path)?;
-                self.line_numbers.update(new_state.items.len(), 0);
-                self.file_list.update(new_state.items.clone())?;
+        match (&list_item.item.file_type, &list_item.item.link) {
+            (FileType::Directory, _)
+            | (
+                FileType::Symlink,
+                Some(LinkTarget {
+                    file_type: Some(FileType::Directory),
+                    ..
+                }),
+            ) => {
+                let change_dir = self.file_manager.change_dir(&list_item.item.file_path);
+                self.load(Reload::Show, change_dir);
+            }
+            (
+                FileType::Symlink,
+                Some(LinkTarget {
+                    path,
+                    file_type: None,
+                }),
+            ) => {
+                let message = format!("Broken link to {}", path.display());
+                self.message = Some(Message::Error(message));
+            }
+            _ if glm::ArchiveKind::detect(&list_item.item.file_path).is_some() => {
+                let open_archive = self.file_manager.open_archive(&list_item.item.file_path);
+                self.load(Reload::Show, open_archive);
+            }
+            _ if self.is_local && self.archive_root.is_none() => {
+                // opened once the terminal is handed over to the editor
+                self.editor_request = Some(list_item.item.file_path.clone());
+            }
+            _ => {
+                let message = format!(
+                    "Can't open {}, it's not on the local file system",
+                    list_item.item.file_name
+                );
+                self.message = Some(Message::Error(message));
             }
-            _ => todo!(), // TODO: we should open the file here
         };
         Ok(())
     }
 
+    /// Lists the directory that contains the real target of the link under
+    /// the cursor, with the cursor on the target.
+    fn reveal_link_target(&mut self) {
+        let Some(item) = self.file_list.selected_items().first().copied() else {
+            return;
+        };
+        if item.file_type != FileType::Symlink {
+            let message = format!("{} is not a symbolic link", item.file_name);
+            self.message = Some(Message::Error(message));
+            return;
+        }
+        let reveal = self.file_manager.reveal_link_target(&item.file_path);
+        self.load(Reload::Show, reveal);
+    }
+
     fn change_to_parent(&mut self) -> anyhow::Result<()> {
-        let path = self.file_manager.get_state().current_dir.clone();
-        if let Some(parent) = path.parent() {
-            let new_state = self.file_manager.change_dir(parent)?;
-            self.line_numbers.update(new_state.items.len(), 0);
-            self.file_list.update(new_state.items.clone())?;
+        if let Some(parent) = self.current_dir.parent() {
+            let change_dir = self.file_manager.change_dir(parent);
+            self.load(Reload::Show, change_dir);
         }
         Ok(())
     }
 
     fn toggle_help(&mut self) {
         self.is_help_open = !self.is_help_open;
+        self.is_jobs_open = false;
+    }
+
+    fn toggle_jobs(&mut self) {
+        self.is_jobs_open = !self.is_jobs_open;
+        self.is_help_open = false;
     }
 
     fn toggle_hidden(&mut self) -> anyhow::Result<()> {
-        self.file_manager.toggle_hidden()?;
-        let items = self.file_manager.get_state().items.to_vec();
-        self.line_numbers.update(items.len(), 0);
-        self.file_list.update(items)?;
+        let toggle_hidden = self.file_manager.toggle_hidden();
+        self.load(Reload::Show, toggle_hidden);
+        Ok(())
+    }
+
+    /// Runs `action` over the selected items, or the item under the cursor,
+    /// refreshing the list with the produced state. Failures are reported on
+    /// the header instead of being propagated.
+    fn apply_to_selection<F, R>(&mut self, action: F, done: &str) -> anyhow::Result<()>
+    where
+        F: FnOnce(&Files, &[PathBuf]) -> R,
+        R: Future<Output = anyhow::Result<ListState>> + Send + 'static,
+    {
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        let done = format!("{done} {} item(s)", paths.len());
+        self.load(
+            Reload::Refresh(Some(done)),
+            action(&self.file_manager, &paths),
+        );
+        Ok(())
+    }
+
+    /// Extracts the selected items of the archive being browsed next to
+    /// the archive file.
+    fn extract_selection(&mut self) -> anyhow::Result<()> {
+        if !self.require_local() {
+            return Ok(());
+        }
+        let Some(dest) = self
+            .archive_root
+            .as_deref()
+            .and_then(|root| root.parent())
+            .map(|dir| dir.to_path_buf())
+        else {
+            self.message = Some(Message::Error("Not inside of an archive".into()));
+            return Ok(());
+        };
+        let paths = self
+            .file_list
+            .selected_items()
+            .iter()
+            .map(|item| item.file_path.clone())
+            .collect::<Vec<_>>();
+        let done = format!("Extracted {} item(s) to {}", paths.len(), dest.display());
+        let extract = self.file_manager.extract(&paths, &dest);
+        self.load(Reload::Refresh(Some(done)), extract);
         Ok(())
     }
 
     pub fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        if self.prompt.is_some() {
+            return self.handle_prompt_key_event(event);
+        }
+        self.message = None;
+        if self.confirm.is_some() {
+            return self.handle_confirm_key_event(event);
+        }
+        if self.permissions.is_some() {
+            return self.handle_permissions_key_event(event);
+        }
+        if self.checksums.is_some() {
+            return self.handle_checksums_key_event(event);
+        }
+        if self.rename.is_some() {
+            return self.handle_rename_key_event(event);
+        }
+        if self.is_jobs_open {
+            return self.handle_jobs_key_event(event);
+        }
+        match self.view {
+            View::Duplicates(_) => return self.handle_duplicates_key_event(event),
+            View::DirDiff(_) => return self.handle_dir_diff_key_event(event),
+            View::Sync(_) => return self.handle_sync_key_event(event),
+            View::Files => (),
+        }
+
         match event.code {
             KeyCode::Enter => self.select_current_item()?,
             KeyCode::Char('q') => self.is_running = false,
             KeyCode::Char('-') => self.change_to_parent()?,
             KeyCode::Char('?') => self.toggle_help(),
             KeyCode::Char('H') => self.toggle_hidden()?,
+            KeyCode::Char('U') => self.file_list.toggle_usage_view(),
+            KeyCode::Char('d') => self.trash_selection(),
+            KeyCode::Char('D') => self.find_duplicates(),
+            KeyCode::Char('C') => self.compare_selected_dirs()?,
+            KeyCode::Char('x') => self.extract_selection()?,
+            KeyCode::Char('Z') => self.open_compress_prompt(),
+            KeyCode::Char('G') => self.download_selection()?,
+            KeyCode::Char('P') => self.open_upload_prompt()?,
+            KeyCode::Char('y') => self.yank_selection(Paste::Copy),
+            KeyCode::Char('m') => self.yank_selection(Paste::Move),
+            KeyCode::Char('p') => self.paste(),
+            KeyCode::Char('J') => self.toggle_jobs(),
+            KeyCode::Char('a') => self.open_create_prompt(PromptAction::Create),
+            KeyCode::Char('A') => self.open_create_prompt(PromptAction::CreateDir),
+            KeyCode::Char('T') => self.open_template_prompt()?,
+            KeyCode::Char('M') => self.open_permissions(),
+            KeyCode::Char('L') => self.open_link_prompt(),
+            KeyCode::Char('F') => self.reveal_link_target(),
+            KeyCode::Char('r') => self.open_rename(),
+            KeyCode::Char('R') => self.open_bulk_rename()?,
+            KeyCode::Char('#') => self.open_checksum_prompt(),
+            KeyCode::Char('V') => self.verify_manifest(),
+            KeyCode::Char('s') => self.apply_to_selection(Files::git_stage, "Staged")?,
+            KeyCode::Char('u') => self.apply_to_selection(Files::git_unstage, "Unstaged")?,
+            KeyCode::Char('X') => self.discard_selection(),
+            KeyCode::Char('i') => self.apply_to_selection(Files::git_ignore, "Ignored")?,
             _ => self.file_list.handle_key_event(event)?,
         }
 
@@ -4,9 +4,20 @@ use clap::Parser;
 #[command(version, about, long_about = None)]
 pub struct Cli {
     path: Option<String>,
+    /// Record every mutating operation in $XDG_STATE_HOME/glm/audit.jsonl
+    #[arg(long)]
+    audit: bool,
 }
 
-pub fn parse() -> String {
+pub struct Args {
+    pub path: String,
+    pub audit: bool,
+}
+
+pub fn parse() -> Args {
     let args = Cli::parse();
-    args.path.as_deref().unwrap_or(".").to_string()
+    Args {
+        path: args.path.as_deref().unwrap_or(".").to_string(),
+        audit: args.audit,
+    }
 }
@@ -0,0 +1,28 @@
+use std::io::Write;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Copies `text` to the clipboard of the terminal with an OSC 52 escape
+/// sequence, which also works over SSH. Terminals that don't support it
+/// ignore the sequence.
+pub fn copy(text: &str) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(osc52(text).as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(osc52("glm"), "\x1b]52;c;Z2xt\x07");
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use glm::{Checksum, HashAlgorithm};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, Padding};
+use ratatui::Frame;
+
+use super::Component;
+
+/// Most checksums displayed at once, longer lists scroll.
+const MAX_HEIGHT: u16 = 12;
+
+/// A popup listing the hash of every hashed file, one of them under the
+/// cursor to be copied.
+#[derive(Debug)]
+pub struct ChecksumsComponent {
+    algorithm: HashAlgorithm,
+    checksums: Vec<Checksum>,
+    /// Directory paths are displayed relative to
+    dir: PathBuf,
+    cursor: usize,
+    scroll: usize,
+    bounds: Rect,
+}
+
+impl ChecksumsComponent {
+    pub fn new(algorithm: HashAlgorithm, checksums: Vec<Checksum>, dir: PathBuf) -> Self {
+        Self {
+            algorithm,
+            checksums,
+            dir,
+            cursor: 0,
+            scroll: 0,
+            bounds: Rect::default(),
+        }
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn checksums(&self) -> &[Checksum] {
+        &self.checksums
+    }
+
+    pub fn selected(&self) -> Option<&Checksum> {
+        self.checksums.get(self.cursor)
+    }
+
+    /// Every checksum in the format of `sha256sum`, to be copied at once.
+    pub fn lines(&self) -> String {
+        self.checksums
+            .iter()
+            .map(|checksum| format!("{}  {}\n", checksum.hash, self.display(&checksum.path)))
+            .collect()
+    }
+
+    fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// Centers the popup on `area`, tall enough for every checksum.
+    fn popup(&self, area: Rect) -> Rect {
+        let width = area.width.saturating_sub(8).max(area.width.min(20));
+        let height = (self.checksums.len() as u16).clamp(1, MAX_HEIGHT) + 2;
+        let height = height.min(area.height);
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    fn move_cursor(&mut self, offset: isize) {
+        let last = self.checksums.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize + offset).clamp(0, last) as usize;
+        let height = self.bounds.height.saturating_sub(2).max(1) as usize;
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+}
+
+impl Component for ChecksumsComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        let popup = self.popup(area);
+        self.bounds = popup;
+        let title = format!(" {} of {} file(s) ", self.algorithm, self.checksums.len());
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(Color::Yellow))
+            .title(Line::from(title.yellow()))
+            .title_bottom(Line::from(
+                " c to copy, C to copy all, w to write a manifest ".gray(),
+            ))
+            .padding(Padding::horizontal(1));
+        let lines = self
+            .checksums
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .map(|(i, checksum)| {
+                let hash = Span::raw(checksum.hash.clone());
+                let hash = match i == self.cursor {
+                    true => hash.reversed(),
+                    false => hash,
+                };
+                Line::from(vec![
+                    hash,
+                    Span::raw("  "),
+                    Span::from(self.display(&checksum.path)).gray(),
+                ])
+            })
+            .collect::<Vec<_>>();
+        f.render_widget(Clear, popup);
+        f.render_widget(List::new(lines).block(block), popup);
+        Ok(())
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = self.popup(size);
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('j') | KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_cursor(-1),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    #[test]
+    fn test_cursor_and_lines() {
+        let checksums = ["a.tar.gz", "bin/app"]
+            .map(|name| Checksum {
+                path: PathBuf::from("/release").join(name),
+                hash: format!("{:x}", name.len()),
+            })
+            .to_vec();
+        let mut sut = ChecksumsComponent::new(HashAlgorithm::Md5, checksums, "/release".into());
+        sut.resize(Rect::new(0, 0, 80, 40)).unwrap();
+
+        for _ in 0..3 {
+            sut.handle_key_event(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE))
+                .expect("failed to handle key");
+        }
+
+        assert_eq!(sut.selected().map(|c| c.hash.as_str()), Some("7"));
+        assert_eq!(sut.lines(), "8  a.tar.gz\n7  bin/app\n");
+    }
+}
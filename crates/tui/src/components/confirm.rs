@@ -0,0 +1,114 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use glm::Plan;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, List, Padding};
+use ratatui::Frame;
+
+use super::Component;
+
+/// Most lines of a plan displayed at once, longer plans scroll.
+const MAX_HEIGHT: u16 = 12;
+
+/// A popup listing every operation of a dry-run plan, so it can be
+/// confirmed before anything is touched.
+#[derive(Debug)]
+pub struct ConfirmComponent {
+    title: String,
+    lines: Vec<String>,
+    scroll: usize,
+    bounds: Rect,
+}
+
+impl ConfirmComponent {
+    pub fn new(title: impl Into<String>, plan: &Plan) -> Self {
+        Self {
+            title: title.into(),
+            lines: plan.ops.iter().map(|op| op.to_string()).collect(),
+            scroll: 0,
+            bounds: Rect::default(),
+        }
+    }
+
+    /// Centers the popup on `area`, tall enough for the plan.
+    fn popup(&self, area: Rect) -> Rect {
+        let width = area.width.saturating_sub(8).max(area.width.min(20));
+        let height = (self.lines.len() as u16).clamp(1, MAX_HEIGHT) + 2;
+        let height = height.min(area.height);
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    fn scroll(&mut self, offset: isize) {
+        let height = self.bounds.height.saturating_sub(2).max(1) as usize;
+        let last = self.lines.len().saturating_sub(height) as isize;
+        self.scroll = (self.scroll as isize + offset).clamp(0, last) as usize;
+    }
+}
+
+impl Component for ConfirmComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        let popup = self.popup(area);
+        self.bounds = popup;
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(Color::Yellow))
+            .title(Line::from(format!(" {} ", self.title).yellow()))
+            .title_bottom(Line::from(" y to confirm, n to cancel ".gray()))
+            .padding(Padding::horizontal(1));
+        let lines = self
+            .lines
+            .iter()
+            .skip(self.scroll)
+            .map(|line| Line::from(line.clone()))
+            .collect::<Vec<_>>();
+        f.render_widget(Clear, popup);
+        f.render_widget(List::new(lines).block(block), popup);
+        Ok(())
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = self.popup(size);
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('j') | KeyCode::Down => self.scroll(1),
+            KeyCode::Char('k') | KeyCode::Up => self.scroll(-1),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+    use glm::PlannedOp;
+
+    #[test]
+    fn test_scroll_stops_at_the_last_page() {
+        let ops = (0..20)
+            .map(|i| PlannedOp::Delete {
+                path: format!("/tmp/{i}.txt").into(),
+            })
+            .collect();
+        let mut sut = ConfirmComponent::new("Trash 20 item(s)?", &Plan::new(ops, true));
+        sut.resize(Rect::new(0, 0, 80, 40)).unwrap();
+
+        for _ in 0..30 {
+            sut.handle_key_event(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE))
+                .expect("failed to handle key");
+        }
+
+        assert_eq!(sut.lines[0], "trash /tmp/0.txt");
+        assert_eq!(sut.scroll, 20 - MAX_HEIGHT as usize);
+    }
+}
@@ -0,0 +1,341 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use glm::{AuditLog, CompareOptions, DirDiff, PlannedOp};
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List};
+use ratatui::Frame;
+
+use super::Component;
+
+/// How an entry differs between both compared trees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffKind {
+    OnlyLeft,
+    OnlyRight,
+    Differing,
+    Identical,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffEntry {
+    pub path: PathBuf,
+    pub kind: DiffKind,
+}
+
+/// Which way an entry should be copied to make both trees match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// A comparison running in the background, with what to report once it
+/// is done.
+#[derive(Debug)]
+struct Comparing {
+    result: mpsc::Receiver<anyhow::Result<DirDiff>>,
+    done: Option<String>,
+}
+
+/// Displays the comparison of two directory trees side by side.
+///
+/// Trees are compared on a background thread, and `poll` applies the
+/// result once it is ready.
+#[derive(Debug)]
+pub struct DirDiffComponent {
+    left: PathBuf,
+    right: PathBuf,
+    options: CompareOptions,
+    entries: Vec<DiffEntry>,
+    /// Whether both trees were compared at least once
+    compared: bool,
+    comparing: Option<Comparing>,
+    show_identical: bool,
+    cursor: usize,
+    scroll: usize,
+    bounds: Rect,
+}
+
+fn to_entries(diff: DirDiff) -> Vec<DiffEntry> {
+    let mut entries = [
+        (diff.only_left, DiffKind::OnlyLeft),
+        (diff.only_right, DiffKind::OnlyRight),
+        (diff.differing, DiffKind::Differing),
+        (diff.identical, DiffKind::Identical),
+    ]
+    .into_iter()
+    .flat_map(|(paths, kind)| paths.into_iter().map(move |path| DiffEntry { path, kind }))
+    .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+impl DirDiffComponent {
+    pub fn new(left: PathBuf, right: PathBuf, bounds: Rect) -> Self {
+        let mut component = Self {
+            left,
+            right,
+            options: CompareOptions::default(),
+            entries: vec![],
+            compared: false,
+            comparing: None,
+            show_identical: false,
+            cursor: 0,
+            scroll: 0,
+            bounds,
+        };
+        component.refresh();
+        component
+    }
+
+    pub fn left(&self) -> &Path {
+        &self.left
+    }
+
+    pub fn right(&self) -> &Path {
+        &self.right
+    }
+
+    pub fn compares_contents(&self) -> bool {
+        self.options.compare_contents
+    }
+
+    /// Whether both trees were compared at least once.
+    pub fn is_compared(&self) -> bool {
+        self.compared
+    }
+
+    /// Compares both trees again in the background, keeping the cursor in
+    /// place once done.
+    pub fn refresh(&mut self) {
+        self.compare(None, || Ok(()));
+    }
+
+    /// Toggles between comparing files by modification time or by content.
+    pub fn toggle_compare_contents(&mut self) {
+        self.options.compare_contents = !self.options.compare_contents;
+        self.refresh();
+    }
+
+    /// Copies the entry under the cursor in `direction` in the background,
+    /// recording it in `audit`, and compares both trees again. Returns
+    /// `false` when the entry doesn't exist on the source side.
+    pub fn copy(&mut self, direction: CopyDirection, audit: Option<AuditLog>) -> bool {
+        let Some((from, to)) = self.copy_paths(direction) else {
+            return false;
+        };
+        let done = format!("Copied {}", to.display());
+        self.compare(Some(done), move || {
+            let result = glm::copy(&from, &to);
+            glm::audited(audit.as_ref(), &PlannedOp::Copy { from, to }, result)
+        });
+        true
+    }
+
+    /// Runs `before` and then compares both trees on a background thread,
+    /// replacing any comparison that is still running.
+    fn compare<F>(&mut self, done: Option<String>, before: F)
+    where
+        F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+    {
+        let (left, right) = (self.left.clone(), self.right.clone());
+        let options = self.options.clone();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = before().and_then(|_| glm::compare_dirs(&left, &right, &options));
+            _ = tx.send(result);
+        });
+        self.comparing = Some(Comparing { result: rx, done });
+    }
+
+    /// Applies the result of the comparison running in the background once
+    /// it is done, returning what to report.
+    pub fn poll(&mut self) -> Option<anyhow::Result<Option<String>>> {
+        let comparing = self.comparing.as_ref()?;
+        let result = match comparing.result.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err(anyhow::anyhow!("comparison stopped unexpectedly"))
+            }
+        };
+        let done = self.comparing.take().and_then(|comparing| comparing.done);
+        Some(result.map(|diff| {
+            self.entries = to_entries(diff);
+            self.compared = true;
+            self.move_cursor(0);
+            done
+        }))
+    }
+
+    fn visible_entries(&self) -> impl Iterator<Item = &DiffEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| self.show_identical || entry.kind != DiffKind::Identical)
+    }
+
+    pub fn entry_under_cursor(&self) -> Option<&DiffEntry> {
+        self.visible_entries().nth(self.cursor)
+    }
+
+    /// Returns the source and destination paths needed to copy the entry
+    /// under the cursor in the given direction, or `None` when the entry
+    /// does not exist on the source side.
+    pub fn copy_paths(&self, direction: CopyDirection) -> Option<(PathBuf, PathBuf)> {
+        let entry = self.entry_under_cursor()?;
+        let (from, to) = match (direction, entry.kind) {
+            (CopyDirection::LeftToRight, DiffKind::OnlyRight)
+            | (CopyDirection::RightToLeft, DiffKind::OnlyLeft) => return None,
+            (CopyDirection::LeftToRight, _) => (&self.left, &self.right),
+            (CopyDirection::RightToLeft, _) => (&self.right, &self.left),
+        };
+        Some((from.join(&entry.path), to.join(&entry.path)))
+    }
+
+    fn toggle_identical(&mut self) {
+        self.show_identical = !self.show_identical;
+        self.move_cursor(0);
+    }
+
+    fn move_cursor(&mut self, offset: isize) {
+        let total = self.visible_entries().count();
+        if total == 0 {
+            self.cursor = 0;
+            self.scroll = 0;
+            return;
+        }
+        self.cursor = (self.cursor as isize + offset).clamp(0, total as isize - 1) as usize;
+
+        let height = self.bounds.height.saturating_sub(1).max(1) as usize;
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+}
+
+impl Component for DirDiffComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        self.bounds = area;
+        let layout = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+
+        let (mut left, mut right) = (vec![], vec![]);
+        for entry in self.visible_entries().skip(self.scroll) {
+            let name = entry.path.to_string_lossy().to_string();
+            let (l, r) = match entry.kind {
+                DiffKind::OnlyLeft => (Line::from(name.green()), Line::from("")),
+                DiffKind::OnlyRight => (Line::from(""), Line::from(name.green())),
+                DiffKind::Differing => {
+                    (Line::from(name.clone().yellow()), Line::from(name.yellow()))
+                }
+                DiffKind::Identical => (
+                    Line::from(name.clone().dark_gray()),
+                    Line::from(name.dark_gray()),
+                ),
+            };
+            left.push(l);
+            right.push(r);
+        }
+
+        let title = |path: &Path| Line::from(path.to_string_lossy().to_string().bold());
+        let left = List::new(left).block(
+            Block::new()
+                .borders(Borders::TOP | Borders::RIGHT)
+                .title(title(&self.left)),
+        );
+        let right =
+            List::new(right).block(Block::new().borders(Borders::TOP).title(title(&self.right)));
+        f.render_widget(left, layout[0]);
+        f.render_widget(right, layout[1]);
+        Ok(())
+    }
+
+    fn tick(&mut self) -> anyhow::Result<()> {
+        let y = (self.cursor - self.scroll) as u16 + self.bounds.y + 1;
+        crossterm::execute!(io::stdout(), MoveTo(self.bounds.x, y))?;
+        Ok(())
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = size;
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('j') | KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Char('=') => self.toggle_identical(),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_sut() -> DirDiffComponent {
+        let diff = DirDiff {
+            only_left: vec!["a".into()],
+            only_right: vec!["b".into()],
+            differing: vec!["c".into()],
+            identical: vec!["d".into()],
+        };
+        DirDiffComponent {
+            left: "/left".into(),
+            right: "/right".into(),
+            options: CompareOptions::default(),
+            entries: to_entries(diff),
+            compared: true,
+            comparing: None,
+            show_identical: false,
+            cursor: 0,
+            scroll: 0,
+            bounds: Rect::new(0, 0, 10, 10),
+        }
+    }
+
+    #[test]
+    fn test_identical_entries_are_hidden() {
+        let mut sut = make_sut();
+
+        sut.move_cursor(10);
+        assert_eq!(sut.entry_under_cursor().unwrap().path, PathBuf::from("c"));
+
+        sut.toggle_identical();
+        sut.move_cursor(10);
+        assert_eq!(sut.entry_under_cursor().unwrap().path, PathBuf::from("d"));
+    }
+
+    #[test]
+    fn test_copy_paths() {
+        let mut sut = make_sut();
+
+        let left_to_right = sut.copy_paths(CopyDirection::LeftToRight);
+        let right_to_left = sut.copy_paths(CopyDirection::RightToLeft);
+
+        assert_eq!(
+            left_to_right,
+            Some((PathBuf::from("/left/a"), PathBuf::from("/right/a")))
+        );
+        assert_eq!(right_to_left, None);
+
+        sut.move_cursor(2);
+        let right_to_left = sut.copy_paths(CopyDirection::RightToLeft);
+        assert_eq!(
+            right_to_left,
+            Some((PathBuf::from("/right/c"), PathBuf::from("/left/c")))
+        );
+    }
+}
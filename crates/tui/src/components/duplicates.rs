@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+
+use glm::DuplicateGroup;
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::List;
+use ratatui::Frame;
+
+use super::Component;
+use crate::format::format_size;
+
+/// A line on the duplicates list, either the header of a group or one of
+/// the files in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Row {
+    Group(usize),
+    File(usize, usize),
+}
+
+/// Lists groups of duplicate files, allowing files to be marked so the
+/// extra copies can be removed or replaced by hard links.
+#[derive(Debug)]
+pub struct DuplicatesComponent {
+    groups: Vec<DuplicateGroup>,
+    rows: Vec<Row>,
+    marked: HashSet<PathBuf>,
+    cursor: usize,
+    scroll: usize,
+    bounds: Rect,
+}
+
+fn compute_rows(groups: &[DuplicateGroup]) -> Vec<Row> {
+    groups
+        .iter()
+        .enumerate()
+        .flat_map(|(group, g)| {
+            std::iter::once(Row::Group(group))
+                .chain((0..g.paths.len()).map(move |file| Row::File(group, file)))
+        })
+        .collect()
+}
+
+impl DuplicatesComponent {
+    pub fn new(groups: Vec<DuplicateGroup>, bounds: Rect) -> Self {
+        let mut component = Self {
+            rows: compute_rows(&groups),
+            groups,
+            marked: HashSet::new(),
+            cursor: 0,
+            scroll: 0,
+            bounds,
+        };
+        component.move_cursor(0);
+        component
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Total amount of bytes that would be reclaimed by keeping a single
+    /// copy of every group.
+    pub fn wasted_size(&self) -> u64 {
+        self.groups.iter().map(DuplicateGroup::wasted_size).sum()
+    }
+
+    /// Returns the marked files, or the file under the cursor when nothing
+    /// is marked.
+    pub fn marked_paths(&self) -> Vec<PathBuf> {
+        if !self.marked.is_empty() {
+            let mut marked = self.marked.iter().cloned().collect::<Vec<_>>();
+            marked.sort();
+            return marked;
+        }
+        match self.rows.get(self.cursor) {
+            Some(Row::File(group, file)) => vec![self.groups[*group].paths[*file].clone()],
+            _ => vec![],
+        }
+    }
+
+    /// Pairs every group that has marked files with the file that should be
+    /// kept, which is the first file of the group that is not marked.
+    /// Groups where every file is marked are skipped.
+    pub fn link_targets(&self) -> Vec<(PathBuf, Vec<PathBuf>)> {
+        let marked = self.marked_paths();
+        self.groups
+            .iter()
+            .filter_map(|group| {
+                let (extras, kept): (Vec<_>, Vec<_>) =
+                    group.paths.iter().partition(|path| marked.contains(path));
+                match (kept.first(), extras.is_empty()) {
+                    (Some(original), false) => {
+                        Some(((*original).clone(), extras.into_iter().cloned().collect()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Removes `paths` from the groups, dropping groups that no longer have
+    /// any duplicates.
+    pub fn remove_paths(&mut self, paths: &[PathBuf]) {
+        for group in self.groups.iter_mut() {
+            group.paths.retain(|path| !paths.contains(path));
+        }
+        self.groups.retain(|group| group.paths.len() > 1);
+        self.marked.clear();
+        self.rows = compute_rows(&self.groups);
+        self.move_cursor(0);
+    }
+
+    /// Marks every file except the first one of each group.
+    fn mark_all_but_first(&mut self) {
+        self.marked = self
+            .groups
+            .iter()
+            .flat_map(|group| group.paths.iter().skip(1).cloned())
+            .collect();
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some(Row::File(group, file)) = self.rows.get(self.cursor) {
+            let path = &self.groups[*group].paths[*file];
+            if !self.marked.remove(path) {
+                self.marked.insert(path.clone());
+            }
+            self.move_cursor(1);
+        }
+    }
+
+    /// Moves the cursor by `offset` files, skipping group headers. An
+    /// offset of zero only makes sure the cursor is on a file.
+    fn move_cursor(&mut self, offset: isize) {
+        let files = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| matches!(row, Row::File(..)))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        if files.is_empty() {
+            self.cursor = 0;
+            self.scroll = 0;
+            return;
+        }
+
+        let current = files
+            .iter()
+            .position(|&index| index >= self.cursor)
+            .unwrap_or(files.len() - 1);
+        let target = (current as isize + offset).clamp(0, files.len() as isize - 1);
+        self.cursor = files[target as usize];
+
+        let height = self.bounds.height.max(1) as usize;
+        // keep the header of the group visible when the cursor is on its
+        // first file
+        let top = self.cursor.saturating_sub(1);
+        if top < self.scroll {
+            self.scroll = top;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+}
+
+impl Component for DuplicatesComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        self.bounds = area;
+        let lines = self
+            .rows
+            .iter()
+            .skip(self.scroll)
+            .map(|row| match *row {
+                Row::Group(group) => {
+                    let group = &self.groups[group];
+                    Line::from(
+                        format!(
+                            "{} copies of {} ({} wasted)",
+                            group.paths.len(),
+                            format_size(group.size),
+                            format_size(group.wasted_size())
+                        )
+                        .cyan()
+                        .bold(),
+                    )
+                }
+                Row::File(group, file) => {
+                    let path = &self.groups[group].paths[file];
+                    let name = format!("  {}", path.display());
+                    match self.marked.contains(path) {
+                        true => Line::from(name.red().crossed_out()),
+                        false => Line::from(name.blue()),
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        f.render_widget(List::new(lines), area);
+        Ok(())
+    }
+
+    fn tick(&mut self) -> anyhow::Result<()> {
+        let y = (self.cursor - self.scroll) as u16 + self.bounds.y;
+        crossterm::execute!(io::stdout(), MoveTo(self.bounds.x + 2, y))?;
+        Ok(())
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = size;
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('j') | KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('a') => self.mark_all_but_first(),
+            KeyCode::Char('c') => self.marked.clear(),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_sut() -> DuplicatesComponent {
+        let groups = vec![
+            DuplicateGroup {
+                size: 10,
+                paths: vec!["/a/1".into(), "/b/1".into(), "/c/1".into()],
+            },
+            DuplicateGroup {
+                size: 5,
+                paths: vec!["/a/2".into(), "/b/2".into()],
+            },
+        ];
+        DuplicatesComponent::new(groups, Rect::new(0, 0, 10, 10))
+    }
+
+    #[test]
+    fn test_cursor_skips_group_headers() {
+        let mut sut = make_sut();
+
+        assert_eq!(sut.cursor, 1);
+        for _ in 0..3 {
+            sut.move_cursor(1);
+        }
+        assert_eq!(sut.cursor, 5);
+        sut.move_cursor(-1);
+        assert_eq!(sut.cursor, 3);
+        sut.move_cursor(10);
+        assert_eq!(sut.cursor, 6);
+    }
+
+    #[test]
+    fn test_link_targets_keep_first_unmarked_file() {
+        let mut sut = make_sut();
+        sut.toggle_mark();
+        sut.move_cursor(1);
+        sut.toggle_mark();
+
+        let targets = sut.link_targets();
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].0, PathBuf::from("/b/1"));
+        assert_eq!(
+            targets[0].1,
+            vec![PathBuf::from("/a/1"), PathBuf::from("/c/1")]
+        );
+    }
+
+    #[test]
+    fn test_remove_paths_drops_empty_groups() {
+        let mut sut = make_sut();
+        sut.mark_all_but_first();
+
+        let marked = sut.marked_paths();
+        sut.remove_paths(&marked);
+
+        assert!(sut.is_empty());
+        assert_eq!(sut.wasted_size(), 0);
+    }
+}
@@ -1,15 +1,26 @@
-use glm::{FileType, Item};
+use glm::{FileType, GitStatus, Item, LinkTarget};
 
 use std::io;
+use std::path::Path;
 
 use crossterm::cursor::MoveTo;
 use crossterm::event::{KeyCode, KeyEvent};
-use ratatui::layout::Rect;
+use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
 use ratatui::widgets::List;
 use ratatui::Frame;
 
 use crate::components::Component;
+use crate::format::format_size;
+
+/// Width of the column reserved for the git status glyph, on the left of
+/// every file name.
+const STATUS_GUTTER: u16 = 2;
+/// Width of the column displaying sizes, on the right of the list.
+const SIZE_COLUMN: u16 = 10;
+/// Width of the percentage bars displayed on the disk usage view.
+const USAGE_BAR_WIDTH: usize = 20;
 
 #[derive(Debug, PartialEq, Clone)]
 struct Position {
@@ -17,10 +28,21 @@ struct Position {
     pub y: u16,
 }
 
+/// Size of an item as displayed on the list. Directory sizes are computed
+/// in the background, so they start unknown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemSize {
+    Unknown,
+    Computing(u64),
+    Known(u64),
+}
+
 #[derive(Debug, Clone)]
 pub struct ListItem {
     pub display_name: String,
     pub item: Item,
+    pub is_selected: bool,
+    pub size: ItemSize,
 }
 
 #[derive(Debug)]
@@ -30,6 +52,7 @@ pub struct FileListComponent {
     last_pos: Option<Position>,
     pos: Position,
     pub scroll: u16,
+    usage_view: bool,
 }
 
 fn format_names(items: Vec<Item>) -> Vec<ListItem> {
@@ -52,14 +75,55 @@ fn format_names(items: Vec<Item>) -> Vec<ListItem> {
                 FileType::Directory => item.file_name.clone() + "/",
                 _ => item.file_name.clone(),
             };
+            let size = match item.file_type {
+                FileType::Directory => ItemSize::Unknown,
+                _ => ItemSize::Known(item.size),
+            };
             ListItem {
                 display_name,
                 item: item.clone(),
+                is_selected: false,
+                size,
             }
         })
         .collect()
 }
 
+fn git_status_glyph(status: Option<GitStatus>) -> Span<'static> {
+    match status {
+        Some(GitStatus::Conflicted) => "! ".red().bold(),
+        Some(GitStatus::Modified) => "M ".yellow(),
+        Some(GitStatus::Staged) => "+ ".green(),
+        Some(GitStatus::Untracked) => "? ".magenta(),
+        Some(GitStatus::Ignored) => ". ".dark_gray(),
+        Some(GitStatus::ContainsChanges) => "* ".cyan(),
+        None => "  ".into(),
+    }
+}
+
+/// The ` -> target` displayed after the name of a symbolic link, in red
+/// when the link is broken.
+fn link_target_span(link: &LinkTarget) -> Span<'static> {
+    let target = format!(" -> {}", link.path.display());
+    match link.file_type {
+        Some(_) => target.dark_gray(),
+        None => target.red().dim(),
+    }
+}
+
+fn usage_bar(size: u64, total: u64) -> Line<'static> {
+    let ratio = match total {
+        0 => 0.0,
+        total => size as f64 / total as f64,
+    };
+    let filled = (ratio * USAGE_BAR_WIDTH as f64).round() as usize;
+    Line::from(vec![
+        "#".repeat(filled).cyan(),
+        " ".repeat(USAGE_BAR_WIDTH - filled).into(),
+        format!(" {:>5.1}%", ratio * 100.0).gray(),
+    ])
+}
+
 impl FileListComponent {
     pub fn new(items: Vec<Item>, bounds: Rect) -> Self {
         Self {
@@ -68,20 +132,134 @@ impl FileListComponent {
             bounds,
             scroll: 0,
             items: format_names(items),
+            usage_view: false,
         }
     }
 
     pub fn update(&mut self, items: Vec<Item>) -> anyhow::Result<()> {
         self.items = format_names(items);
         self.pos = Position { x: 0, y: 0 };
+        self.scroll = 0;
         self.last_pos = None;
+        self.sort_by_usage();
         self.draw_cursor()?;
         Ok(())
     }
 
+    /// Replaces the items on the list while keeping the cursor where it was,
+    /// as long as it still fits the new list. Selections are cleared.
+    pub fn refresh(&mut self, items: Vec<Item>) -> anyhow::Result<()> {
+        self.items = format_names(items);
+        self.sort_by_usage();
+        let max_index = self.items.len().saturating_sub(1) as u16;
+        self.scroll = u16::min(self.scroll, max_index);
+        self.pos.y = u16::min(self.pos.y, max_index - self.scroll);
+        if !self.items.is_empty() {
+            self.constrain_to_line();
+        }
+        self.draw_cursor()?;
+        Ok(())
+    }
+
+    /// Updates the displayed size of the item at `path`, if it is listed.
+    pub fn set_size(&mut self, path: &Path, size: ItemSize) {
+        if let Some(line) = self.items.iter_mut().find(|i| i.item.file_path == path) {
+            line.size = size;
+            if let ItemSize::Known(_) = size {
+                self.keep_cursor_on_item(Self::sort_by_usage);
+            }
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn is_usage_view(&self) -> bool {
+        self.usage_view
+    }
+
+    /// Toggles the disk usage view, in which items are sorted by size, from
+    /// the biggest to the smallest, and displayed with a percentage bar.
+    pub fn toggle_usage_view(&mut self) {
+        self.usage_view = !self.usage_view;
+        match self.usage_view {
+            true => self.keep_cursor_on_item(Self::sort_by_usage),
+            false => self.keep_cursor_on_item(|list| {
+                let items = list.items.drain(..).collect::<Vec<_>>();
+                let mut sorted = format_names(items.iter().map(|i| i.item.clone()).collect());
+                for line in sorted.iter_mut() {
+                    if let Some(old) = items
+                        .iter()
+                        .find(|i| i.item.file_path == line.item.file_path)
+                    {
+                        line.size = old.size;
+                        line.is_selected = old.is_selected;
+                    }
+                }
+                list.items = sorted;
+            }),
+        }
+    }
+
+    /// Sum of the sizes of every listed item that are already known.
+    pub fn total_size(&self) -> u64 {
+        self.items
+            .iter()
+            .map(|i| match i.size {
+                ItemSize::Known(size) | ItemSize::Computing(size) => size,
+                ItemSize::Unknown => 0,
+            })
+            .sum()
+    }
+
+    fn sort_by_usage(&mut self) {
+        if !self.usage_view {
+            return;
+        }
+        // the sort is stable, so items of the same size are still sorted by
+        // name with directories first
+        self.items.sort_by_key(|i| match i.size {
+            ItemSize::Known(size) | ItemSize::Computing(size) => std::cmp::Reverse(size),
+            ItemSize::Unknown => std::cmp::Reverse(0),
+        });
+    }
+
+    /// Runs `reorder` over the list, then moves the cursor to wherever the
+    /// item that was under the cursor ended up.
+    fn keep_cursor_on_item<F>(&mut self, reorder: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        let current = self
+            .items
+            .get(self.cursor_index())
+            .map(|i| i.item.file_path.clone());
+        reorder(self);
+        if let Some(path) = current {
+            self.focus(&path);
+        }
+    }
+
+    /// Places the cursor on the item at `path`, if it is listed.
+    pub fn focus(&mut self, path: &Path) {
+        let Some(index) = self.items.iter().position(|i| i.item.file_path == path) else {
+            return;
+        };
+
+        let height = self.bounds.height.max(1) as usize;
+        if index < self.scroll as usize {
+            self.scroll = index as u16;
+        } else if index >= self.scroll as usize + height {
+            self.scroll = (index + 1 - height) as u16;
+        }
+        self.pos.y = (index - self.scroll as usize) as u16;
+        self.constrain_to_line();
+    }
+
     pub fn draw_cursor(&mut self) -> anyhow::Result<()> {
         self.last_pos = Some(self.pos.clone());
-        let x = self.pos.x + self.bounds.x;
+        let x = self.pos.x + self.bounds.x + STATUS_GUTTER;
         let y = self.pos.y + self.bounds.y;
         crossterm::execute!(io::stdout(), MoveTo(x, y))?;
         Ok(())
@@ -93,7 +271,41 @@ impl FileListComponent {
     }
 
     pub fn get_line_under_cursor(&self) -> &ListItem {
-        &self.items[self.pos.y as usize]
+        &self.items[self.cursor_index()]
+    }
+
+    fn cursor_index(&self) -> usize {
+        (self.pos.y + self.scroll) as usize
+    }
+
+    /// Returns the selected items, or the item under the cursor when nothing
+    /// is selected.
+    pub fn selected_items(&self) -> Vec<&Item> {
+        let selected = self
+            .items
+            .iter()
+            .filter(|i| i.is_selected)
+            .map(|i| &i.item)
+            .collect::<Vec<_>>();
+        match selected.is_empty() {
+            true => self
+                .items
+                .get(self.cursor_index())
+                .map(|i| vec![&i.item])
+                .unwrap_or_default(),
+            false => selected,
+        }
+    }
+
+    /// Toggles the selection of the item under the cursor, and moves the
+    /// cursor down so consecutive items can be selected quickly.
+    fn toggle_selection(&mut self) -> anyhow::Result<()> {
+        let index = self.cursor_index();
+        if let Some(line) = self.items.get_mut(index) {
+            line.is_selected = !line.is_selected;
+            self.move_cursor_down()?;
+        }
+        Ok(())
     }
 
     fn is_separator(&self, c: char) -> bool {
@@ -198,12 +410,66 @@ impl Component for FileListComponent {
             .items
             .iter()
             .skip(self.scroll as usize)
-            .map(|i| match i.item.file_type {
-                FileType::Directory => i.display_name.clone().yellow().bold(),
-                _ => i.display_name.clone().blue().dim(),
+            .map(|i| {
+                let name = match (&i.item.file_type, &i.item.link) {
+                    (FileType::Directory, _) => i.display_name.clone().yellow().bold(),
+                    (FileType::Symlink, Some(link)) if link.file_type.is_none() => {
+                        i.display_name.clone().red().crossed_out()
+                    }
+                    (FileType::Symlink, _) => i.display_name.clone().cyan(),
+                    _ => i.display_name.clone().blue().dim(),
+                };
+                let name = match i.item.git_status {
+                    Some(GitStatus::Ignored) => name.dark_gray(),
+                    _ => name,
+                };
+                let name = match i.is_selected {
+                    true => name.reversed(),
+                    false => name,
+                };
+                let mut spans = vec![git_status_glyph(i.item.git_status), name];
+                spans.extend(i.item.link.as_ref().map(link_target_span));
+                Line::from(spans)
+            })
+            .collect::<Vec<_>>();
+        let sizes = self
+            .items
+            .iter()
+            .skip(self.scroll as usize)
+            .map(|i| match i.size {
+                ItemSize::Unknown => Line::from("-".dark_gray()),
+                ItemSize::Computing(size) => Line::from(format_size(size).dark_gray().italic()),
+                ItemSize::Known(size) => Line::from(format_size(size).gray()),
             })
+            .map(|line| line.right_aligned())
             .collect::<Vec<_>>();
-        f.render_widget(List::new(list), area);
+
+        let usage_bar_width = match self.usage_view {
+            true => USAGE_BAR_WIDTH as u16 + 8,
+            false => 0,
+        };
+        let layout = Layout::horizontal([
+            Constraint::Fill(1),
+            Constraint::Length(usage_bar_width),
+            Constraint::Length(SIZE_COLUMN),
+        ])
+        .split(area);
+        f.render_widget(List::new(list), layout[0]);
+        f.render_widget(List::new(sizes), layout[2]);
+
+        if self.usage_view {
+            let total = self.total_size();
+            let bars = self
+                .items
+                .iter()
+                .skip(self.scroll as usize)
+                .map(|i| match i.size {
+                    ItemSize::Known(size) | ItemSize::Computing(size) => usage_bar(size, total),
+                    ItemSize::Unknown => Line::from(""),
+                })
+                .collect::<Vec<_>>();
+            f.render_widget(List::new(bars), layout[1]);
+        }
         self.draw_cursor()?;
         Ok(())
     }
@@ -226,6 +492,7 @@ impl Component for FileListComponent {
             KeyCode::Char('0') => self.move_cursor_to_line_start()?,
             KeyCode::Char('$') => self.move_cursor_to_line_end()?,
             KeyCode::Char('w') => self.move_cursor_to_next_word()?,
+            KeyCode::Char(' ') => self.toggle_selection()?,
             _ => (),
         }
         self.draw_cursor()?;
@@ -255,10 +522,17 @@ mod tests {
         for i in 0..lines_number {
             lines.push(Item {
                 is_hidden: false,
+                size: 0,
+                modified: None,
+                mode: None,
+                uid: None,
+                gid: None,
+                link: None,
                 file_type: FileType::File,
                 file_ext: Some(".txt".into()),
                 file_name: i.to_string() + "hello_world",
                 file_path: PathBuf::new(),
+                git_status: None,
             });
         }
         // start not in 0,0 to test bounds
@@ -266,6 +540,28 @@ mod tests {
         FileListComponent::new(lines, area)
     }
 
+    #[test]
+    fn test_link_target_span() {
+        let link = LinkTarget {
+            path: PathBuf::from("../dotfiles/vimrc"),
+            file_type: Some(FileType::File),
+        };
+        let broken = LinkTarget {
+            file_type: None,
+            ..link.clone()
+        };
+
+        assert_eq!(link_target_span(&link).content, " -> ../dotfiles/vimrc");
+        assert_eq!(
+            link_target_span(&link).style.fg,
+            Some(ratatui::style::Color::DarkGray)
+        );
+        assert_eq!(
+            link_target_span(&broken).style.fg,
+            Some(ratatui::style::Color::Red)
+        );
+    }
+
     #[test]
     fn test_get_line_under_cursor() {
         let sut = make_sut(1);
@@ -360,32 +656,131 @@ mod tests {
         assert_eq!(sut.pos.x, 11);
     }
 
+    #[test]
+    fn test_selected_items_defaults_to_cursor() {
+        let mut sut = make_sut(5);
+        sut.pos.y = 2;
+
+        let selected = sut.selected_items();
+
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].file_name, "2hello_world");
+    }
+
+    #[test]
+    fn test_toggle_selection() {
+        let mut sut = make_sut(5);
+
+        _ = sut.toggle_selection();
+        _ = sut.toggle_selection();
+        _ = sut.move_cursor_up();
+        _ = sut.toggle_selection();
+
+        let selected = sut.selected_items();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].file_name, "0hello_world");
+        assert_eq!(sut.pos.y, 2);
+    }
+
+    #[test]
+    fn test_refresh_keeps_cursor() {
+        let mut sut = make_sut(5);
+        sut.pos.y = 4;
+        _ = sut.toggle_selection();
+        let items = sut.items.iter().take(3).map(|i| i.item.clone()).collect();
+
+        _ = sut.refresh(items);
+
+        assert_eq!(sut.pos.y, 2);
+        assert!(sut.items.iter().all(|i| !i.is_selected));
+    }
+
+    #[test]
+    fn test_set_size() {
+        let mut sut = make_sut(2);
+        sut.items[1].item.file_path = PathBuf::from("/some/dir");
+
+        sut.set_size(Path::new("/some/dir"), ItemSize::Known(42));
+
+        assert_eq!(sut.items[1].size, ItemSize::Known(42));
+        assert_eq!(sut.items[0].size, ItemSize::Known(0));
+    }
+
+    #[test]
+    fn test_usage_view_sorts_by_size() {
+        let mut sut = make_sut(3);
+        for (i, line) in sut.items.iter_mut().enumerate() {
+            line.item.file_path = PathBuf::from(i.to_string());
+            line.size = ItemSize::Known(i as u64 * 10);
+        }
+        sut.pos.y = 1;
+
+        sut.toggle_usage_view();
+
+        assert_eq!(sut.items[0].display_name, "2hello_world");
+        assert_eq!(sut.items[2].display_name, "0hello_world");
+        // cursor should follow the item that was under it
+        assert_eq!(sut.get_line_under_cursor().display_name, "1hello_world");
+
+        sut.set_size(Path::new("0"), ItemSize::Known(100));
+
+        assert_eq!(sut.items[0].display_name, "0hello_world");
+        assert_eq!(sut.get_line_under_cursor().display_name, "1hello_world");
+        assert_eq!(sut.total_size(), 130);
+
+        sut.toggle_usage_view();
+
+        assert_eq!(sut.items[0].display_name, "0hello_world");
+        assert_eq!(sut.items[0].size, ItemSize::Known(100));
+    }
+
     #[test]
     fn test_sort_correctly() {
         let mut lines = vec![];
         lines.push(Item {
             is_hidden: false,
+            size: 0,
+            modified: None,
+            mode: None,
+            uid: None,
+            gid: None,
+            link: None,
             file_type: FileType::File,
             file_ext: Some(".txt".into()),
             file_name: String::from("6hello_world"),
             file_path: PathBuf::new(),
+            git_status: None,
         });
         for i in 0..3 {
             lines.push(Item {
                 is_hidden: false,
+                size: 0,
+                modified: None,
+                mode: None,
+                uid: None,
+                gid: None,
+                link: None,
                 file_type: FileType::File,
                 file_ext: Some(".txt".into()),
                 file_name: i.to_string() + "hello_world",
                 file_path: PathBuf::new(),
+                git_status: None,
             });
         }
         for i in 3..6 {
             lines.push(Item {
                 is_hidden: false,
+                size: 0,
+                modified: None,
+                mode: None,
+                uid: None,
+                gid: None,
+                link: None,
                 file_type: FileType::Directory,
                 file_ext: Some(".txt".into()),
                 file_name: i.to_string() + "hello_world",
                 file_path: PathBuf::new(),
+                git_status: None,
             });
         }
         let area = Rect::new(10, 10, 10, 10);
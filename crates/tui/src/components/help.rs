@@ -7,29 +7,56 @@ use ratatui::{
 
 use super::Component;
 
+/// Width of a single column of the help pane, key and description included.
+const COLUMN_WIDTH: u16 = 44;
+const KEY_WIDTH: u16 = 8;
+
 #[derive(Debug)]
 pub struct HelpComponent {
-    keys: [Line<'static>; 5],
-    lines: [Line<'static>; 5],
+    keys: Vec<Line<'static>>,
+    lines: Vec<Line<'static>>,
 }
 
 impl HelpComponent {
     pub fn new() -> Self {
+        let entries = [
+            ("-", "Move up to parent directory"),
+            ("Enter", "Open the entry under the cursor"),
+            ("q", "Quit glm"),
+            ("?", "Toggle this help pane"),
+            ("H", "Toggle hidden files"),
+            ("Space", "Select the entry under the cursor"),
+            ("a", "Create a file, from a matching template"),
+            ("T", "Create a file from a chosen template"),
+            ("A", "Create a directory"),
+            ("r", "Rename selection with a pattern, previewed"),
+            ("R", "Rename selection, or every item, in $EDITOR"),
+            ("M", "Change permissions: Space toggle, R recursive"),
+            ("d", "Move selection to the trash, after confirming"),
+            ("U", "Toggle disk usage view"),
+            ("D", "Find duplicate files"),
+            ("C", "Compare the two selected directories"),
+            ("S", "Compare: sync left into right"),
+            ("x", "Extract selection from an archive"),
+            ("Z", "Compress selection into an archive"),
+            ("y", "Yank selection to copy"),
+            ("m", "Yank selection to move"),
+            ("p", "Paste yanked items as a job, after confirming"),
+            ("L", "Link yanked items here: symlinks or hard links"),
+            ("F", "Jump to the real target of the link under the cursor"),
+            ("#", "Hash selection: c copy, w write a manifest"),
+            ("V", "Verify the checksum manifest under the cursor"),
+            ("J", "Toggle jobs: p pause, c cancel, C clear"),
+            ("G", "SFTP: download selection"),
+            ("P", "SFTP: upload a local path"),
+            ("s", "Git: stage selection"),
+            ("u", "Git: unstage selection"),
+            ("X", "Git: discard changes (to trash)"),
+            ("i", "Git: add selection to .gitignore"),
+        ];
         Self {
-            keys: [
-                "-".into(),
-                "Enter".into(),
-                "q".into(),
-                "?".into(),
-                "H".into(),
-            ],
-            lines: [
-                "Move up to parent directory".into(),
-                "Open the entry under the cursor".into(),
-                "Quit glm".into(),
-                "Toggle this help pane".into(),
-                "Toggle hidden files".into(),
-            ],
+            keys: entries.iter().map(|(key, _)| (*key).into()).collect(),
+            lines: entries.iter().map(|(_, line)| (*line).into()).collect(),
         }
     }
 }
@@ -45,15 +72,38 @@ impl Component for HelpComponent {
             .border_style(Style::new().fg(Color::Gray))
             .padding(Padding::left(1));
 
-        let keys = Paragraph::new(self.keys.to_vec())
-            .red()
-            .block(help_block.clone());
-        let lines = Paragraph::new(self.lines.to_vec()).blue().block(help_block);
+        // entries are split into as many columns as needed to fit the pane
+        let rows = usize::max(area.height.saturating_sub(1) as usize, 1);
+        let columns = self.keys.len().div_ceil(rows);
+        let layout = Layout::horizontal(
+            (0..columns)
+                .flat_map(|_| {
+                    [
+                        Constraint::Length(KEY_WIDTH),
+                        Constraint::Length(COLUMN_WIDTH - KEY_WIDTH),
+                    ]
+                })
+                .chain([Constraint::Fill(1)]),
+        )
+        .split(area);
 
-        let layout = Layout::horizontal([Constraint::Length(6), Constraint::Fill(1)]).split(area);
+        for (column, (keys, lines)) in self
+            .keys
+            .chunks(rows)
+            .zip(self.lines.chunks(rows))
+            .enumerate()
+        {
+            let keys = Paragraph::new(keys.to_vec())
+                .red()
+                .block(help_block.clone());
+            let lines = Paragraph::new(lines.to_vec())
+                .blue()
+                .block(help_block.clone());
 
-        f.render_widget(keys, layout[0]);
-        f.render_widget(lines, layout[1]);
+            f.render_widget(keys, layout[column * 2]);
+            f.render_widget(lines, layout[column * 2 + 1]);
+        }
+        f.render_widget(help_block, layout[columns * 2]);
         Ok(())
     }
 }
@@ -0,0 +1,207 @@
+use std::io;
+
+use glm::{AuditLog, Job, JobEvent, JobId, JobKind, JobQueue, JobStatus};
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, Padding};
+use ratatui::Frame;
+
+use super::Component;
+
+/// Lists the jobs running on the background, so they can be paused or
+/// cancelled. Owns the queue jobs are submitted to.
+#[derive(Debug)]
+pub struct JobsComponent {
+    queue: JobQueue,
+    /// Jobs as of the last event of the queue
+    jobs: Vec<Job>,
+    cursor: usize,
+    scroll: usize,
+    bounds: Rect,
+}
+
+fn status_span(status: &JobStatus) -> Span<'static> {
+    match status {
+        JobStatus::Queued => "queued   ".gray(),
+        JobStatus::Running => "running  ".cyan(),
+        JobStatus::Paused => "paused   ".yellow(),
+        JobStatus::Done => "done     ".green(),
+        JobStatus::Failed(_) => "failed   ".red(),
+        JobStatus::Cancelled => "cancelled".gray().dim(),
+    }
+}
+
+fn job_line(job: &Job) -> Line<'static> {
+    let percent = match job.total {
+        0 if job.status == JobStatus::Done => "100%".to_string(),
+        0 => "".to_string(),
+        total => format!("{}%", job.done.min(total) * 100 / total),
+    };
+    let mut spans = vec![
+        format!("#{:<4}", job.id).gray(),
+        status_span(&job.status),
+        format!(" {percent:>4}  ").into(),
+        job.kind.to_string().into(),
+    ];
+    if let JobStatus::Failed(error) = &job.status {
+        spans.push(format!(": {error}").red());
+    }
+    Line::from(spans)
+}
+
+impl JobsComponent {
+    pub fn new() -> Self {
+        Self {
+            queue: JobQueue::default(),
+            jobs: vec![],
+            cursor: 0,
+            scroll: 0,
+            bounds: Rect::default(),
+        }
+    }
+
+    /// See [`JobQueue::set_audit_log`].
+    pub fn set_audit_log(&self, audit: Option<AuditLog>) {
+        self.queue.set_audit_log(audit);
+    }
+
+    pub fn submit(&mut self, kind: JobKind) -> JobId {
+        let id = self.queue.submit(kind);
+        self.jobs = self.queue.jobs();
+        id
+    }
+
+    /// Returns every event of the queue since the last call, updating the
+    /// listed jobs.
+    pub fn poll(&mut self) -> Vec<JobEvent> {
+        let events = self.queue.events();
+        if !events.is_empty() {
+            self.jobs = self.queue.jobs();
+        }
+        events
+    }
+
+    pub fn job(&self, id: JobId) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    fn selected(&self) -> Option<&Job> {
+        self.jobs.get(self.cursor)
+    }
+
+    fn toggle_pause(&mut self) -> anyhow::Result<()> {
+        let Some(job) = self.selected() else {
+            return Ok(());
+        };
+        match job.status {
+            JobStatus::Paused => self.queue.resume(job.id)?,
+            _ => self.queue.pause(job.id)?,
+        }
+        self.jobs = self.queue.jobs();
+        Ok(())
+    }
+
+    fn cancel(&mut self) -> anyhow::Result<()> {
+        if let Some(job) = self.selected() {
+            self.queue.cancel(job.id)?;
+        }
+        self.jobs = self.queue.jobs();
+        Ok(())
+    }
+
+    fn clear_finished(&mut self) {
+        self.queue.clear_finished();
+        self.jobs = self.queue.jobs();
+        self.move_cursor(0);
+    }
+
+    fn move_cursor(&mut self, offset: isize) {
+        let last = self.jobs.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize + offset).clamp(0, last) as usize;
+
+        let height = self.bounds.height.saturating_sub(1).max(1) as usize;
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+}
+
+impl Component for JobsComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        self.bounds = area;
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .border_style(Style::new().fg(Color::Gray))
+            .padding(Padding::left(1));
+        let lines = match self.jobs.is_empty() {
+            true => vec![Line::from(
+                "No jobs, p pastes yanked items as a job".gray().dim(),
+            )],
+            false => self
+                .jobs
+                .iter()
+                .skip(self.scroll)
+                .map(job_line)
+                .collect::<Vec<_>>(),
+        };
+        f.render_widget(List::new(lines).block(block), area);
+        Ok(())
+    }
+
+    fn tick(&mut self) -> anyhow::Result<()> {
+        // below the border, after the padding
+        let y = self.bounds.y + 1 + (self.cursor - self.scroll) as u16;
+        crossterm::execute!(io::stdout(), MoveTo(self.bounds.x + 1, y))?;
+        Ok(())
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = size;
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('j') | KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Char('p') => self.toggle_pause()?,
+            KeyCode::Char('c') => self.cancel()?,
+            KeyCode::Char('C') => self.clear_finished(),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_line() {
+        let job = Job {
+            id: 7,
+            kind: JobKind::Delete {
+                paths: vec!["/tmp/a.txt".into()],
+            },
+            status: JobStatus::Failed("permission denied".into()),
+            done: 0,
+            total: 1,
+            checksums: vec![],
+        };
+
+        let text = job_line(&job)
+            .spans
+            .iter()
+            .map(|span| span.content.to_string())
+            .collect::<String>();
+
+        assert_eq!(text, "#7   failed      0%  Delete a.txt: permission denied");
+    }
+}
@@ -23,7 +23,7 @@ impl LineNumbersComponent {
         self.total_lines = total_lines;
     }
 
-    fn compose_list(&self) -> Vec<Span> {
+    fn compose_list(&self) -> Vec<Span<'_>> {
         let mut lines = vec![];
         let mut starting_line = self.scroll;
         for _ in 0..self.bounds.height as usize {
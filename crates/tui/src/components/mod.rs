@@ -1,6 +1,15 @@
+pub mod checksums;
+pub mod confirm;
+pub mod dir_diff;
+pub mod duplicates;
 pub mod file_list;
 pub mod help;
+pub mod jobs;
 pub mod line_numbers;
+pub mod permissions;
+pub mod prompt;
+pub mod rename;
+pub mod sync;
 
 use crossterm::event::KeyEvent;
 use ratatui::{layout::Rect, Frame};
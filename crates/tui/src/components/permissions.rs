@@ -0,0 +1,164 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use glm::{format_mode, ModeChange};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Padding, Paragraph};
+use ratatui::Frame;
+
+use super::Component;
+
+const CLASSES: [&str; 3] = ["user", "group", "other"];
+const PERMISSIONS: [&str; 3] = ["read", "write", "execute"];
+
+/// A popup with a read, write and execute toggle for each class, editing
+/// the permissions of the selected items.
+///
+/// Only the toggled bits change, so items with different permissions keep
+/// whatever wasn't touched.
+#[derive(Debug)]
+pub struct PermissionsComponent {
+    title: String,
+    initial: u32,
+    mode: u32,
+    /// Class and permission under the cursor
+    row: usize,
+    column: usize,
+    recursive: bool,
+}
+
+impl PermissionsComponent {
+    pub fn new(title: impl Into<String>, mode: u32) -> Self {
+        Self {
+            title: title.into(),
+            initial: mode,
+            mode,
+            row: 0,
+            column: 0,
+            recursive: false,
+        }
+    }
+
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    pub fn is_recursive(&self) -> bool {
+        self.recursive
+    }
+
+    /// Whether applying the dialog would change anything.
+    pub fn is_changed(&self) -> bool {
+        self.mode != self.initial
+    }
+
+    /// The bits toggled on and off since the dialog was opened.
+    pub fn change(&self) -> ModeChange {
+        ModeChange::toggle(self.mode & !self.initial, self.initial & !self.mode)
+    }
+
+    fn bit(row: usize, column: usize) -> u32 {
+        0o400 >> (row * 3 + column)
+    }
+
+    /// Centers the popup on `area`, wide enough for the title.
+    fn popup(&self, area: Rect) -> Rect {
+        let width = (self.title.chars().count() as u16 + 4)
+            .max(40)
+            .min(area.width);
+        let height = 8.min(area.height);
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+impl Component for PermissionsComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        let popup = self.popup(area);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(Color::Yellow))
+            .title(Line::from(format!(" {} ", self.title).yellow()))
+            .title_bottom(Line::from(" Enter to apply, e to type a mode ".gray()))
+            .padding(Padding::horizontal(1));
+
+        let mut header = vec![Span::raw(format!("{:<8}", ""))];
+        header.extend(PERMISSIONS.map(|name| Span::from(format!("{name:<9}")).gray()));
+        let mut lines = vec![Line::from(header)];
+        for (row, class) in CLASSES.iter().enumerate() {
+            let mut spans = vec![Span::raw(format!("{class:<8}"))];
+            for column in 0..PERMISSIONS.len() {
+                let checked = self.mode & Self::bit(row, column) != 0;
+                let toggle = Span::raw(if checked { "[x]" } else { "[ ]" });
+                let toggle = match (row, column) == (self.row, self.column) {
+                    true => toggle.reversed(),
+                    false => toggle,
+                };
+                spans.extend([toggle, Span::raw(" ".repeat(6))]);
+            }
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::default());
+        lines.push(Line::from(vec![
+            Span::raw(format!("{} {:04o}", format_mode(self.mode), self.mode)),
+            Span::from(format!(
+                "   R recursive: {}",
+                if self.recursive { "on" } else { "off" }
+            ))
+            .gray(),
+        ]));
+
+        f.render_widget(Clear, popup);
+        f.render_widget(Paragraph::new(lines).block(block), popup);
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('h') | KeyCode::Left => self.column = self.column.saturating_sub(1),
+            KeyCode::Char('l') | KeyCode::Right => self.column = (self.column + 1).min(2),
+            KeyCode::Char('k') | KeyCode::Up => self.row = self.row.saturating_sub(1),
+            KeyCode::Char('j') | KeyCode::Down => self.row = (self.row + 1).min(2),
+            KeyCode::Char(' ') => self.mode ^= Self::bit(self.row, self.column),
+            KeyCode::Char('R') => self.recursive = !self.recursive,
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    #[test]
+    fn test_change_only_has_toggled_bits() {
+        let mut sut = PermissionsComponent::new("Permissions", 0o644);
+        let press = |sut: &mut PermissionsComponent, code| {
+            sut.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE))
+                .expect("failed to handle key");
+        };
+
+        // execute for the user, then write for the group
+        press(&mut sut, KeyCode::Char('l'));
+        press(&mut sut, KeyCode::Char('l'));
+        press(&mut sut, KeyCode::Char(' '));
+        press(&mut sut, KeyCode::Char('j'));
+        press(&mut sut, KeyCode::Char('h'));
+        press(&mut sut, KeyCode::Char(' '));
+
+        assert_eq!(sut.mode(), 0o764);
+        assert!(sut.is_changed());
+        // other items keep their own bits
+        assert_eq!(sut.change().apply(0o600, false), 0o720);
+
+        // toggled back, nothing changes
+        press(&mut sut, KeyCode::Char(' '));
+        assert_eq!(sut.change().apply(0o600, false), 0o700);
+    }
+}
@@ -0,0 +1,117 @@
+use std::io;
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::{Clear, Paragraph};
+use ratatui::Frame;
+
+use super::Component;
+
+/// A single line text input, preceded by a question.
+#[derive(Debug)]
+pub struct PromptComponent {
+    question: String,
+    input: Vec<char>,
+    /// Position of the cursor on the input, in characters
+    cursor: usize,
+    bounds: Rect,
+}
+
+impl PromptComponent {
+    pub fn new(question: impl Into<String>, initial: &str) -> Self {
+        let input = initial.chars().collect::<Vec<_>>();
+        Self {
+            question: question.into(),
+            cursor: input.len(),
+            input,
+            bounds: Rect::default(),
+        }
+    }
+
+    pub fn value(&self) -> String {
+        self.input.iter().collect()
+    }
+
+    /// Places the cursor right before the `n`th character of the input.
+    pub fn set_cursor(&mut self, n: usize) {
+        self.cursor = n.min(self.input.len());
+    }
+
+    fn prefix_width(&self) -> u16 {
+        self.question.chars().count() as u16 + 1
+    }
+}
+
+impl Component for PromptComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        self.bounds = area;
+        f.render_widget(Clear, area);
+        let line = Line::from(vec![
+            format!("{} ", self.question).cyan(),
+            self.value().into(),
+        ]);
+        f.render_widget(Paragraph::new(line), area);
+        Ok(())
+    }
+
+    fn tick(&mut self) -> anyhow::Result<()> {
+        let x = self.bounds.x + self.prefix_width() + self.cursor as u16;
+        crossterm::execute!(io::stdout(), MoveTo(x, self.bounds.y))?;
+        Ok(())
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = size;
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char(c) => {
+                self.input.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.input.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.len(),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(sut: &mut PromptComponent, code: KeyCode) {
+        sut.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE))
+            .expect("failed to handle key");
+    }
+
+    #[test]
+    fn test_editing_input() {
+        let mut sut = PromptComponent::new("Name:", "logs.zip");
+
+        sut.set_cursor(4);
+        press(&mut sut, KeyCode::Char('2'));
+        press(&mut sut, KeyCode::End);
+        press(&mut sut, KeyCode::Backspace);
+        press(&mut sut, KeyCode::Home);
+        press(&mut sut, KeyCode::Delete);
+
+        assert_eq!(sut.value(), "ogs2.zi");
+    }
+}
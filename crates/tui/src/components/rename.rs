@@ -0,0 +1,296 @@
+use std::io;
+use std::path::PathBuf;
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{KeyCode, KeyEvent};
+use glm::{Case, RenamePattern, RenamePreview};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+
+use super::prompt::PromptComponent;
+use super::Component;
+
+/// Most previewed names displayed at once.
+const MAX_PREVIEW: usize = 12;
+/// Text fields, in order, followed by the case selector.
+const FIND: usize = 0;
+const REPLACE: usize = 1;
+const START: usize = 2;
+const PADDING: usize = 3;
+const EXTENSION: usize = 4;
+const CASE: usize = 5;
+
+/// A dialog to rename the selected items with a pattern, previewing the
+/// new names as the pattern is typed.
+#[derive(Debug)]
+pub struct RenameComponent {
+    paths: Vec<PathBuf>,
+    fields: Vec<PromptComponent>,
+    case: Option<Case>,
+    /// Field under the cursor, the case selector comes last
+    focus: usize,
+    /// New names for the current pattern, or why it's invalid
+    preview: Result<Vec<RenamePreview>, String>,
+    bounds: Rect,
+}
+
+impl RenameComponent {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let fields = [
+            ("Find (regex):", ""),
+            ("Replace ($1, {n}):", ""),
+            ("Start {n} at:", "1"),
+            ("Digits of {n}:", "1"),
+            ("Extension (. drops):", ""),
+        ]
+        .map(|(question, initial)| PromptComponent::new(format!("{question:<21}"), initial));
+        Self {
+            paths,
+            fields: fields.into(),
+            case: None,
+            focus: FIND,
+            preview: Ok(vec![]),
+            bounds: Rect::default(),
+        }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The pattern of the typed fields.
+    pub fn pattern(&self) -> Result<RenamePattern, String> {
+        let number = |field: usize, name: &str| {
+            let value = self.fields[field].value();
+            value
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| format!("{name} must be a number, not {value:?}"))
+        };
+        let extension = match self.fields[EXTENSION].value() {
+            extension if extension.is_empty() => None,
+            extension if extension == "." => Some(String::new()),
+            extension => Some(extension.trim_start_matches('.').to_string()),
+        };
+        Ok(RenamePattern {
+            find: self.fields[FIND].value(),
+            replace: self.fields[REPLACE].value(),
+            start: number(START, "start")?,
+            padding: number(PADDING, "digits")? as usize,
+            case: self.case,
+            extension,
+        })
+    }
+
+    pub fn set_preview(&mut self, preview: Result<Vec<RenamePreview>, String>) {
+        self.preview = preview;
+    }
+
+    /// Items that would be renamed, failing when the pattern is invalid or
+    /// when any of them can't take its new name.
+    pub fn changes(&self) -> Result<Vec<&RenamePreview>, String> {
+        let previews = self.preview.as_ref().map_err(String::clone)?;
+        if let Some(preview) = previews.iter().find(|preview| preview.conflict.is_some()) {
+            let name = preview.to.file_name().unwrap_or_default().to_string_lossy();
+            let conflict = preview.conflict.as_ref().expect("has a conflict");
+            return Err(format!("{name} {conflict}"));
+        }
+        Ok(previews.iter().filter(|p| p.is_changed()).collect())
+    }
+
+    fn cycle_case(&mut self, forward: bool) {
+        let options = [None]
+            .into_iter()
+            .chain(Case::ALL.map(Some))
+            .collect::<Vec<_>>();
+        let current = options.iter().position(|case| *case == self.case);
+        let current = current.unwrap_or_default();
+        let next = match forward {
+            true => (current + 1) % options.len(),
+            false => (current + options.len() - 1) % options.len(),
+        };
+        self.case = options[next];
+    }
+
+    fn popup(&self, area: Rect) -> Rect {
+        let width = area.width.saturating_sub(8).max(area.width.min(20));
+        let previews = self.preview.as_ref().map_or(1, |previews| previews.len());
+        let height = (CASE as u16 + 1) + 1 + previews.clamp(1, MAX_PREVIEW) as u16 + 2;
+        let height = height.min(area.height);
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    fn preview_lines(&self) -> Vec<Line<'static>> {
+        let previews = match &self.preview {
+            Ok(previews) => previews,
+            Err(e) => return vec![Line::from(e.clone().red())],
+        };
+        let name = |path: &PathBuf| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        };
+        let mut lines = previews
+            .iter()
+            .take(MAX_PREVIEW)
+            .map(|preview| {
+                let from = Span::raw(name(&preview.from));
+                match (&preview.conflict, preview.is_changed()) {
+                    (Some(conflict), _) => Line::from(vec![
+                        from,
+                        " -> ".gray(),
+                        name(&preview.to).red(),
+                        format!("  {conflict}").red().dim(),
+                    ]),
+                    (None, true) => {
+                        Line::from(vec![from, " -> ".gray(), name(&preview.to).green()])
+                    }
+                    (None, false) => Line::from(from.dim()),
+                }
+            })
+            .collect::<Vec<_>>();
+        if previews.len() > MAX_PREVIEW {
+            let more = format!("... and {} more", previews.len() - MAX_PREVIEW);
+            *lines.last_mut().expect("there are previews") = Line::from(more.gray());
+        }
+        lines
+    }
+}
+
+impl Component for RenameComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        let popup = self.popup(area);
+        self.bounds = popup;
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(Color::Yellow))
+            .title(Line::from(
+                format!(" Rename {} item(s) ", self.paths.len()).yellow(),
+            ))
+            .title_bottom(Line::from(
+                " Tab next field, Left/Right change case, Enter to rename ".gray(),
+            ));
+        let inner = block.inner(popup);
+        f.render_widget(Clear, popup);
+        f.render_widget(block, popup);
+
+        let row = |i: usize| Rect {
+            x: inner.x + 1,
+            y: inner.y + i as u16,
+            width: inner.width.saturating_sub(2),
+            height: 1,
+        };
+        for (i, field) in self.fields.iter_mut().enumerate() {
+            if row(i).y < inner.bottom() {
+                field.draw(f, row(i))?;
+            }
+        }
+        let case = match self.case {
+            Some(case) => case.to_string(),
+            None => "unchanged".into(),
+        };
+        let case = Line::from(vec![format!("{:<21} ", "Case:").cyan(), case.into()]);
+        if row(CASE).y < inner.bottom() {
+            f.render_widget(Paragraph::new(case), row(CASE));
+        }
+
+        let preview = Rect {
+            y: row(CASE).y + 2,
+            height: inner.bottom().saturating_sub(row(CASE).y + 2),
+            ..row(CASE)
+        };
+        f.render_widget(Paragraph::new(self.preview_lines()), preview);
+        Ok(())
+    }
+
+    fn tick(&mut self) -> anyhow::Result<()> {
+        match self.fields.get_mut(self.focus) {
+            Some(field) => field.tick(),
+            None => {
+                // on the case selector, right before its value
+                let x = self.bounds.x + 2 + 22;
+                let y = self.bounds.y + 1 + CASE as u16;
+                crossterm::execute!(io::stdout(), MoveTo(x, y))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = self.popup(size);
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Tab | KeyCode::Down => self.focus = (self.focus + 1) % (CASE + 1),
+            KeyCode::BackTab | KeyCode::Up => self.focus = (self.focus + CASE) % (CASE + 1),
+            KeyCode::Left if self.focus == CASE => self.cycle_case(false),
+            KeyCode::Right | KeyCode::Char(' ') if self.focus == CASE => self.cycle_case(true),
+            _ => {
+                if let Some(field) = self.fields.get_mut(self.focus) {
+                    field.handle_key_event(event)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn press(sut: &mut RenameComponent, code: KeyCode) {
+        sut.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE))
+            .expect("failed to handle key");
+    }
+
+    #[test]
+    fn test_typed_pattern() {
+        let mut sut = RenameComponent::new(vec!["/pics/IMG_1.JPG".into()]);
+
+        "IMG_"
+            .chars()
+            .for_each(|c| press(&mut sut, KeyCode::Char(c)));
+        press(&mut sut, KeyCode::Tab);
+        "photo-{n}-"
+            .chars()
+            .for_each(|c| press(&mut sut, KeyCode::Char(c)));
+        press(&mut sut, KeyCode::Tab);
+        press(&mut sut, KeyCode::Tab);
+        press(&mut sut, KeyCode::Backspace);
+        press(&mut sut, KeyCode::Char('3'));
+        press(&mut sut, KeyCode::Tab);
+        press(&mut sut, KeyCode::Char('.'));
+        press(&mut sut, KeyCode::Tab);
+        press(&mut sut, KeyCode::Right);
+        press(&mut sut, KeyCode::Right);
+
+        let pattern = sut.pattern().expect("pattern is valid");
+        assert_eq!(pattern.find, "IMG_");
+        assert_eq!(pattern.replace, "photo-{n}-");
+        assert_eq!((pattern.start, pattern.padding), (1, 3));
+        assert_eq!(pattern.extension, Some(String::new()));
+        assert_eq!(pattern.case, Some(Case::Lower));
+
+        press(&mut sut, KeyCode::Up);
+        press(&mut sut, KeyCode::Up);
+        press(&mut sut, KeyCode::Up);
+        press(&mut sut, KeyCode::Char('x'));
+        assert_eq!(
+            sut.pattern().unwrap_err(),
+            "start must be a number, not \"1x\""
+        );
+    }
+}
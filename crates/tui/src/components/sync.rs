@@ -0,0 +1,313 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use glm::{AuditLog, SyncAction, SyncOptions, SyncPlan, SyncProgress, SyncReport};
+
+use crossterm::cursor::MoveTo;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::List;
+use ratatui::Frame;
+
+use super::Component;
+
+/// Events sent by the thread executing a sync.
+#[derive(Debug)]
+enum SyncEvent {
+    Progress(SyncProgress),
+    Finished(SyncReport),
+}
+
+#[derive(Debug)]
+enum SyncState {
+    /// The plan is displayed as a dry run, waiting to be confirmed
+    Planned,
+    Running {
+        events: mpsc::Receiver<SyncEvent>,
+        done: usize,
+    },
+    Finished(SyncReport),
+}
+
+/// Displays the plan of a one way sync between two directories, and
+/// executes it on a background thread once confirmed.
+#[derive(Debug)]
+pub struct SyncComponent {
+    options: SyncOptions,
+    plan: SyncPlan,
+    /// Where the changes of the sync are recorded, when auditing is enabled
+    audit: Option<AuditLog>,
+    state: SyncState,
+    cursor: usize,
+    scroll: usize,
+    bounds: Rect,
+}
+
+fn action_line(action: &SyncAction) -> Line<'static> {
+    let path = action.path().to_string_lossy().to_string();
+    match action {
+        SyncAction::Copy(_) => Line::from(vec!["+ copy   ".green(), path.into()]),
+        SyncAction::Update(_) => Line::from(vec!["~ update ".yellow(), path.into()]),
+        SyncAction::Delete(_) => Line::from(vec!["- delete ".red(), path.into()]),
+    }
+}
+
+impl SyncComponent {
+    pub fn new(src: PathBuf, dst: PathBuf, bounds: Rect) -> anyhow::Result<Self> {
+        let options = SyncOptions::default();
+        let plan = glm::plan_sync(&src, &dst, &options)?;
+        Ok(Self {
+            options,
+            plan,
+            audit: None,
+            state: SyncState::Planned,
+            cursor: 0,
+            scroll: 0,
+            bounds,
+        })
+    }
+
+    /// A short description of the current state, to be displayed as a
+    /// title for the view.
+    pub fn summary(&self) -> String {
+        let count = |f: fn(&SyncAction) -> bool| self.plan.actions.iter().filter(|a| f(a)).count();
+        match &self.state {
+            SyncState::Planned => format!(
+                "Sync {} into {}: {} to copy, {} to update, {} to delete. Enter to run, d to {} extras, c to compare by {}, x to exclude",
+                self.plan.src.display(),
+                self.plan.dst.display(),
+                count(|a| matches!(a, SyncAction::Copy(_))),
+                count(|a| matches!(a, SyncAction::Update(_))),
+                count(|a| matches!(a, SyncAction::Delete(_))),
+                match self.options.delete_extraneous {
+                    true => "keep",
+                    false => "delete",
+                },
+                match self.options.checksum {
+                    true => "modification time",
+                    false => "checksum",
+                },
+            ),
+            SyncState::Running { done, .. } => {
+                format!("Syncing... {done}/{}", self.plan.actions.len())
+            }
+            SyncState::Finished(report) => format!(
+                "Sync finished: {} done, {} failed",
+                report.completed.len(),
+                report.failed.len()
+            ),
+        }
+    }
+
+    /// Records every change of the sync in `audit` once it runs, or stops
+    /// recording them with `None`.
+    pub fn set_audit_log(&mut self, audit: Option<AuditLog>) {
+        self.audit = audit;
+    }
+
+    pub fn src(&self) -> &Path {
+        &self.plan.src
+    }
+
+    pub fn dst(&self) -> &Path {
+        &self.plan.dst
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, SyncState::Running { .. })
+    }
+
+    /// Starts executing the plan on a background thread. Only a plan that
+    /// was not executed yet can be started.
+    pub fn start(&mut self) {
+        if !matches!(self.state, SyncState::Planned) {
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        let plan = self.plan.clone();
+        let audit = self.audit.clone();
+        std::thread::spawn(move || {
+            let progress = |progress| _ = tx.send(SyncEvent::Progress(progress));
+            let report = plan.execute(progress, audit.as_ref());
+            _ = tx.send(SyncEvent::Finished(report));
+        });
+        self.state = SyncState::Running {
+            events: rx,
+            done: 0,
+        };
+    }
+
+    /// Applies the progress reported by the running sync, if any.
+    pub fn poll(&mut self) {
+        let SyncState::Running { events, done } = &mut self.state else {
+            return;
+        };
+        for event in events.try_iter().collect::<Vec<_>>() {
+            match event {
+                SyncEvent::Progress(progress) => *done = progress.done,
+                SyncEvent::Finished(report) => {
+                    self.state = SyncState::Finished(report);
+                    self.move_cursor(0);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Glob patterns of the items left untouched by the sync.
+    pub fn exclude(&self) -> &[String] {
+        &self.options.exclude
+    }
+
+    /// Replaces the excluded patterns and plans the sync again.
+    pub fn set_exclude(&mut self, exclude: Vec<String>) -> anyhow::Result<()> {
+        self.update_options(|options| options.exclude = exclude)
+    }
+
+    /// Changes the options and plans the sync again, the options are kept
+    /// as they were when planning fails.
+    fn update_options<F>(&mut self, update: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut SyncOptions),
+    {
+        if !matches!(self.state, SyncState::Planned) {
+            return Ok(());
+        }
+        let mut options = self.options.clone();
+        update(&mut options);
+        self.plan = glm::plan_sync(&self.plan.src, &self.plan.dst, &options)?;
+        self.options = options;
+        self.move_cursor(0);
+        Ok(())
+    }
+
+    fn lines(&self) -> Vec<Line<'static>> {
+        match &self.state {
+            SyncState::Finished(report) if !report.failed.is_empty() => report
+                .failed
+                .iter()
+                .map(|(action, error)| {
+                    let mut line = action_line(action);
+                    line.spans.push(Span::from(format!("  {error}")).red());
+                    line
+                })
+                .collect(),
+            _ if self.plan.is_empty() => vec![Line::from("Nothing to sync".gray())],
+            _ => self.plan.actions.iter().map(action_line).collect(),
+        }
+    }
+
+    fn move_cursor(&mut self, offset: isize) {
+        let total = self.lines().len();
+        self.cursor = (self.cursor as isize + offset).clamp(0, total as isize - 1) as usize;
+
+        let height = self.bounds.height.max(1) as usize;
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + height {
+            self.scroll = self.cursor + 1 - height;
+        }
+    }
+}
+
+impl Component for SyncComponent {
+    fn draw(&mut self, f: &mut Frame, area: Rect) -> anyhow::Result<()> {
+        self.bounds = area;
+        let lines = self
+            .lines()
+            .into_iter()
+            .skip(self.scroll)
+            .collect::<Vec<_>>();
+        f.render_widget(List::new(lines), area);
+        Ok(())
+    }
+
+    fn tick(&mut self) -> anyhow::Result<()> {
+        let y = (self.cursor - self.scroll) as u16 + self.bounds.y;
+        crossterm::execute!(io::stdout(), MoveTo(self.bounds.x, y))?;
+        Ok(())
+    }
+
+    fn resize(&mut self, size: Rect) -> anyhow::Result<()> {
+        self.bounds = size;
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) -> anyhow::Result<()> {
+        match event.code {
+            KeyCode::Char('j') | KeyCode::Down => self.move_cursor(1),
+            KeyCode::Char('k') | KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Char('d') => self
+                .update_options(|options| options.delete_extraneous = !options.delete_extraneous)?,
+            KeyCode::Char('c') => {
+                self.update_options(|options| options.checksum = !options.checksum)?
+            }
+            KeyCode::Enter => self.start(),
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_sut() -> SyncComponent {
+        SyncComponent {
+            options: SyncOptions::default(),
+            plan: SyncPlan {
+                src: "/src".into(),
+                dst: "/dst".into(),
+                actions: vec![
+                    SyncAction::Copy("a".into()),
+                    SyncAction::Update("b".into()),
+                    SyncAction::Delete("c".into()),
+                ],
+            },
+            audit: None,
+            state: SyncState::Planned,
+            cursor: 0,
+            scroll: 0,
+            bounds: Rect::new(0, 0, 10, 2),
+        }
+    }
+
+    #[test]
+    fn test_summary_counts_actions() {
+        let sut = make_sut();
+
+        let summary = sut.summary();
+
+        assert!(summary.contains("1 to copy, 1 to update, 1 to delete"));
+    }
+
+    #[test]
+    fn test_invalid_exclude_keeps_options() {
+        let mut sut = make_sut();
+
+        let result = sut.set_exclude(vec!["[".into()]);
+
+        assert!(result.is_err());
+        assert!(sut.exclude().is_empty());
+        assert_eq!(sut.plan.actions.len(), 3);
+    }
+
+    #[test]
+    fn test_cursor_scrolls_and_clamps() {
+        let mut sut = make_sut();
+
+        sut.move_cursor(10);
+        assert_eq!((sut.cursor, sut.scroll), (2, 1));
+
+        sut.state = SyncState::Finished(SyncReport {
+            completed: vec![],
+            failed: vec![(SyncAction::Copy("a".into()), "denied".into())],
+        });
+        sut.move_cursor(0);
+        assert_eq!((sut.cursor, sut.scroll), (0, 0));
+    }
+}
@@ -1,12 +1,16 @@
-use std::sync::mpsc;
+use std::sync::mpsc as std_mpsc;
 use std::thread;
 use std::time::Duration;
 
 use crossterm::event::KeyEvent;
 use crossterm::event::{self, Event as CrosstermEvent};
+use tokio::sync::mpsc;
 
 pub enum Event {
     Key(KeyEvent),
+    /// Sent whenever polling times out without any terminal event, so
+    /// background work can be applied even when no key is pressed.
+    Tick,
 }
 
 /// `EventHandler` spawns a new thread to poll for events.
@@ -14,8 +18,22 @@ pub enum Event {
 /// Events are sent via message passing through a channel, and can only be
 /// accessed through the `next()` method.
 pub struct EventHandler {
-    _tx: mpsc::Sender<Event>,
-    rx: mpsc::Receiver<Event>,
+    _tx: mpsc::UnboundedSender<Event>,
+    rx: mpsc::UnboundedReceiver<Event>,
+    pauses: std_mpsc::Sender<Pause>,
+}
+
+/// Asks the polling thread to stop reading the terminal. It confirms on
+/// `paused`, and waits until `resume` is dropped.
+struct Pause {
+    paused: std_mpsc::Sender<()>,
+    resume: std_mpsc::Receiver<()>,
+}
+
+/// Keeps the polling thread from reading the terminal while it's alive,
+/// see [`EventHandler::pause`].
+pub struct Paused {
+    _resume: std_mpsc::Sender<()>,
 }
 
 impl EventHandler {
@@ -28,25 +46,56 @@ impl EventHandler {
     /// * tick_rate: the interval in milliseconds in between attempts to poll for new events.
     pub fn new(tick_rate: u64) -> Self {
         let tick_rate = Duration::from_millis(tick_rate);
-        let (tx, rx) = mpsc::channel();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (pauses, pause_requests) = std_mpsc::channel::<Pause>();
         {
             let sender = tx.clone();
             thread::spawn(move || loop {
+                if let Ok(pause) = pause_requests.try_recv() {
+                    _ = pause.paused.send(());
+                    _ = pause.resume.recv();
+                }
                 if event::poll(tick_rate).expect("failed to poll events") {
                     match event::read().expect("unable to read events") {
                         CrosstermEvent::Key(e) => sender.send(Event::Key(e)),
-                        _ => unimplemented!(),
+                        // other events, like a resize, only need a redraw
+                        _ => sender.send(Event::Tick),
                     }
                     .expect("failed to send terminal event")
+                } else {
+                    sender.send(Event::Tick).expect("failed to send tick event")
                 }
             });
         }
-        Self { _tx: tx, rx }
+        Self {
+            _tx: tx,
+            rx,
+            pauses,
+        }
+    }
+
+    /// Stops reading the terminal until the returned guard is dropped, so
+    /// another program like an editor can take the input. Waits for the
+    /// poll in progress to finish, up to one tick.
+    pub fn pause(&self) -> Paused {
+        let (paused, is_paused) = std_mpsc::channel();
+        let (resume, on_resume) = std_mpsc::channel();
+        let pause = Pause {
+            paused,
+            resume: on_resume,
+        };
+        if self.pauses.send(pause).is_ok() {
+            _ = is_paused.recv();
+        }
+        Paused { _resume: resume }
     }
 
-    /// Returns the next channel on the channel of `Event`s by blocking the thread
-    /// until a new `Event` is received
-    pub fn next(&self) -> anyhow::Result<Event> {
-        Ok(self.rx.recv()?)
+    /// Returns the next `Event` on the channel, waiting without blocking the
+    /// runtime until one is received
+    pub async fn next(&mut self) -> anyhow::Result<Event> {
+        self.rx
+            .recv()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("event channel closed"))
     }
 }
@@ -1,29 +1,53 @@
 mod app;
 mod cli;
+mod clipboard;
 mod components;
 mod events;
+mod format;
 
 use std::io;
 use std::panic;
+use std::path::Path;
+use std::process::Command;
 
 use app::App;
 use events::{Event, EventHandler};
-use glm::FileManager;
+use glm::{AsyncFileManager, AuditLog, Config, LocalFs, SftpFs, SftpLocation, Templates, Vfs};
 
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::{backend::CrosstermBackend, Terminal};
 
-fn main() -> anyhow::Result<()> {
-    let path = crate::cli::parse();
-    let file_manager = FileManager::new(path)?;
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = crate::cli::parse();
+    let path = args.path;
+    let audit = match args.audit {
+        true => Some(AuditLog::in_state_dir()?),
+        false => None,
+    };
+    let templates = Templates::in_config_dir(&Config::load()?);
+    let (file_manager, remote) = match path.starts_with("sftp://") {
+        true => {
+            let remote = SftpFs::connect(&SftpLocation::parse(&path)?)?;
+            let dir = remote.initial_dir()?;
+            let transfers = remote.try_clone()?;
+            let backend: Box<dyn Vfs + Send> = Box::new(remote);
+            let file_manager = AsyncFileManager::with_backend(backend, dir).await?;
+            (file_manager, Some(transfers))
+        }
+        false => {
+            let backend: Box<dyn Vfs + Send> = Box::new(LocalFs);
+            (AsyncFileManager::with_backend(backend, path).await?, None)
+        }
+    };
 
     setup_terminal()?;
 
     let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
-    let events = EventHandler::new(250);
-    let mut app = App::new(file_manager, terminal.size()?)?;
+    let mut events = EventHandler::new(250);
+    let mut app = App::new(file_manager, remote, audit, templates, terminal.size()?).await?;
 
     terminal.clear()?;
 
@@ -36,8 +60,26 @@ fn main() -> anyhow::Result<()> {
         })?;
         app.update_cursor()?;
         terminal.show_cursor()?;
-        match events.next()? {
-            Event::Key(event) => app.handle_key_event(event)?,
+        // directories are listed in the background, so input is handled
+        // while a slow directory is still loading
+        tokio::select! {
+            event = events.next() => match event? {
+                Event::Key(event) => app.handle_key_event(event)?,
+                Event::Tick => app.tick()?,
+            },
+            listing = app.next_listing() => app.apply_listing(listing)?,
+        }
+        if let Some(file) = app.take_editor_request() {
+            // the editor gets the terminal, and its input, until it exits
+            let result = {
+                let _paused = events.pause();
+                reset_terminal()?;
+                let result = run_editor(&file);
+                resume_terminal()?;
+                result
+            };
+            terminal.clear()?;
+            app.editor_closed(result);
         }
     }
 
@@ -58,6 +100,34 @@ fn setup_terminal() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Takes the terminal back after another program used it.
+fn resume_terminal() -> anyhow::Result<()> {
+    enable_raw_mode()?;
+    crossterm::execute!(io::stdout(), EnterAlternateScreen)?;
+    Ok(())
+}
+
+/// Opens `file` with `$VISUAL` or `$EDITOR`, or vi when neither is set,
+/// waiting for it to exit.
+fn run_editor(file: &Path) -> anyhow::Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".into());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(file)
+        .status()
+        .map_err(|e| anyhow::anyhow!("failed to run {editor}: {e}"))?;
+    if !status.success() {
+        anyhow::bail!("{editor} exited with {status}");
+    }
+    Ok(())
+}
+
 fn reset_terminal() -> anyhow::Result<()> {
     disable_raw_mode()?;
     crossterm::execute!(io::stdout(), LeaveAlternateScreen)?;